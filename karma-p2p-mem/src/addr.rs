@@ -0,0 +1,14 @@
+use crate::MemHub;
+
+#[derive(Debug, Clone)]
+pub enum MemAddr {
+    Bootstrap(MemHub),
+
+    Label(String),
+
+    /// Node id of initiator, produced by `start`.
+    Offer(u64),
+
+    /// Node id of answerer, produced when offer is set as remote address.
+    Answer(u64),
+}
@@ -0,0 +1,22 @@
+#[derive(Debug)]
+pub enum Error {
+    ErrAddrType,
+    ErrNodeNotFound(u64),
+    ErrChannelExists,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        std::io::Error::other(format!("{:?}", e))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
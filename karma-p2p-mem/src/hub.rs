@@ -0,0 +1,176 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    task::Waker,
+};
+
+use crate::{stream, Error, MemAddr, MemStream, Result};
+
+#[derive(Default)]
+pub(crate) struct Node {
+    pub(crate) remote: Option<u64>,
+    pub(crate) local_addrs: VecDeque<MemAddr>,
+    pub(crate) addr_waker: Option<Waker>,
+    // Negotiated endpoints waiting for same label and port on remote.
+    pub(crate) channels: HashMap<(String, u16), Arc<Mutex<stream::Endpoint>>>,
+    // Announced streams waiting for remote to be known.
+    pub(crate) announced: Vec<MemStream>,
+    pub(crate) accepted: VecDeque<MemStream>,
+    pub(crate) accept_waker: Option<Waker>,
+}
+
+impl Node {
+    pub(crate) fn push_local_addr(&mut self, addr: MemAddr) {
+        self.local_addrs.push_back(addr);
+
+        if let Some(waker) = self.addr_waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn push_accepted(&mut self, stream: MemStream) {
+        self.accepted.push_back(stream);
+
+        if let Some(waker) = self.accept_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct HubInner {
+    next_id: u64,
+    nodes: HashMap<u64, Arc<Mutex<Node>>>,
+}
+
+/// Shared registry of in-memory sockets.
+///
+/// Sockets bound to same hub can reach each other.
+#[derive(Clone, Default)]
+pub struct MemHub {
+    inner: Arc<Mutex<HubInner>>,
+}
+
+impl fmt::Debug for MemHub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+
+        f.debug_struct("MemHub")
+            .field("nodes", &inner.nodes.len())
+            .finish()
+    }
+}
+
+impl MemHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn register(&self) -> (u64, Arc<Mutex<Node>>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        let node = Arc::new(Mutex::new(Node::default()));
+        inner.nodes.insert(id, node.clone());
+
+        (id, node)
+    }
+
+    pub(crate) fn unregister(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.nodes.remove(&id);
+    }
+
+    /// Set `remote` as remote of `local`, link pending channels and deliver announced streams.
+    pub(crate) fn pair(&self, local: u64, remote: u64) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+
+        let local_node = inner
+            .nodes
+            .get(&local)
+            .ok_or(Error::ErrNodeNotFound(local))?;
+        let remote_node = inner
+            .nodes
+            .get(&remote)
+            .ok_or(Error::ErrNodeNotFound(remote))?;
+
+        if local == remote {
+            return Err(Error::ErrAddrType);
+        }
+
+        let mut local_node = local_node.lock().unwrap();
+        let mut remote_node = remote_node.lock().unwrap();
+
+        local_node.remote = Some(remote);
+
+        let keys: Vec<_> = local_node
+            .channels
+            .keys()
+            .filter(|k| remote_node.channels.contains_key(*k))
+            .cloned()
+            .collect();
+
+        for key in keys {
+            if let (Some(a), Some(b)) = (
+                local_node.channels.remove(&key),
+                remote_node.channels.remove(&key),
+            ) {
+                stream::link(&a, &b);
+            }
+        }
+
+        for s in local_node.announced.drain(..) {
+            remote_node.push_accepted(s);
+        }
+
+        Ok(())
+    }
+
+    /// Open channel from `local`, fails when label and port are already used.
+    pub(crate) fn open(&self, local: u64, label: String, port: u16) -> Result<MemStream> {
+        let inner = self.inner.lock().unwrap();
+
+        let local_node = inner
+            .nodes
+            .get(&local)
+            .ok_or(Error::ErrNodeNotFound(local))?;
+        let mut local_node = local_node.lock().unwrap();
+
+        let remote_node = local_node.remote.and_then(|id| inner.nodes.get(&id));
+
+        if port == 0 {
+            let (s, peer) = MemStream::pair();
+
+            match remote_node {
+                Some(remote_node) => remote_node.lock().unwrap().push_accepted(peer),
+                None => local_node.announced.push(peer),
+            }
+
+            return Ok(s);
+        }
+
+        let key = (label, port);
+
+        if local_node.channels.contains_key(&key) {
+            return Err(Error::ErrChannelExists);
+        }
+
+        let (s, endpoint) = MemStream::new();
+
+        let remote_endpoint =
+            remote_node.and_then(|remote_node| remote_node.lock().unwrap().channels.remove(&key));
+
+        match remote_endpoint {
+            Some(remote_endpoint) => stream::link(&endpoint, &remote_endpoint),
+            None => {
+                local_node.channels.insert(key, endpoint);
+            }
+        }
+
+        Ok(s)
+    }
+}
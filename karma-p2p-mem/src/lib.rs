@@ -0,0 +1,14 @@
+mod hub;
+pub use hub::*;
+
+mod socket;
+pub use socket::*;
+
+mod stream;
+pub use stream::*;
+
+mod addr;
+pub use addr::*;
+
+mod error;
+pub use error::*;
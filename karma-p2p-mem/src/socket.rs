@@ -0,0 +1,300 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_lite::future;
+use karma_p2p::{P2pSocket, P2pSocketEvents, SocketEvent};
+
+use crate::{hub::Node, Error, MemAddr, MemHub, MemStream, Result};
+
+/// In-memory p2p socket.
+///
+/// Sockets exchange node ids as addresses, so an offer and an answer are enough to
+/// pair them. Negotiated channels can be opened before pairing, data written before
+/// remote opens same label and port is buffered.
+pub struct MemSocket {
+    hub: MemHub,
+    id: u64,
+    node: Arc<Mutex<Node>>,
+}
+
+impl MemSocket {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn _connect(&self, label: &MemAddr, port: u16) -> Result<MemStream> {
+        match label {
+            MemAddr::Label(label) => self.hub.open(self.id, label.clone(), port),
+            _ => Err(Error::ErrAddrType),
+        }
+    }
+
+    fn _set_remote_addr(&self, remote: &MemAddr) -> Result<()> {
+        match remote {
+            MemAddr::Offer(id) => {
+                self.hub.pair(self.id, *id)?;
+
+                self.node
+                    .lock()
+                    .unwrap()
+                    .push_local_addr(MemAddr::Answer(self.id));
+
+                Ok(())
+            }
+            MemAddr::Answer(id) => self.hub.pair(self.id, *id),
+            _ => Err(Error::ErrAddrType),
+        }
+    }
+}
+
+impl Drop for MemSocket {
+    fn drop(&mut self) {
+        self.hub.unregister(self.id);
+    }
+}
+
+impl P2pSocket for MemSocket {
+    type Stream = MemStream;
+
+    type Addr = MemAddr;
+
+    type Error = Error;
+
+    type Binding = future::Ready<Result<Self>>;
+
+    type Connecting = future::Ready<Result<MemStream>>;
+
+    type SettingRemoteAddr = future::Ready<Result<()>>;
+
+    fn binding(bootstrap: Self::Addr) -> Self::Binding {
+        let hub = match bootstrap {
+            MemAddr::Bootstrap(hub) => hub,
+            _ => return future::ready(Err(Error::ErrAddrType)),
+        };
+
+        let (id, node) = hub.register();
+
+        future::ready(Ok(Self { hub, id, node }))
+    }
+
+    fn connecting(&self, label: Self::Addr, port: u16) -> Self::Connecting {
+        future::ready(self._connect(&label, port))
+    }
+
+    fn poll_accept(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Result<Self::Stream>> {
+        let mut node = self.node.lock().unwrap();
+
+        match node.accepted.pop_front() {
+            Some(s) => Poll::Ready(Ok(s)),
+            None => {
+                node.accept_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let id = self.id;
+
+        self.node
+            .lock()
+            .unwrap()
+            .push_local_addr(MemAddr::Offer(id));
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_fetch_local_addr(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Addr>> {
+        let mut node = self.node.lock().unwrap();
+
+        match node.local_addrs.pop_front() {
+            Some(addr) => Poll::Ready(Ok(addr)),
+            None => {
+                node.addr_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn setting_remote_addr(&self, remote: Self::Addr) -> Self::SettingRemoteAddr {
+        future::ready(self._set_remote_addr(&remote))
+    }
+
+    fn is_last_addr(addr: &Self::Addr) -> bool {
+        matches!(addr, MemAddr::Offer(_) | MemAddr::Answer(_))
+    }
+}
+
+/// Sockets are paired without connection state, so no event is reported.
+impl P2pSocketEvents for MemSocket {
+    fn poll_event(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{future::block_on, AsyncReadExt, AsyncWriteExt};
+    use karma_p2p::{
+        testkit::{self, Harness},
+        P2pMessageStreamExt, P2pSocketExt,
+    };
+
+    use super::*;
+
+    fn pair(hub: &MemHub) -> (MemSocket, MemSocket) {
+        block_on(async {
+            let bootstrap = MemAddr::Bootstrap(hub.clone());
+
+            let mut a = MemSocket::bind(bootstrap.clone()).await.unwrap();
+            let mut b = MemSocket::bind(bootstrap.clone()).await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            (a, b)
+        })
+    }
+
+    #[test]
+    fn negotiated_before_pairing() {
+        block_on(async {
+            let hub = MemHub::new();
+            let bootstrap = MemAddr::Bootstrap(hub.clone());
+            let label = MemAddr::Label("data".into());
+
+            let mut a = MemSocket::bind(bootstrap.clone()).await.unwrap();
+            let mut b = MemSocket::bind(bootstrap.clone()).await.unwrap();
+
+            let mut sa = a.connect(label.clone(), 1).await.unwrap();
+            sa.write_all(b"hello").await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let mut sb = b.connect(label.clone(), 1).await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            sb.write_all(b"world").await.unwrap();
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"world");
+        })
+    }
+
+    #[test]
+    fn announced_to_accept() {
+        let hub = MemHub::new();
+        let (a, b) = pair(&hub);
+
+        block_on(async {
+            let label = MemAddr::Label("data".into());
+
+            let mut sa = a.connect(label.clone(), 0).await.unwrap();
+            let mut sb = b.accept().await.unwrap();
+
+            sa.send_message(bytes::Bytes::from_static(b"ping"))
+                .await
+                .unwrap();
+            sa.send_message(bytes::Bytes::from_static(b"pong"))
+                .await
+                .unwrap();
+
+            assert_eq!(sb.recv_message().await.unwrap().unwrap().as_ref(), b"ping");
+            assert_eq!(sb.recv_message().await.unwrap().unwrap().as_ref(), b"pong");
+        })
+    }
+
+    #[test]
+    fn close_reports_eof() {
+        let hub = MemHub::new();
+        let (a, b) = pair(&hub);
+
+        block_on(async {
+            let label = MemAddr::Label("data".into());
+
+            let mut sa = a.connect(label.clone(), 2).await.unwrap();
+            let mut sb = b.connect(label.clone(), 2).await.unwrap();
+
+            sa.write_all(b"bye").await.unwrap();
+            sa.close().await.unwrap();
+
+            let mut buf = Vec::new();
+            sb.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, b"bye");
+
+            assert!(sa.write_all(b"more").await.is_err());
+        })
+    }
+
+    #[test]
+    fn duplicate_channel() {
+        let hub = MemHub::new();
+        let (a, _b) = pair(&hub);
+
+        block_on(async {
+            let label = MemAddr::Label("data".into());
+
+            let _s = a.connect(label.clone(), 3).await.unwrap();
+            assert!(matches!(
+                a.connect(label.clone(), 3).await,
+                Err(Error::ErrChannelExists)
+            ));
+        })
+    }
+
+    struct MemHarness(MemHub);
+
+    impl Harness for MemHarness {
+        type Socket = MemSocket;
+
+        type Stream = MemStream;
+
+        type Addr = MemAddr;
+
+        type Error = Error;
+
+        fn bootstrap(&self) -> MemAddr {
+            MemAddr::Bootstrap(self.0.clone())
+        }
+
+        fn label(&self, name: &str) -> MemAddr {
+            MemAddr::Label(String::from(name))
+        }
+
+        fn wrong_addr(&self) -> MemAddr {
+            MemAddr::Bootstrap(self.0.clone())
+        }
+
+        fn is_offer(&self, addr: &MemAddr) -> bool {
+            matches!(addr, MemAddr::Offer(_))
+        }
+
+        fn is_answer(&self, addr: &MemAddr) -> bool {
+            matches!(addr, MemAddr::Answer(_))
+        }
+
+        fn is_addr_type_error(&self, err: &Error) -> bool {
+            matches!(err, Error::ErrAddrType)
+        }
+    }
+
+    #[test]
+    fn conformance() {
+        block_on(testkit::check_all(&MemHarness(MemHub::new())));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    cmp,
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use bytes::{Buf, Bytes};
+use futures_lite::{AsyncRead, AsyncWrite};
+use karma_p2p::P2pMessageStream;
+
+/// One side of in-memory stream.
+#[derive(Debug, Default)]
+pub(crate) struct Endpoint {
+    inbound: VecDeque<Bytes>,
+    waker: Option<Waker>,
+    peer: Option<Weak<Mutex<Endpoint>>>,
+    // Written before linked to peer.
+    pending: VecDeque<Bytes>,
+    closed: bool,
+    peer_closed: bool,
+}
+
+impl Endpoint {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Link two endpoints and deliver everything written before.
+pub(crate) fn link(a: &Arc<Mutex<Endpoint>>, b: &Arc<Mutex<Endpoint>>) {
+    let (a_pending, a_closed) = {
+        let mut a = a.lock().unwrap();
+        a.peer = Some(Arc::downgrade(b));
+        (std::mem::take(&mut a.pending), a.closed)
+    };
+
+    let (b_pending, b_closed) = {
+        let mut b = b.lock().unwrap();
+        b.peer = Some(Arc::downgrade(a));
+        b.inbound.extend(a_pending);
+        b.peer_closed |= a_closed;
+        b.wake();
+        (std::mem::take(&mut b.pending), b.closed)
+    };
+
+    let mut a = a.lock().unwrap();
+    a.inbound.extend(b_pending);
+    a.peer_closed |= b_closed;
+    a.wake();
+}
+
+pub struct MemStream {
+    endpoint: Arc<Mutex<Endpoint>>,
+}
+
+impl MemStream {
+    pub(crate) fn new() -> (Self, Arc<Mutex<Endpoint>>) {
+        let endpoint = Arc::new(Mutex::new(Endpoint::default()));
+
+        let stream = Self {
+            endpoint: endpoint.clone(),
+        };
+
+        (stream, endpoint)
+    }
+
+    /// Create two linked streams.
+    pub(crate) fn pair() -> (Self, Self) {
+        let (a, ea) = Self::new();
+        let (b, eb) = Self::new();
+
+        link(&ea, &eb);
+
+        (a, b)
+    }
+
+    fn send(&self, message: Bytes) -> std::io::Result<()> {
+        let peer = {
+            let mut endpoint = self.endpoint.lock().unwrap();
+
+            if endpoint.closed {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+
+            match &endpoint.peer {
+                Some(peer) => peer.clone(),
+                None => {
+                    endpoint.pending.push_back(message);
+                    return Ok(());
+                }
+            }
+        };
+
+        let peer = peer
+            .upgrade()
+            .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+
+        let mut peer = peer.lock().unwrap();
+
+        peer.inbound.push_back(message);
+        peer.wake();
+
+        Ok(())
+    }
+
+    fn close(&self) {
+        let peer = {
+            let mut endpoint = self.endpoint.lock().unwrap();
+
+            if endpoint.closed {
+                return;
+            }
+
+            endpoint.closed = true;
+            endpoint.peer.clone()
+        };
+
+        if let Some(peer) = peer.and_then(|p| p.upgrade()) {
+            let mut peer = peer.lock().unwrap();
+
+            peer.peer_closed = true;
+            peer.wake();
+        }
+    }
+}
+
+impl Drop for MemStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl AsyncRead for MemStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        let mut endpoint = self.endpoint.lock().unwrap();
+
+        while let Some(data) = endpoint.inbound.front_mut() {
+            if data.is_empty() {
+                endpoint.inbound.pop_front();
+                continue;
+            }
+
+            let size = cmp::min(buf.len(), data.len());
+
+            buf[..size].copy_from_slice(&data[..size]);
+            data.advance(size);
+
+            if data.is_empty() {
+                endpoint.inbound.pop_front();
+            }
+
+            return Poll::Ready(Ok(size));
+        }
+
+        if endpoint.peer_closed {
+            Poll::Ready(Ok(0))
+        } else {
+            endpoint.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncWrite for MemStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.send(Bytes::copy_from_slice(buf)).map(|_| buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl P2pMessageStream for MemStream {
+    type Error = std::io::Error;
+
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::io::Result<Bytes>>> {
+        let mut endpoint = self.endpoint.lock().unwrap();
+
+        if let Some(data) = endpoint.inbound.pop_front() {
+            Poll::Ready(Some(Ok(data)))
+        } else if endpoint.peer_closed {
+            Poll::Ready(None)
+        } else {
+            endpoint.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn poll_send_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Bytes) -> std::io::Result<()> {
+        self.send(message)
+    }
+
+    fn poll_send_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_send_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.close();
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,44 @@
+use std::net::SocketAddr;
+
+use karma_p2p::{Keypair, PeerId};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum QuicAddr {
+    /// Local udp address to bind and identity proven by certificate.
+    #[serde(skip)]
+    Bootstrap(SocketAddr, Keypair),
+
+    #[serde(skip)]
+    Label(String),
+
+    /// Address and identity of initiator, produced by `start`.
+    Offer(
+        SocketAddr,
+        #[serde(
+            serialize_with = "serialize_peer",
+            deserialize_with = "deserialize_peer"
+        )]
+        PeerId,
+    ),
+
+    /// Address and identity of answerer, produced when offer is set as remote address.
+    Answer(
+        SocketAddr,
+        #[serde(
+            serialize_with = "serialize_peer",
+            deserialize_with = "deserialize_peer"
+        )]
+        PeerId,
+    ),
+}
+
+fn serialize_peer<S: Serializer>(peer: &PeerId, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(peer)
+}
+
+fn deserialize_peer<'de, D: Deserializer<'de>>(d: D) -> Result<PeerId, D::Error> {
+    String::deserialize(d)?
+        .parse()
+        .map_err(|_| serde::de::Error::custom("invalid peer id"))
+}
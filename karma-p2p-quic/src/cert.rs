@@ -0,0 +1,168 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use karma_p2p::{Keypair, PeerId};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    server::{ClientCertVerified, ClientCertVerifier},
+    Certificate, CertificateError, DistinguishedName, PrivateKey, ServerName,
+};
+use x509_parser::oid_registry::OID_SIG_ED25519;
+
+use crate::Result;
+
+const ALPN: &[u8] = b"karma";
+
+/// Name in certificates, peers are verified by key instead.
+pub(crate) const SERVER_NAME: &str = "karma";
+
+/// PKCS#8 v1 prefix of ed25519 private key, followed by 32 bytes secret.
+const PKCS8_ED25519_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// Self-signed certificate with identity key as its key, so the TLS handshake proves
+/// the identity.
+fn certificate(keypair: &Keypair) -> Result<(Certificate, PrivateKey)> {
+    let mut der = PKCS8_ED25519_PREFIX.to_vec();
+    der.extend(keypair.to_bytes());
+
+    let mut params = rcgen::CertificateParams::new(vec![String::from(SERVER_NAME)]);
+    params.alg = &rcgen::PKCS_ED25519;
+    params.key_pair = Some(rcgen::KeyPair::from_der(&der)?);
+
+    let cert = rcgen::Certificate::from_params(params)?;
+
+    Ok((Certificate(cert.serialize_der()?), PrivateKey(der)))
+}
+
+/// Peer of ed25519 key in `cert`.
+fn peer_id_of(cert: &Certificate) -> std::result::Result<PeerId, rustls::Error> {
+    let bad = || rustls::Error::InvalidCertificate(CertificateError::BadEncoding);
+
+    let (_, cert) = x509_parser::parse_x509_certificate(&cert.0).map_err(|_| bad())?;
+
+    let spki = cert.public_key();
+
+    if spki.algorithm.algorithm != OID_SIG_ED25519 {
+        return Err(bad());
+    }
+
+    let key = spki
+        .subject_public_key
+        .data
+        .as_ref()
+        .try_into()
+        .map_err(|_| bad())?;
+
+    PeerId::from_bytes(key).map_err(|_| bad())
+}
+
+/// Accept only certificate of expected remote peer, in both client and server role.
+///
+/// Handshake signatures are checked against the same key by default methods.
+pub(crate) struct PeerVerifier {
+    remote: Arc<Mutex<Option<PeerId>>>,
+}
+
+impl PeerVerifier {
+    fn verify_peer(&self, cert: &Certificate) -> std::result::Result<(), rustls::Error> {
+        let peer = peer_id_of(cert)?;
+
+        if *self.remote.lock().unwrap() != Some(peer) {
+            return Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl ServerCertVerifier for PeerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        self.verify_peer(end_entity)?;
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+impl ClientCertVerifier for PeerVerifier {
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> std::result::Result<ClientCertVerified, rustls::Error> {
+        self.verify_peer(end_entity)?;
+
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// Config of accepting connection from `remote`.
+pub(crate) fn server_config(
+    keypair: &Keypair,
+    remote: Arc<Mutex<Option<PeerId>>>,
+) -> Result<quinn::ServerConfig> {
+    let (cert, key) = certificate(keypair)?;
+
+    let mut tls = rustls::ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_client_cert_verifier(Arc::new(PeerVerifier { remote }))
+        .with_single_cert(vec![cert], key)?;
+
+    tls.alpn_protocols = vec![ALPN.to_vec()];
+
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(tls)))
+}
+
+/// Config of connecting to `remote`.
+pub(crate) fn client_config(
+    keypair: &Keypair,
+    remote: Arc<Mutex<Option<PeerId>>>,
+) -> Result<quinn::ClientConfig> {
+    let (cert, key) = certificate(keypair)?;
+
+    let mut tls = rustls::ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_custom_certificate_verifier(Arc::new(PeerVerifier { remote }))
+        .with_client_auth_cert(vec![cert], key)?;
+
+    tls.alpn_protocols = vec![ALPN.to_vec()];
+
+    Ok(quinn::ClientConfig::new(Arc::new(tls)))
+}
+
+#[cfg(test)]
+mod tests {
+    use karma_p2p::Keypair;
+
+    use super::{certificate, peer_id_of};
+
+    #[test]
+    fn certificate_of_identity() {
+        let keypair = Keypair::generate();
+
+        let (cert, _) = certificate(&keypair).unwrap();
+        assert_eq!(peer_id_of(&cert).unwrap(), keypair.peer_id());
+    }
+}
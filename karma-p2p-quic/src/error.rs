@@ -0,0 +1,73 @@
+#[derive(Debug)]
+pub enum Error {
+    ErrAddrType,
+    ErrChannelClosed,
+    ErrInvalidHeader,
+    IoError(std::io::Error),
+    TlsError(rustls::Error),
+    CertError(rcgen::RcgenError),
+    ConnectError(quinn::ConnectError),
+    ConnectionError(quinn::ConnectionError),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl From<rustls::Error> for Error {
+    fn from(e: rustls::Error) -> Self {
+        Error::TlsError(e)
+    }
+}
+
+impl From<rcgen::RcgenError> for Error {
+    fn from(e: rcgen::RcgenError) -> Self {
+        Error::CertError(e)
+    }
+}
+
+impl From<quinn::ConnectError> for Error {
+    fn from(e: quinn::ConnectError) -> Self {
+        Error::ConnectError(e)
+    }
+}
+
+impl From<quinn::ConnectionError> for Error {
+    fn from(e: quinn::ConnectionError) -> Self {
+        Error::ConnectionError(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            Error::TlsError(e) => Some(e),
+            Error::CertError(e) => Some(e),
+            Error::ConnectError(e) => Some(e),
+            Error::ConnectionError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::ErrChannelClosed => std::io::ErrorKind::BrokenPipe.into(),
+            Error::IoError(e) => e,
+            Error::ConnectionError(e) => e.into(),
+            e => std::io::Error::other(format!("{:?}", e)),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
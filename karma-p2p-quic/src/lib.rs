@@ -0,0 +1,13 @@
+mod socket;
+pub use socket::*;
+
+mod stream;
+pub use stream::*;
+
+mod cert;
+
+mod addr;
+pub use addr::*;
+
+mod error;
+pub use error::*;
@@ -0,0 +1,748 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, ready};
+use karma_p2p::{Keypair, P2pSocket, P2pSocketEvents, PeerId, SocketEvent};
+use quinn::{Connection, Endpoint, RecvStream, SendStream};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    oneshot, watch,
+};
+
+use crate::{
+    cert::{self, SERVER_NAME},
+    Error, QuicAddr, QuicStream, Result,
+};
+
+type OpFuture<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+/// Remote stream must send its header within it, or it's dropped.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Negotiated remote stream not claimed by local `connect` within it is dropped.
+const UNCLAIMED_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+enum ConnState {
+    Connecting,
+
+    /// Established connection, and whether local side dialed it.
+    Connected(Connection, bool),
+
+    Closed,
+}
+
+/// Negotiated streams of one label and port, whichever of remote stream and local
+/// `connect` comes first waits for the other. Each side is queued in order.
+#[derive(Default)]
+struct Slot {
+    waiting: VecDeque<oneshot::Sender<(SendStream, RecvStream)>>,
+    arrived: VecDeque<(SendStream, RecvStream, Instant)>,
+}
+
+struct Shared {
+    conn: watch::Sender<ConnState>,
+    slots: Mutex<HashMap<(String, u16), Slot>>,
+    accept_tx: UnboundedSender<QuicStream>,
+}
+
+impl Shared {
+    async fn connection(&self) -> Result<(Connection, bool)> {
+        let mut rx = self.conn.subscribe();
+
+        let state = rx
+            .wait_for(|s| !matches!(s, ConnState::Connecting))
+            .await
+            .map_err(|_| Error::ErrChannelClosed)?;
+
+        match &*state {
+            ConnState::Connected(conn, dialer) => Ok((conn.clone(), *dialer)),
+            _ => Err(Error::ErrChannelClosed),
+        }
+    }
+
+    fn established(self: &Arc<Self>, conn: Connection, dialer: bool) {
+        self.conn
+            .send_replace(ConnState::Connected(conn.clone(), dialer));
+
+        tokio::spawn(accept_streams(self.clone(), conn));
+    }
+
+    fn deliver(&self, label: String, port: u16, send: SendStream, recv: RecvStream) {
+        let mut slots = self.slots.lock().unwrap();
+
+        expire(&mut slots, Instant::now());
+
+        let slot = slots.entry((label, port)).or_default();
+        let mut streams = (send, recv);
+
+        // Local stream may be dropped already, then next one takes it.
+        while let Some(tx) = slot.waiting.pop_front() {
+            match tx.send(streams) {
+                Ok(()) => return,
+                Err(s) => streams = s,
+            }
+        }
+
+        slot.arrived
+            .push_back((streams.0, streams.1, Instant::now()));
+    }
+
+    /// Take first remote stream arrived for `label` and `port`, or wait for next.
+    fn claim(
+        &self,
+        label: String,
+        port: u16,
+    ) -> std::result::Result<(SendStream, RecvStream), oneshot::Receiver<(SendStream, RecvStream)>>
+    {
+        let mut slots = self.slots.lock().unwrap();
+
+        expire(&mut slots, Instant::now());
+
+        let slot = slots.entry((label, port)).or_default();
+
+        if let Some((send, recv, _)) = slot.arrived.pop_front() {
+            return Ok((send, recv));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        slot.waiting.push_back(tx);
+
+        Err(rx)
+    }
+}
+
+/// Drop remote streams unclaimed for `UNCLAIMED_TTL` and local ones gone.
+fn expire(slots: &mut HashMap<(String, u16), Slot>, now: Instant) {
+    slots.retain(|_, slot| {
+        slot.waiting.retain(|tx| !tx.is_closed());
+        slot.arrived
+            .retain(|(.., at)| now.saturating_duration_since(*at) < UNCLAIMED_TTL);
+
+        !slot.waiting.is_empty() || !slot.arrived.is_empty()
+    });
+}
+
+/// P2p socket over QUIC.
+///
+/// Initiator sends an offer with its address and identity, answerer accepts one
+/// connection from it and replies with its own, then initiator dials. Both sides
+/// verify remote certificate is made by identity in the address. Must be used
+/// within tokio runtime.
+pub struct QuicSocket {
+    endpoint: Endpoint,
+    keypair: Keypair,
+    remote: Arc<Mutex<Option<PeerId>>>,
+    shared: Arc<Shared>,
+    addr_tx: UnboundedSender<QuicAddr>,
+    addr_rx: UnboundedReceiver<QuicAddr>,
+    accept_rx: Mutex<UnboundedReceiver<QuicStream>>,
+}
+
+impl QuicSocket {
+    fn _bind(bootstrap: &QuicAddr) -> Result<Self> {
+        let (bind, keypair) = match bootstrap {
+            QuicAddr::Bootstrap(bind, keypair) => (*bind, keypair.clone()),
+            _ => return Err(Error::ErrAddrType),
+        };
+
+        let remote = Arc::new(Mutex::new(None));
+
+        let endpoint = Endpoint::server(cert::server_config(&keypair, remote.clone())?, bind)?;
+
+        let (addr_tx, addr_rx) = unbounded_channel();
+        let (accept_tx, accept_rx) = unbounded_channel();
+
+        let shared = Arc::new(Shared {
+            conn: watch::Sender::new(ConnState::Connecting),
+            slots: Mutex::new(HashMap::new()),
+            accept_tx,
+        });
+
+        Ok(Self {
+            endpoint,
+            keypair,
+            remote,
+            shared,
+            addr_tx,
+            addr_rx,
+            accept_rx: Mutex::new(accept_rx),
+        })
+    }
+
+    pub fn local_peer_id(&self) -> PeerId {
+        self.keypair.peer_id()
+    }
+
+    /// Identity of remote, `None` until connection is established.
+    pub fn remote_peer_id(&self) -> Option<PeerId> {
+        match &*self.shared.conn.borrow() {
+            ConnState::Connected(..) => *self.remote.lock().unwrap(),
+            _ => None,
+        }
+    }
+
+    fn local_addr(&self, offer: bool) -> Result<QuicAddr> {
+        let addr = reachable_addr(self.endpoint.local_addr()?);
+        let peer = self.keypair.peer_id();
+
+        Ok(if offer {
+            QuicAddr::Offer(addr, peer)
+        } else {
+            QuicAddr::Answer(addr, peer)
+        })
+    }
+
+    /// Open stream `label`.
+    ///
+    /// Non-zero `port` is negotiated, both sides must connect with the same `label`
+    /// and `port`, dialing side opens it. Port `0` announces the stream to the
+    /// remote, which receives it from `accept`.
+    async fn _connect(
+        shared: Arc<Shared>,
+        label: String,
+        port: u16,
+    ) -> Result<(SendStream, RecvStream)> {
+        let (conn, dialer) = shared.connection().await?;
+
+        if port != 0 && !dialer {
+            return match shared.claim(label, port) {
+                Ok(streams) => Ok(streams),
+                Err(rx) => rx.await.map_err(|_| Error::ErrChannelClosed),
+            };
+        }
+
+        let (mut send, recv) = conn.open_bi().await?;
+
+        send.write_all(&encode_header(&label, port))
+            .await
+            .map_err(|e| Error::IoError(e.into()))?;
+
+        Ok((send, recv))
+    }
+
+    async fn _dial(
+        endpoint: Endpoint,
+        config: quinn::ClientConfig,
+        shared: Arc<Shared>,
+        addr: std::net::SocketAddr,
+    ) -> Result<()> {
+        let conn = endpoint.connect_with(config, addr, SERVER_NAME)?.await?;
+
+        shared.established(conn, true);
+
+        Ok(())
+    }
+
+    fn _set_remote_addr(&self, remote: &QuicAddr) -> Result<Option<OpFuture<()>>> {
+        match remote {
+            QuicAddr::Offer(_, peer) => {
+                *self.remote.lock().unwrap() = Some(*peer);
+
+                tokio::spawn(accept_connection(
+                    self.endpoint.clone(),
+                    self.shared.clone(),
+                ));
+
+                let answer = self.local_addr(false)?;
+                let _ = self.addr_tx.send(answer);
+
+                Ok(None)
+            }
+            QuicAddr::Answer(addr, peer) => {
+                *self.remote.lock().unwrap() = Some(*peer);
+
+                let config = cert::client_config(&self.keypair, self.remote.clone())?;
+
+                Ok(Some(Box::pin(Self::_dial(
+                    self.endpoint.clone(),
+                    config,
+                    self.shared.clone(),
+                    *addr,
+                ))))
+            }
+            _ => Err(Error::ErrAddrType),
+        }
+    }
+}
+
+impl Drop for QuicSocket {
+    fn drop(&mut self) {
+        self.shared.conn.send_replace(ConnState::Closed);
+        self.endpoint.close(0u32.into(), b"closed");
+    }
+}
+
+/// Accept first connection made by expected remote, certificates of others are
+/// rejected during handshake.
+async fn accept_connection(endpoint: Endpoint, shared: Arc<Shared>) {
+    while let Some(incoming) = endpoint.accept().await {
+        match incoming.await {
+            Ok(conn) => return shared.established(conn, false),
+            Err(e) => log::warn!("Reject incoming connection: {:?}", e),
+        }
+    }
+}
+
+/// Route streams opened by remote until connection closes.
+async fn accept_streams(shared: Arc<Shared>, conn: Connection) {
+    while let Ok((send, recv)) = conn.accept_bi().await {
+        tokio::spawn(route_stream(shared.clone(), send, recv));
+    }
+}
+
+/// Read header of remote stream and hand it over, so a stream slow to send it
+/// doesn't hold up others.
+async fn route_stream(shared: Arc<Shared>, send: SendStream, mut recv: RecvStream) {
+    let header = tokio::time::timeout(HEADER_TIMEOUT, read_header(&mut recv))
+        .await
+        .unwrap_or(Err(Error::ErrInvalidHeader));
+
+    let (label, port) = match header {
+        Ok(header) => header,
+        Err(e) => {
+            log::warn!("Drop stream with invalid header: {:?}", e);
+            return;
+        }
+    };
+
+    if port == 0 {
+        let _ = shared.accept_tx.send(QuicStream::new(send, recv));
+    } else {
+        shared.deliver(label, port, send, recv);
+    }
+}
+
+/// Address peers can reach `addr` at. Unspecified ip is replaced by ip of interface
+/// routing outbound traffic, or by loopback without route.
+fn reachable_addr(addr: SocketAddr) -> SocketAddr {
+    if !addr.ip().is_unspecified() {
+        return addr;
+    }
+
+    let ip = interface_ip(addr.ip()).unwrap_or(match addr.ip() {
+        IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+        IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+    });
+
+    SocketAddr::new(ip, addr.port())
+}
+
+/// Ip picked for route to a documentation address, connecting udp socket sends
+/// nothing.
+fn interface_ip(unspecified: IpAddr) -> Option<IpAddr> {
+    let probe: SocketAddr = match unspecified {
+        IpAddr::V4(_) => (Ipv4Addr::new(192, 0, 2, 1), 9).into(),
+        IpAddr::V6(_) => (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 9).into(),
+    };
+
+    let socket = UdpSocket::bind((unspecified, 0)).ok()?;
+    socket.connect(probe).ok()?;
+
+    let ip = socket.local_addr().ok()?.ip();
+
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+/// Header of stream: label length and label, then port, big endian.
+fn encode_header(label: &str, port: u16) -> Vec<u8> {
+    let mut header = Vec::with_capacity(label.len() + 4);
+
+    header.extend((label.len() as u16).to_be_bytes());
+    header.extend(label.as_bytes());
+    header.extend(port.to_be_bytes());
+
+    header
+}
+
+async fn read_header(recv: &mut RecvStream) -> Result<(String, u16)> {
+    let mut len = [0u8; 2];
+    recv.read_exact(&mut len)
+        .await
+        .map_err(|_| Error::ErrInvalidHeader)?;
+
+    let mut label = vec![0u8; u16::from_be_bytes(len) as usize];
+    recv.read_exact(&mut label)
+        .await
+        .map_err(|_| Error::ErrInvalidHeader)?;
+
+    let mut port = [0u8; 2];
+    recv.read_exact(&mut port)
+        .await
+        .map_err(|_| Error::ErrInvalidHeader)?;
+
+    let label = String::from_utf8(label).map_err(|_| Error::ErrInvalidHeader)?;
+
+    Ok((label, u16::from_be_bytes(port)))
+}
+
+impl P2pSocket for QuicSocket {
+    type Addr = QuicAddr;
+
+    type Stream = QuicStream;
+
+    type Error = Error;
+
+    type Binding = future::Ready<Result<Self>>;
+
+    type Connecting = future::Ready<Result<QuicStream>>;
+
+    type SettingRemoteAddr = OpFuture<()>;
+
+    fn binding(bootstrap: Self::Addr) -> Self::Binding {
+        future::ready(Self::_bind(&bootstrap))
+    }
+
+    fn connecting(&self, label: Self::Addr, port: u16) -> Self::Connecting {
+        let label = match &label {
+            QuicAddr::Label(label) => label.clone(),
+            _ => return future::ready(Err(Error::ErrAddrType)),
+        };
+
+        if label.len() > u16::MAX as usize {
+            return future::ready(Err(Error::ErrInvalidHeader));
+        }
+
+        let fu = Box::pin(Self::_connect(self.shared.clone(), label, port));
+
+        future::ready(Ok(QuicStream::opening(fu)))
+    }
+
+    fn poll_accept(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Result<Self::Stream>> {
+        let mut accept_rx = self.accept_rx.lock().unwrap();
+
+        match ready!(accept_rx.poll_recv(cx)) {
+            Some(stream) => Poll::Ready(Ok(stream)),
+            None => Poll::Ready(Err(Error::ErrChannelClosed)),
+        }
+    }
+
+    fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let offer = self.local_addr(true)?;
+        let _ = self.addr_tx.send(offer);
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_fetch_local_addr(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Addr>> {
+        match ready!(self.get_mut().addr_rx.poll_recv(cx)) {
+            Some(addr) => Poll::Ready(Ok(addr)),
+            None => Poll::Ready(Err(Error::ErrChannelClosed)),
+        }
+    }
+
+    fn setting_remote_addr(&self, remote: Self::Addr) -> Self::SettingRemoteAddr {
+        match self._set_remote_addr(&remote) {
+            Ok(Some(fu)) => fu,
+            Ok(None) => Box::pin(future::ready(Ok(()))),
+            Err(e) => Box::pin(future::ready(Err(e))),
+        }
+    }
+
+    fn is_last_addr(addr: &Self::Addr) -> bool {
+        matches!(addr, QuicAddr::Offer(..) | QuicAddr::Answer(..))
+    }
+}
+
+/// Connection state is not tracked, broken connection shows up as failing streams.
+impl P2pSocketEvents for QuicSocket {
+    fn poll_event(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_lite::{AsyncReadExt, AsyncWriteExt};
+    use karma_p2p::{
+        testkit::{self, Harness},
+        Keypair, P2pSocketExt,
+    };
+
+    use super::*;
+
+    fn bootstrap() -> QuicAddr {
+        QuicAddr::Bootstrap("127.0.0.1:0".parse().unwrap(), Keypair::generate())
+    }
+
+    async fn pair() -> (QuicSocket, QuicSocket) {
+        let mut a = QuicSocket::bind(bootstrap()).await.unwrap();
+        let mut b = QuicSocket::bind(bootstrap()).await.unwrap();
+
+        a.start().await.unwrap();
+        let offer = a.fetch_local_addr().await.unwrap();
+        b.set_remote_addr(offer).await.unwrap();
+        let answer = b.fetch_local_addr().await.unwrap();
+        a.set_remote_addr(answer).await.unwrap();
+
+        (a, b)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn announced_to_accept() {
+        let test = async {
+            let (a, b) = pair().await;
+            let label = QuicAddr::Label("data".into());
+
+            // Announced streams are opened by either side.
+            let mut sb = b.connect(label.clone(), 0).await.unwrap();
+            sb.write_all(b"ping").await.unwrap();
+
+            let mut sa = a.accept().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            sa.write_all(b"pong").await.unwrap();
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+
+            assert_eq!(a.remote_peer_id(), Some(b.local_peer_id()));
+            assert_eq!(b.remote_peer_id(), Some(a.local_peer_id()));
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), test)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn negotiated_after_pairing() {
+        let test = async {
+            let (a, b) = pair().await;
+            let label = QuicAddr::Label("data".into());
+
+            // Remote stream arrives before answerer connects.
+            let mut sa = a.connect(label.clone(), 7).await.unwrap();
+            sa.write_all(b"hello").await.unwrap();
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            let mut sb = b.connect(label.clone(), 7).await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            sb.write_all(b"world").await.unwrap();
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"world");
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), test)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unspecified_bind() {
+        let test = async {
+            let unspecified =
+                || QuicAddr::Bootstrap("0.0.0.0:0".parse().unwrap(), Keypair::generate());
+
+            let mut a = QuicSocket::bind(unspecified()).await.unwrap();
+            let mut b = QuicSocket::bind(unspecified()).await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+
+            match &offer {
+                QuicAddr::Offer(addr, _) => {
+                    assert!(!addr.ip().is_unspecified());
+                    assert_eq!(addr.port(), a.endpoint.local_addr().unwrap().port());
+                }
+                addr => panic!("unexpected address {:?}", addr),
+            }
+
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let label = QuicAddr::Label("data".into());
+            let mut sa = a.connect(label.clone(), 1).await.unwrap();
+            let mut sb = b.connect(label, 1).await.unwrap();
+
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), test)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn queue_negotiated_streams() {
+        let test = async {
+            let (a, b) = pair().await;
+            let label = QuicAddr::Label("data".into());
+
+            // Both local streams wait, neither replaces the other.
+            let (mut sb, mut sc) = (
+                b.connect(label.clone(), 7).await.unwrap(),
+                b.connect(label.clone(), 7).await.unwrap(),
+            );
+
+            let send = |s: &'static str| {
+                let label = label.clone();
+                let a = &a;
+
+                async move {
+                    let mut sa = a.connect(label, 7).await.unwrap();
+                    sa.write_all(s.as_bytes()).await.unwrap();
+                    sa
+                }
+            };
+
+            let (_x, _y) = (send("one").await, send("two").await);
+
+            let mut got = Vec::new();
+            for s in [&mut sb, &mut sc] {
+                let mut buf = [0u8; 3];
+                s.read_exact(&mut buf).await.unwrap();
+                got.push(buf);
+            }
+
+            got.sort();
+            assert_eq!(got, [*b"one", *b"two"]);
+
+            // Remote stream nobody claims is dropped after a while.
+            let mut sa = a.connect(label.clone(), 8).await.unwrap();
+            sa.write_all(b"late").await.unwrap();
+
+            while b.shared.slots.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            let mut slots = b.shared.slots.lock().unwrap();
+            expire(&mut slots, Instant::now() + UNCLAIMED_TTL);
+            assert!(slots.is_empty());
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), test)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stalled_header() {
+        let test = async {
+            let (a, b) = pair().await;
+
+            // Stream opened without finishing its header.
+            let (conn, _) = a.shared.connection().await.unwrap();
+            let (mut stalled, _recv) = conn.open_bi().await.unwrap();
+            stalled.write_all(&[0, 4, b'd']).await.unwrap();
+
+            let mut sa = a.connect(QuicAddr::Label("data".into()), 0).await.unwrap();
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut sb = b.accept().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        };
+
+        tokio::time::timeout(Duration::from_secs(5), test)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reject_unexpected_identity() {
+        let test = async {
+            let mut a = QuicSocket::bind(bootstrap()).await.unwrap();
+            let mut b = QuicSocket::bind(bootstrap()).await.unwrap();
+
+            a.start().await.unwrap();
+
+            // Offer relayed with identity swapped.
+            let offer = match a.fetch_local_addr().await.unwrap() {
+                QuicAddr::Offer(addr, _) => QuicAddr::Offer(addr, Keypair::generate().peer_id()),
+                addr => panic!("unexpected addr {:?}", addr),
+            };
+
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+
+            // Client certificate is rejected after client finished handshake, so
+            // dial may succeed before connection is closed by answerer.
+            let _ = a.set_remote_addr(answer).await;
+
+            let mut sa = a.connect(QuicAddr::Label("data".into()), 0).await.unwrap();
+
+            let res = async {
+                sa.write_all(b"ping").await?;
+                sa.read(&mut [0u8; 4]).await
+            }
+            .await;
+
+            assert!(res.is_err());
+            assert_eq!(b.remote_peer_id(), None);
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), test)
+            .await
+            .unwrap();
+    }
+
+    struct QuicHarness;
+
+    impl Harness for QuicHarness {
+        type Socket = QuicSocket;
+
+        type Stream = QuicStream;
+
+        type Addr = QuicAddr;
+
+        type Error = Error;
+
+        fn bootstrap(&self) -> QuicAddr {
+            bootstrap()
+        }
+
+        fn label(&self, name: &str) -> QuicAddr {
+            QuicAddr::Label(String::from(name))
+        }
+
+        fn wrong_addr(&self) -> QuicAddr {
+            bootstrap()
+        }
+
+        fn is_offer(&self, addr: &QuicAddr) -> bool {
+            matches!(addr, QuicAddr::Offer(..))
+        }
+
+        fn is_answer(&self, addr: &QuicAddr) -> bool {
+            matches!(addr, QuicAddr::Answer(..))
+        }
+
+        fn is_addr_type_error(&self, err: &Error) -> bool {
+            matches!(err, Error::ErrAddrType)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn conformance() {
+        tokio::time::timeout(Duration::from_secs(30), testkit::check_all(&QuicHarness))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{ready, AsyncRead, AsyncWrite, FutureExt};
+use quinn::{RecvStream, SendStream};
+
+use crate::Result;
+
+pub(crate) type OpenFuture = Pin<Box<dyn Future<Output = Result<(SendStream, RecvStream)>> + Send>>;
+
+/// Bidirectional QUIC stream.
+///
+/// Stream connected before connection is established opens once it is, reads and
+/// writes are pending until then.
+pub struct QuicStream {
+    opening: Option<OpenFuture>,
+    streams: Option<(SendStream, RecvStream)>,
+}
+
+impl QuicStream {
+    pub(crate) fn new(send: SendStream, recv: RecvStream) -> Self {
+        Self {
+            opening: None,
+            streams: Some((send, recv)),
+        }
+    }
+
+    pub(crate) fn opening(fu: OpenFuture) -> Self {
+        Self {
+            opening: Some(fu),
+            streams: None,
+        }
+    }
+
+    fn poll_open(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(&mut SendStream, &mut RecvStream)>> {
+        if let Some(fu) = &mut self.opening {
+            let res = ready!(fu.poll(cx));
+
+            // Completed future can't be polled again, even if opening failed.
+            self.opening = None;
+            self.streams = Some(res?);
+        }
+
+        match self.streams.as_mut() {
+            Some((send, recv)) => Poll::Ready(Ok((send, recv))),
+            None => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        }
+    }
+}
+
+impl AsyncRead for QuicStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let (_, recv) = ready!(self.get_mut().poll_open(cx))?;
+
+        Pin::new(recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let (send, _) = ready!(self.get_mut().poll_open(cx))?;
+
+        Pin::new(send).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (send, _) = ready!(self.get_mut().poll_open(cx))?;
+
+        Pin::new(send).poll_flush(cx)
+    }
+
+    /// Finish sending, remote reads EOF after pending data.
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let (send, _) = ready!(self.get_mut().poll_open(cx))?;
+
+        Pin::new(send).poll_close(cx)
+    }
+}
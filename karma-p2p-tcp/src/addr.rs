@@ -0,0 +1,29 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum TcpAddr {
+    /// Local address to bind, and endpoints of it observed by others, such as
+    /// bootstrap nodes.
+    #[serde(skip)]
+    Bootstrap {
+        bind: SocketAddr,
+        observed: Vec<SocketAddr>,
+    },
+
+    #[serde(skip)]
+    Label(String),
+
+    /// Endpoints of initiator and random session, produced by `start`.
+    ///
+    /// Both sides prove knowledge of session on selected connection, it's never
+    /// sent there, so offer must only travel over a trusted signaling path.
+    Offer {
+        endpoints: Vec<SocketAddr>,
+        session: [u8; 32],
+    },
+
+    /// Endpoints of answerer, produced when offer is set as remote address.
+    Answer { endpoints: Vec<SocketAddr> },
+}
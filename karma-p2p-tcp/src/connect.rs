@@ -0,0 +1,343 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use smol::{channel::unbounded, Async, Timer};
+use socket2::{Domain, Socket, Type};
+
+use crate::{Error, Result};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Length of session and of nonces and proofs exchanged on connections.
+pub(crate) const SESSION_LEN: usize = 32;
+
+const PROOF_CONTEXT: &[u8] = b"karma-tcp-v1";
+
+/// Dials to each remote endpoint are retried, so SYNs cross while both sides dial.
+const DIAL_ATTEMPTS: usize = 5;
+
+const DIAL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Connection is given up if none is selected in time.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Socket which shares local port with listener and dials.
+fn reusable_socket(addr: &SocketAddr) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, None)?;
+
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+
+    socket.bind(&(*addr).into())?;
+
+    Ok(socket)
+}
+
+pub(crate) fn listen(addr: &SocketAddr) -> io::Result<Async<TcpListener>> {
+    let socket = reusable_socket(addr)?;
+
+    socket.listen(128)?;
+
+    Async::new(TcpListener::from(socket))
+}
+
+/// Address peers can reach `addr` at. Unspecified ip is replaced by ip of interface
+/// routing outbound traffic, or by loopback without route.
+pub(crate) fn reachable_addr(addr: SocketAddr) -> SocketAddr {
+    if !addr.ip().is_unspecified() {
+        return addr;
+    }
+
+    let ip = interface_ip(addr.ip()).unwrap_or(match addr.ip() {
+        IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+        IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+    });
+
+    SocketAddr::new(ip, addr.port())
+}
+
+/// Ip picked for route to a documentation address, connecting udp socket sends
+/// nothing.
+fn interface_ip(unspecified: IpAddr) -> Option<IpAddr> {
+    let probe: SocketAddr = match unspecified {
+        IpAddr::V4(_) => (Ipv4Addr::new(192, 0, 2, 1), 9).into(),
+        IpAddr::V6(_) => (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 9).into(),
+    };
+
+    let socket = UdpSocket::bind((unspecified, 0)).ok()?;
+    socket.connect(probe).ok()?;
+
+    let ip = socket.local_addr().ok()?.ip();
+
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+/// Dial `remote` from port of listener.
+async fn dial(local: SocketAddr, remote: SocketAddr) -> io::Result<Async<TcpStream>> {
+    let socket = reusable_socket(&local)?;
+
+    match socket.connect(&remote.into()) {
+        Ok(()) => {}
+        Err(e) if in_progress(&e) => {}
+        Err(e) => return Err(e),
+    }
+
+    let stream = Async::new(TcpStream::from(socket))?;
+
+    stream.writable().await?;
+
+    match stream.get_ref().take_error()? {
+        Some(e) => Err(e),
+        None => {
+            stream.get_ref().peer_addr()?;
+            Ok(stream)
+        }
+    }
+}
+
+fn in_progress(e: &io::Error) -> bool {
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(libc::EINPROGRESS) {
+        return true;
+    }
+
+    e.kind() == io::ErrorKind::WouldBlock
+}
+
+async fn dial_with_retry(local: SocketAddr, remote: SocketAddr) -> io::Result<Async<TcpStream>> {
+    let mut last = io::ErrorKind::NotConnected.into();
+
+    for _ in 0..DIAL_ATTEMPTS {
+        match dial(local, remote).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = e,
+        }
+
+        Timer::after(DIAL_INTERVAL).await;
+    }
+
+    Err(last)
+}
+
+/// MAC of both nonces under `session`, `role` keeps proofs of each side apart.
+fn proof(session: &[u8; SESSION_LEN], role: &[u8], nonces: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(session).expect("hmac takes any key size");
+
+    mac.update(PROOF_CONTEXT);
+    mac.update(role);
+    mac.update(nonces);
+
+    mac
+}
+
+/// Prove both sides know `session` without sending it.
+///
+/// Each side sends a random nonce. Initiator then sends its proof over both nonces,
+/// answerer verifies it and returns its own proof, to be sent only on connection it
+/// selects. Initiator is done once it verified that one.
+async fn handshake(
+    stream: &mut Async<TcpStream>,
+    initiator: bool,
+    session: &[u8; SESSION_LEN],
+) -> Result<Option<[u8; SESSION_LEN]>> {
+    let mut local = [0u8; SESSION_LEN];
+    OsRng.fill_bytes(&mut local);
+
+    stream.write_all(&local).await?;
+
+    let mut remote = [0u8; SESSION_LEN];
+    stream.read_exact(&mut remote).await?;
+
+    let nonces = match initiator {
+        true => [local, remote].concat(),
+        false => [remote, local].concat(),
+    };
+
+    let (ours, theirs): (&[u8], &[u8]) = match initiator {
+        true => (b"initiator", b"answerer"),
+        false => (b"answerer", b"initiator"),
+    };
+
+    let reply: [u8; SESSION_LEN] = proof(session, ours, &nonces).finalize().into_bytes().into();
+
+    if initiator {
+        stream.write_all(&reply).await?;
+    }
+
+    let mut remote_proof = [0u8; SESSION_LEN];
+    stream.read_exact(&mut remote_proof).await?;
+
+    proof(session, theirs, &nonces)
+        .verify_slice(&remote_proof)
+        .map_err(|_| Error::ErrInvalidProof)?;
+
+    Ok((!initiator).then_some(reply))
+}
+
+/// Connect to remote by simultaneous open, falling back to whichever of listen and
+/// dial succeeds first.
+///
+/// Both sides may end up with several connections, each is verified by
+/// [`handshake`]. Answerer keeps the first initiator proved `session` on and
+/// proves it back only there, initiator keeps that one.
+pub(crate) async fn establish(
+    listener: Arc<Async<TcpListener>>,
+    remotes: Vec<SocketAddr>,
+    initiator: bool,
+    session: [u8; SESSION_LEN],
+) -> Result<Async<TcpStream>> {
+    let local = listener.get_ref().local_addr()?;
+
+    let (candidate_tx, candidate_rx) = unbounded();
+
+    // Tasks are cancelled when dropped on return.
+    let mut tasks = Vec::new();
+
+    for remote in remotes {
+        let candidate_tx = candidate_tx.clone();
+
+        tasks.push(smol::spawn(async move {
+            match dial_with_retry(local, remote).await {
+                Ok(stream) => drop(candidate_tx.send(stream).await),
+                Err(e) => log::debug!("Dial {} failed: {:?}", remote, e),
+            }
+        }));
+    }
+
+    tasks.push(smol::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            if candidate_tx.send(stream).await.is_err() {
+                break;
+            }
+        }
+    }));
+
+    let select = async {
+        let (verified_tx, verified_rx) = unbounded();
+
+        enum Event {
+            Candidate(Async<TcpStream>),
+            Verified(Async<TcpStream>, Option<[u8; SESSION_LEN]>),
+        }
+
+        loop {
+            let event = future::or(
+                async { candidate_rx.recv().await.map(Event::Candidate) },
+                async {
+                    let (stream, reply) = verified_rx.recv().await?;
+                    Ok(Event::Verified(stream, reply))
+                },
+            );
+
+            match event.await? {
+                Event::Candidate(mut stream) => {
+                    let verified_tx = verified_tx.clone();
+
+                    tasks.push(smol::spawn(async move {
+                        match handshake(&mut stream, initiator, &session).await {
+                            Ok(reply) => drop(verified_tx.send((stream, reply)).await),
+                            Err(e) => log::debug!("Handshake failed: {:?}", e),
+                        }
+                    }));
+                }
+                Event::Verified(mut stream, reply) => {
+                    let sent = match reply {
+                        Some(reply) => stream.write_all(&reply).await.is_ok(),
+                        None => true,
+                    };
+
+                    if sent {
+                        return Ok(stream);
+                    }
+                }
+            }
+        }
+    };
+
+    future::or(select, async {
+        Timer::after(CONNECT_TIMEOUT).await;
+        Err(Error::ErrConnectFailed)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+    use smol::Async;
+
+    use super::{handshake, SESSION_LEN};
+    use crate::Error;
+
+    async fn connected() -> (Async<TcpStream>, Async<TcpStream>) {
+        let listener = Async::<TcpListener>::bind(([127, 0, 0, 1], 0)).unwrap();
+        let addr = listener.get_ref().local_addr().unwrap();
+
+        let (a, b) = future::zip(Async::<TcpStream>::connect(addr), listener.accept()).await;
+
+        (a.unwrap(), b.unwrap().0)
+    }
+
+    #[test]
+    fn prove_session() {
+        smol::block_on(async {
+            let session = [7u8; SESSION_LEN];
+
+            let (mut a, mut b) = connected().await;
+
+            let (initiator, answerer) = future::zip(handshake(&mut a, true, &session), async {
+                let reply = handshake(&mut b, false, &session).await.unwrap().unwrap();
+                b.write_all(&reply).await
+            })
+            .await;
+
+            assert!(initiator.unwrap().is_none());
+            answerer.unwrap();
+
+            // Peer without session only sees nonces and initiator's proof.
+            let (mut a, mut b) = connected().await;
+
+            let peer = async {
+                let mut seen = [0u8; SESSION_LEN * 2];
+
+                b.read_exact(&mut seen[..SESSION_LEN]).await.unwrap();
+                b.write_all(&[0u8; SESSION_LEN]).await.unwrap();
+                b.read_exact(&mut seen[SESSION_LEN..]).await.unwrap();
+                b.write_all(&[0u8; SESSION_LEN]).await.unwrap();
+
+                seen
+            };
+
+            let (res, seen) = future::zip(handshake(&mut a, true, &session), peer).await;
+
+            assert!(matches!(res, Err(Error::ErrInvalidProof)));
+            assert!(!seen.windows(SESSION_LEN).any(|w| w == session));
+
+            // Initiator with wrong session is rejected before answerer proves anything.
+            let (mut a, mut b) = connected().await;
+
+            let res = future::or(
+                async {
+                    let _ = handshake(&mut a, true, &[8u8; SESSION_LEN]).await;
+                    future::pending().await
+                },
+                handshake(&mut b, false, &session),
+            )
+            .await;
+
+            assert!(matches!(res, Err(Error::ErrInvalidProof)));
+        });
+    }
+}
@@ -0,0 +1,49 @@
+#[derive(Debug)]
+pub enum Error {
+    ErrAddrType,
+    ErrChannelClosed,
+    ErrInvalidHeader,
+    ErrConnectFailed,
+    ErrInvalidProof,
+    IoError(std::io::Error),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl From<smol::channel::RecvError> for Error {
+    fn from(_: smol::channel::RecvError) -> Self {
+        Error::ErrChannelClosed
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::ErrChannelClosed => std::io::ErrorKind::BrokenPipe.into(),
+            Error::ErrConnectFailed => std::io::ErrorKind::NotConnected.into(),
+            Error::IoError(e) => e,
+            e => std::io::Error::other(format!("{:?}", e)),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
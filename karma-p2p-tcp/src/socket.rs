@@ -0,0 +1,902 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{SocketAddr, TcpListener, TcpStream as StdTcpStream},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, ready, AsyncReadExt, AsyncWriteExt, StreamExt};
+use karma_p2p::{Mux, P2pSocket, P2pSocketEvents, SocketEvent, Substream};
+use rand_core::{OsRng, RngCore};
+use smol::{
+    channel::{bounded, unbounded, Receiver, Sender},
+    Async, Task, Timer,
+};
+
+use crate::{
+    connect::{establish, listen, reachable_addr, SESSION_LEN},
+    Error, Result, TcpAddr, TcpStream,
+};
+
+pub(crate) type TcpSubstream = Substream<Async<StdTcpStream>>;
+
+/// Remote stream must send its header within it, or it's dropped.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Negotiated remote stream not claimed by local `connect` within it is dropped.
+const UNCLAIMED_TTL: Duration = Duration::from_secs(30);
+
+enum ConnState {
+    Connecting,
+
+    /// Connection multiplexed into streams.
+    Connected {
+        mux: Mux<Async<StdTcpStream>>,
+        initiator: bool,
+        peer: SocketAddr,
+    },
+
+    Failed,
+}
+
+/// Negotiated streams of one label and port, whichever of remote stream and local
+/// `connect` comes first waits for the other. Each side is queued in order.
+#[derive(Default)]
+struct Slot {
+    waiting: VecDeque<Sender<TcpSubstream>>,
+    arrived: VecDeque<(TcpSubstream, Instant)>,
+}
+
+struct Shared {
+    state: Mutex<ConnState>,
+    // Closed once connection is established or failed.
+    ready_tx: Sender<()>,
+    ready_rx: Receiver<()>,
+    slots: Mutex<HashMap<(String, u16), Slot>>,
+    accept_tx: Sender<TcpStream>,
+}
+
+impl Shared {
+    async fn connection(&self) -> Result<(Mux<Async<StdTcpStream>>, bool)> {
+        // Fails as well when socket is dropped, state is checked below.
+        let _ = self.ready_rx.recv().await;
+
+        match &*self.state.lock().unwrap() {
+            ConnState::Connected { mux, initiator, .. } => Ok((mux.clone(), *initiator)),
+            ConnState::Failed => Err(Error::ErrConnectFailed),
+            ConnState::Connecting => Err(Error::ErrChannelClosed),
+        }
+    }
+
+    fn deliver(&self, label: String, port: u16, stream: TcpSubstream) {
+        let mut slots = self.slots.lock().unwrap();
+
+        expire(&mut slots, Instant::now());
+
+        let slot = slots.entry((label, port)).or_default();
+        let mut stream = stream;
+
+        // Local stream may be dropped already, then next one takes it.
+        while let Some(tx) = slot.waiting.pop_front() {
+            match tx.try_send(stream) {
+                Ok(()) => return,
+                Err(e) => stream = e.into_inner(),
+            }
+        }
+
+        slot.arrived.push_back((stream, Instant::now()));
+    }
+
+    /// Take first remote stream arrived for `label` and `port`, or wait for next.
+    fn claim(
+        &self,
+        label: String,
+        port: u16,
+    ) -> std::result::Result<TcpSubstream, Receiver<TcpSubstream>> {
+        let mut slots = self.slots.lock().unwrap();
+
+        expire(&mut slots, Instant::now());
+
+        let slot = slots.entry((label, port)).or_default();
+
+        if let Some((stream, _)) = slot.arrived.pop_front() {
+            return Ok(stream);
+        }
+
+        let (tx, rx) = bounded(1);
+        slot.waiting.push_back(tx);
+
+        Err(rx)
+    }
+}
+
+/// Drop remote streams unclaimed for `UNCLAIMED_TTL` and local ones gone.
+fn expire(slots: &mut HashMap<(String, u16), Slot>, now: Instant) {
+    slots.retain(|_, slot| {
+        slot.waiting.retain(|tx| tx.receiver_count() > 0);
+        slot.arrived
+            .retain(|(_, at)| now.saturating_duration_since(*at) < UNCLAIMED_TTL);
+
+        !slot.waiting.is_empty() || !slot.arrived.is_empty()
+    });
+}
+
+/// P2p socket over one TCP connection, multiplexed into streams by [`Mux`].
+///
+/// Addresses carry endpoints of each side. Once remote endpoints are known, both
+/// sides dial them from the listening port, so connection is made by simultaneous
+/// open through NATs keeping port, or by plain dial to a reachable listener.
+pub struct TcpSocket {
+    listener: Arc<Async<TcpListener>>,
+    observed: Vec<SocketAddr>,
+    session: [u8; SESSION_LEN],
+    shared: Arc<Shared>,
+    addr_tx: Sender<TcpAddr>,
+    addr_rx: Receiver<TcpAddr>,
+    accept_rx: Mutex<Receiver<TcpStream>>,
+
+    // Establishes connection then routes remote streams, cancelled when dropped.
+    driver: Mutex<Option<Task<()>>>,
+}
+
+impl TcpSocket {
+    fn _bind(bootstrap: &TcpAddr) -> Result<Self> {
+        let (bind, observed) = match bootstrap {
+            TcpAddr::Bootstrap { bind, observed } => (*bind, observed.clone()),
+            _ => return Err(Error::ErrAddrType),
+        };
+
+        let (ready_tx, ready_rx) = bounded(1);
+        let (addr_tx, addr_rx) = unbounded();
+        let (accept_tx, accept_rx) = unbounded();
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(ConnState::Connecting),
+            ready_tx,
+            ready_rx,
+            slots: Mutex::new(HashMap::new()),
+            accept_tx,
+        });
+
+        Ok(Self {
+            listener: Arc::new(listen(&bind)?),
+            observed,
+            session: new_session(),
+            shared,
+            addr_tx,
+            addr_rx,
+            accept_rx: Mutex::new(accept_rx),
+            driver: Mutex::new(None),
+        })
+    }
+
+    /// Address of listening port, which dials are made from as well.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.get_ref().local_addr()?)
+    }
+
+    /// Peer address of connection, `None` until established.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        match &*self.shared.state.lock().unwrap() {
+            ConnState::Connected { peer, .. } => Some(*peer),
+            _ => None,
+        }
+    }
+
+    /// Listening address with unspecified ip made reachable, then observed endpoints.
+    fn endpoints(&self) -> Result<Vec<SocketAddr>> {
+        let mut endpoints = vec![reachable_addr(self.local_addr()?)];
+
+        for observed in &self.observed {
+            if !observed.ip().is_unspecified() && !endpoints.contains(observed) {
+                endpoints.push(*observed);
+            }
+        }
+
+        Ok(endpoints)
+    }
+
+    /// Open stream `label`.
+    ///
+    /// Non-zero `port` is negotiated, both sides must connect with the same `label`
+    /// and `port`, initiator opens it. Port `0` announces the stream to the remote,
+    /// which receives it from `accept`.
+    async fn _connect(shared: Arc<Shared>, label: String, port: u16) -> Result<TcpSubstream> {
+        let (mux, initiator) = shared.connection().await?;
+
+        if port != 0 && !initiator {
+            return match shared.claim(label, port) {
+                Ok(stream) => Ok(stream),
+                Err(rx) => Ok(rx.recv().await?),
+            };
+        }
+
+        let mut stream = mux.open()?;
+
+        stream.write_all(&encode_header(&label, port)).await?;
+
+        Ok(stream)
+    }
+
+    fn drive(&self, remotes: Vec<SocketAddr>, initiator: bool, session: [u8; SESSION_LEN]) {
+        let fu = drive(
+            self.shared.clone(),
+            self.listener.clone(),
+            remotes,
+            initiator,
+            session,
+        );
+
+        *self.driver.lock().unwrap() = Some(smol::spawn(fu));
+    }
+}
+
+fn new_session() -> [u8; SESSION_LEN] {
+    let mut session = [0u8; SESSION_LEN];
+    OsRng.fill_bytes(&mut session);
+
+    session
+}
+
+async fn drive(
+    shared: Arc<Shared>,
+    listener: Arc<Async<TcpListener>>,
+    remotes: Vec<SocketAddr>,
+    initiator: bool,
+    session: [u8; SESSION_LEN],
+) {
+    let connected = establish(listener, remotes, initiator, session)
+        .await
+        .and_then(|stream| Ok((stream.get_ref().peer_addr()?, stream)));
+
+    let (peer, mux) = match connected {
+        Ok((peer, stream)) => {
+            let _ = stream.get_ref().set_nodelay(true);
+            (peer, Mux::new(stream, initiator))
+        }
+        Err(e) => {
+            log::warn!("Establish connection failed: {:?}", e);
+
+            *shared.state.lock().unwrap() = ConnState::Failed;
+            shared.ready_tx.close();
+
+            return;
+        }
+    };
+
+    *shared.state.lock().unwrap() = ConnState::Connected {
+        mux: mux.clone(),
+        initiator,
+        peer,
+    };
+    shared.ready_tx.close();
+
+    // Route streams opened by remote until connection closes.
+    while let Ok(stream) = mux.accept().await {
+        smol::spawn(route_stream(shared.clone(), stream)).detach();
+    }
+}
+
+/// Read header of remote stream and hand it over, so a stream slow to send it
+/// doesn't hold up others.
+async fn route_stream(shared: Arc<Shared>, mut stream: TcpSubstream) {
+    let header = future::or(read_header(&mut stream), async {
+        Timer::after(HEADER_TIMEOUT).await;
+        Err(Error::ErrInvalidHeader)
+    })
+    .await;
+
+    let (label, port) = match header {
+        Ok(header) => header,
+        Err(e) => {
+            log::warn!("Drop stream with invalid header: {:?}", e);
+            return;
+        }
+    };
+
+    if port == 0 {
+        let _ = shared.accept_tx.try_send(TcpStream::connected(stream));
+    } else {
+        shared.deliver(label, port, stream);
+    }
+}
+
+/// Header of stream: label length and label, then port, big endian.
+fn encode_header(label: &str, port: u16) -> Vec<u8> {
+    let mut header = Vec::with_capacity(label.len() + 4);
+
+    header.extend((label.len() as u16).to_be_bytes());
+    header.extend(label.as_bytes());
+    header.extend(port.to_be_bytes());
+
+    header
+}
+
+async fn read_header(stream: &mut TcpSubstream) -> Result<(String, u16)> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await?;
+
+    let mut label = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut label).await?;
+
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+
+    let label = String::from_utf8(label).map_err(|_| Error::ErrInvalidHeader)?;
+
+    Ok((label, u16::from_be_bytes(port)))
+}
+
+impl P2pSocket for TcpSocket {
+    type Addr = TcpAddr;
+
+    type Stream = TcpStream;
+
+    type Error = Error;
+
+    type Binding = future::Ready<Result<Self>>;
+
+    type Connecting = future::Ready<Result<TcpStream>>;
+
+    type SettingRemoteAddr = future::Ready<Result<()>>;
+
+    fn binding(bootstrap: Self::Addr) -> Self::Binding {
+        future::ready(Self::_bind(&bootstrap))
+    }
+
+    fn connecting(&self, label: Self::Addr, port: u16) -> Self::Connecting {
+        let label = match &label {
+            TcpAddr::Label(label) => label.clone(),
+            _ => return future::ready(Err(Error::ErrAddrType)),
+        };
+
+        if label.len() > u16::MAX as usize {
+            return future::ready(Err(Error::ErrInvalidHeader));
+        }
+
+        let fu = Self::_connect(self.shared.clone(), label, port);
+
+        future::ready(Ok(TcpStream::connecting(smol::spawn(fu))))
+    }
+
+    fn poll_accept(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Result<Self::Stream>> {
+        let mut accept_rx = self.accept_rx.lock().unwrap();
+
+        match ready!(accept_rx.poll_next(cx)) {
+            Some(stream) => Poll::Ready(Ok(stream)),
+            None => Poll::Ready(Err(Error::ErrChannelClosed)),
+        }
+    }
+
+    fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let offer = TcpAddr::Offer {
+            endpoints: self.endpoints()?,
+            session: self.session,
+        };
+
+        Poll::Ready(
+            self.addr_tx
+                .try_send(offer)
+                .map_err(|_| Error::ErrChannelClosed),
+        )
+    }
+
+    fn poll_fetch_local_addr(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Addr>> {
+        match ready!(self.addr_rx.poll_next(cx)) {
+            Some(addr) => Poll::Ready(Ok(addr)),
+            None => Poll::Ready(Err(Error::ErrChannelClosed)),
+        }
+    }
+
+    fn setting_remote_addr(&self, remote: Self::Addr) -> Self::SettingRemoteAddr {
+        let res = match &remote {
+            TcpAddr::Offer { endpoints, session } => self.endpoints().and_then(|local| {
+                self.drive(endpoints.clone(), false, *session);
+
+                self.addr_tx
+                    .try_send(TcpAddr::Answer { endpoints: local })
+                    .map_err(|_| Error::ErrChannelClosed)
+            }),
+            TcpAddr::Answer { endpoints } => {
+                self.drive(endpoints.clone(), true, self.session);
+                Ok(())
+            }
+            _ => Err(Error::ErrAddrType),
+        };
+
+        future::ready(res)
+    }
+
+    fn is_last_addr(addr: &Self::Addr) -> bool {
+        matches!(addr, TcpAddr::Offer { .. } | TcpAddr::Answer { .. })
+    }
+}
+
+/// Connection state is not tracked, broken connection shows up as failing streams.
+impl P2pSocketEvents for TcpSocket {
+    fn poll_event(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::Future, net::TcpListener, time::Duration};
+
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+    use karma_p2p::{
+        testkit::{self, Harness},
+        BoxedSocket, DynAddr, Format, P2pSocketExt, SerdeAddrCodec,
+    };
+    use smol::Timer;
+
+    use super::*;
+
+    fn bootstrap() -> TcpAddr {
+        TcpAddr::Bootstrap {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            observed: Vec::new(),
+        }
+    }
+
+    fn run<F: Future<Output = ()>>(test: F) {
+        smol::block_on(future::or(test, async {
+            Timer::after(Duration::from_secs(30)).await;
+            panic!("test timed out");
+        }))
+    }
+
+    async fn pair() -> (TcpSocket, TcpSocket) {
+        let mut a = TcpSocket::bind(bootstrap()).await.unwrap();
+        let mut b = TcpSocket::bind(bootstrap()).await.unwrap();
+
+        a.start().await.unwrap();
+        let offer = a.fetch_local_addr().await.unwrap();
+        b.set_remote_addr(offer).await.unwrap();
+        let answer = b.fetch_local_addr().await.unwrap();
+        a.set_remote_addr(answer).await.unwrap();
+
+        (a, b)
+    }
+
+    #[test]
+    fn announced_to_accept() {
+        run(async {
+            let (a, b) = pair().await;
+            let label = TcpAddr::Label("data".into());
+
+            let mut sb = b.connect(label.clone(), 0).await.unwrap();
+            sb.write_all(b"ping").await.unwrap();
+
+            let mut sa = a.accept().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            sa.write_all(b"pong").await.unwrap();
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+
+            assert_eq!(a.remote_addr(), Some(b.local_addr().unwrap()));
+            assert_eq!(b.remote_addr(), Some(a.local_addr().unwrap()));
+        });
+    }
+
+    #[test]
+    fn unspecified_bind() {
+        run(async {
+            let unspecified = || TcpAddr::Bootstrap {
+                bind: "0.0.0.0:0".parse().unwrap(),
+                observed: vec!["0.0.0.0:1".parse().unwrap()],
+            };
+
+            let mut a = TcpSocket::bind(unspecified()).await.unwrap();
+            let mut b = TcpSocket::bind(unspecified()).await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+
+            match &offer {
+                TcpAddr::Offer { endpoints, .. } => {
+                    assert_eq!(endpoints.len(), 1);
+                    assert!(!endpoints[0].ip().is_unspecified());
+                    assert_eq!(endpoints[0].port(), a.local_addr().unwrap().port());
+                }
+                addr => panic!("unexpected address {:?}", addr),
+            }
+
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let label = TcpAddr::Label("data".into());
+            let mut sa = a.connect(label.clone(), 1).await.unwrap();
+            let mut sb = b.connect(label, 1).await.unwrap();
+
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    #[test]
+    fn negotiated_before_pairing() {
+        run(async {
+            let mut a = TcpSocket::bind(bootstrap()).await.unwrap();
+            let mut b = TcpSocket::bind(bootstrap()).await.unwrap();
+            let label = TcpAddr::Label("data".into());
+
+            // Answerer connects first, its stream waits for initiator to open it.
+            let mut sb = b.connect(label.clone(), 7).await.unwrap();
+            let mut sa = a.connect(label.clone(), 7).await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            sa.write_all(b"hello").await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            sb.write_all(b"world").await.unwrap();
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"world");
+        });
+    }
+
+    #[test]
+    fn queue_negotiated_streams() {
+        run(async {
+            let (a, b) = pair().await;
+            let label = TcpAddr::Label("data".into());
+
+            // Both local streams wait, neither replaces the other.
+            let mut sb = b.connect(label.clone(), 7).await.unwrap();
+            let mut sc = b.connect(label.clone(), 7).await.unwrap();
+
+            let mut sx = a.connect(label.clone(), 7).await.unwrap();
+            sx.write_all(b"one").await.unwrap();
+            let mut sy = a.connect(label.clone(), 7).await.unwrap();
+            sy.write_all(b"two").await.unwrap();
+
+            let mut got = Vec::new();
+            for s in [&mut sb, &mut sc] {
+                let mut buf = [0u8; 3];
+                s.read_exact(&mut buf).await.unwrap();
+                got.push(buf);
+            }
+
+            got.sort();
+            assert_eq!(got, [*b"one", *b"two"]);
+
+            // Remote stream nobody claims is dropped after a while.
+            let mut sz = a.connect(label.clone(), 8).await.unwrap();
+            sz.write_all(b"late").await.unwrap();
+
+            while b.shared.slots.lock().unwrap().is_empty() {
+                Timer::after(Duration::from_millis(10)).await;
+            }
+
+            let mut slots = b.shared.slots.lock().unwrap();
+            expire(&mut slots, Instant::now() + UNCLAIMED_TTL);
+            assert!(slots.is_empty());
+        });
+    }
+
+    #[test]
+    fn stalled_header() {
+        run(async {
+            let (a, b) = pair().await;
+
+            // Stream opened without finishing its header.
+            let (mux, _) = a.shared.connection().await.unwrap();
+            let mut stalled = mux.open().unwrap();
+            stalled.write_all(&[0, 4, b'd']).await.unwrap();
+
+            let mut sa = a.connect(TcpAddr::Label("data".into()), 0).await.unwrap();
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut sb = future::or(b.accept(), async {
+                Timer::after(Duration::from_secs(5)).await;
+                panic!("accept held up by stalled stream");
+            })
+            .await
+            .unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    #[test]
+    fn fallback_to_listener() {
+        run(async {
+            let mut a = TcpSocket::bind(bootstrap()).await.unwrap();
+            let mut b = TcpSocket::bind(bootstrap()).await.unwrap();
+
+            // Port nobody listens on, so dials of answerer fail.
+            let closed = TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+
+            a.start().await.unwrap();
+            let offer = match a.fetch_local_addr().await.unwrap() {
+                TcpAddr::Offer { session, .. } => TcpAddr::Offer {
+                    endpoints: vec![closed],
+                    session,
+                },
+                addr => panic!("unexpected addr {:?}", addr),
+            };
+
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let mut sa = a.connect(TcpAddr::Label("data".into()), 0).await.unwrap();
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut sb = b.accept().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    #[test]
+    fn reject_wrong_session() {
+        run(async {
+            let mut a = TcpSocket::bind(bootstrap()).await.unwrap();
+            let mut b = TcpSocket::bind(bootstrap()).await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = match a.fetch_local_addr().await.unwrap() {
+                TcpAddr::Offer { endpoints, session } => TcpAddr::Offer {
+                    endpoints,
+                    session: session.map(|b| !b),
+                },
+                addr => panic!("unexpected addr {:?}", addr),
+            };
+
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let mut sb = b.connect(TcpAddr::Label("data".into()), 0).await.unwrap();
+
+            assert!(sb.write_all(b"ping").await.is_err());
+            assert_eq!(b.remote_addr(), None);
+        });
+    }
+
+    #[test]
+    fn boxed_with_serde_codec() {
+        run(async {
+            let codec = || SerdeAddrCodec::new(Format::Json, TcpAddr::Label);
+
+            let a = TcpSocket::bind(bootstrap()).await.unwrap();
+            let b = TcpSocket::bind(bootstrap()).await.unwrap();
+
+            let mut a = BoxedSocket::new(a, codec());
+            let mut b = BoxedSocket::new(b, codec());
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let mut sa = a.connect(DynAddr::Label("data".into()), 0).await.unwrap();
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut sb = b.accept().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    struct TcpHarness;
+
+    impl Harness for TcpHarness {
+        type Socket = TcpSocket;
+
+        type Stream = TcpStream;
+
+        type Addr = TcpAddr;
+
+        type Error = Error;
+
+        fn bootstrap(&self) -> TcpAddr {
+            bootstrap()
+        }
+
+        fn label(&self, name: &str) -> TcpAddr {
+            TcpAddr::Label(String::from(name))
+        }
+
+        fn wrong_addr(&self) -> TcpAddr {
+            bootstrap()
+        }
+
+        fn is_offer(&self, addr: &TcpAddr) -> bool {
+            matches!(addr, TcpAddr::Offer { .. })
+        }
+
+        fn is_answer(&self, addr: &TcpAddr) -> bool {
+            matches!(addr, TcpAddr::Answer { .. })
+        }
+
+        fn is_addr_type_error(&self, err: &Error) -> bool {
+            matches!(err, Error::ErrAddrType)
+        }
+    }
+
+    #[test]
+    fn conformance() {
+        run(testkit::check_all(&TcpHarness));
+    }
+
+    /// Network namespaces linked by a veth pair, deleted when dropped.
+    struct Netns(Vec<String>);
+
+    impl Netns {
+        fn new() -> Self {
+            let id = std::process::id();
+            let (a, b) = (format!("karma-{}-a", id), format!("karma-{}-b", id));
+            let (va, vb) = (format!("kv{}a", id), format!("kv{}b", id));
+
+            let netns = Netns(vec![a.clone(), b.clone()]);
+
+            let ip = |args: &[&str]| {
+                let status = std::process::Command::new("ip").args(args).status();
+                assert!(status.unwrap().success(), "ip {:?} failed", args);
+            };
+
+            ip(&["netns", "add", &a]);
+            ip(&["netns", "add", &b]);
+            ip(&["link", "add", &va, "type", "veth", "peer", "name", &vb]);
+            ip(&["link", "set", &va, "netns", &a]);
+            ip(&["link", "set", &vb, "netns", &b]);
+            ip(&["-n", &a, "addr", "add", "10.77.0.1/24", "dev", &va]);
+            ip(&["-n", &b, "addr", "add", "10.77.0.2/24", "dev", &vb]);
+            ip(&["-n", &a, "link", "set", &va, "up"]);
+            ip(&["-n", &b, "link", "set", &vb, "up"]);
+
+            netns
+        }
+    }
+
+    impl Drop for Netns {
+        fn drop(&mut self) {
+            for ns in &self.0 {
+                let _ = std::process::Command::new("ip")
+                    .args(["netns", "del", ns])
+                    .status();
+            }
+        }
+    }
+
+    /// Side of `across_namespaces` run in a child process, addresses are lines of
+    /// stdin and stdout.
+    fn namespace_peer(role: &str) {
+        let bind = std::env::var("KARMA_NETNS_BIND").unwrap().parse().unwrap();
+
+        let mut lines = std::io::stdin().lines();
+
+        let mut recv = move || {
+            let line = lines.next().unwrap().unwrap();
+            Format::Json.decode::<TcpAddr>(line.as_bytes()).unwrap()
+        };
+
+        let send = |addr: TcpAddr| {
+            let json = Format::Json.encode(&addr).unwrap();
+            println!("addr:{}", std::str::from_utf8(&json).unwrap());
+        };
+
+        run(async {
+            let mut socket = TcpSocket::bind(TcpAddr::Bootstrap {
+                bind,
+                observed: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+            let label = TcpAddr::Label("data".into());
+            let mut buf = [0u8; 4];
+
+            if role == "initiator" {
+                socket.start().await.unwrap();
+                send(socket.fetch_local_addr().await.unwrap());
+                socket.set_remote_addr(recv()).await.unwrap();
+
+                let mut stream = socket.connect(label, 7).await.unwrap();
+                stream.write_all(b"ping").await.unwrap();
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"pong");
+            } else {
+                socket.set_remote_addr(recv()).await.unwrap();
+                send(socket.fetch_local_addr().await.unwrap());
+
+                let mut stream = socket.connect(label, 7).await.unwrap();
+                stream.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ping");
+                stream.write_all(b"pong").await.unwrap();
+
+                // Until initiator exits, so pong isn't lost with this process.
+                let _ = stream.read(&mut buf).await;
+            }
+
+            let remote = socket.remote_addr().unwrap();
+            assert!(remote.ip().to_string().starts_with("10.77.0."));
+        });
+    }
+
+    #[test]
+    #[ignore = "needs root and iproute2"]
+    fn across_namespaces() {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            process::{Command, Stdio},
+        };
+
+        if let Ok(role) = std::env::var("KARMA_NETNS_ROLE") {
+            return namespace_peer(&role);
+        }
+
+        let netns = Netns::new();
+        let exe = std::env::current_exe().unwrap();
+
+        let spawn = |ns: &str, role: &str, bind: &str| {
+            Command::new("ip")
+                .args(["netns", "exec", ns])
+                .arg(&exe)
+                .args(["--exact", "socket::tests::across_namespaces"])
+                .args(["--ignored", "--nocapture"])
+                .env("KARMA_NETNS_ROLE", role)
+                .env("KARMA_NETNS_BIND", bind)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap()
+        };
+
+        let mut a = spawn(&netns.0[0], "initiator", "10.77.0.1:0");
+        let mut b = spawn(&netns.0[1], "answerer", "10.77.0.2:0");
+
+        let mut out_a = BufReader::new(a.stdout.take().unwrap()).lines();
+        let mut out_b = BufReader::new(b.stdout.take().unwrap()).lines();
+
+        // Output of test harness is skipped, it may share a line with address.
+        let next_addr = |lines: &mut dyn Iterator<Item = std::io::Result<String>>| {
+            lines
+                .map(|l| l.unwrap())
+                .find_map(|l| l.split_once("addr:").map(|(_, addr)| String::from(addr)))
+                .expect("peer exited before sending address")
+        };
+
+        let offer = next_addr(&mut out_a);
+        writeln!(b.stdin.as_mut().unwrap(), "{}", offer).unwrap();
+
+        let answer = next_addr(&mut out_b);
+        writeln!(a.stdin.as_mut().unwrap(), "{}", answer).unwrap();
+
+        assert!(a.wait().unwrap().success());
+        assert!(b.wait().unwrap().success());
+    }
+}
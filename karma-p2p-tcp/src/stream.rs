@@ -0,0 +1,92 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{ready, AsyncRead, AsyncWrite};
+use smol::Task;
+
+use crate::{socket::TcpSubstream, Result};
+
+enum StreamState {
+    Connecting(Task<Result<TcpSubstream>>),
+    Connected(TcpSubstream),
+    Failed,
+}
+
+/// Stream multiplexed over TCP connection of socket.
+///
+/// Stream connected before connection is established opens once it is, reads and
+/// writes are pending until then.
+pub struct TcpStream {
+    state: StreamState,
+}
+
+impl TcpStream {
+    pub(crate) fn connecting(task: Task<Result<TcpSubstream>>) -> Self {
+        Self {
+            state: StreamState::Connecting(task),
+        }
+    }
+
+    pub(crate) fn connected(stream: TcpSubstream) -> Self {
+        Self {
+            state: StreamState::Connected(stream),
+        }
+    }
+
+    fn poll_connected(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<&mut TcpSubstream>> {
+        if let StreamState::Connecting(fu) = &mut self.state {
+            match ready!(Pin::new(fu).poll(cx)) {
+                Ok(stream) => self.state = StreamState::Connected(stream),
+                Err(e) => {
+                    self.state = StreamState::Failed;
+                    return Poll::Ready(Err(e.into()));
+                }
+            }
+        }
+
+        match &mut self.state {
+            StreamState::Connected(stream) => Poll::Ready(Ok(stream)),
+            _ => Poll::Ready(Err(io::ErrorKind::NotConnected.into())),
+        }
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_close(cx)
+    }
+}
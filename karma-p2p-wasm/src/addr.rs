@@ -1,5 +1,7 @@
-use serde::{Deserialize, Serialize};
-use web_sys::{RtcIceCandidate, RtcSessionDescriptionInit};
+use js_sys::Reflect;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use wasm_bindgen::JsValue;
+use web_sys::{RtcIceCandidate, RtcIceCandidateInit, RtcSdpType, RtcSessionDescriptionInit};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum CredentialType {
@@ -18,9 +20,126 @@ pub struct IceServer {
     pub username: String,
 }
 
+/// Address of socket, `SDP`, `ICE` and `EndOfCandidates` are serialized as the native
+/// backend does, so browser and native peers signal each other.
 pub enum WebrtcAddr {
     Bootstrap(Vec<IceServer>),
     SDP(RtcSessionDescriptionInit),
     ICE(RtcIceCandidate),
+
+    /// Gathering is complete, no more `ICE` follows until ICE restarts.
+    EndOfCandidates,
+
     Label(String),
 }
+
+#[derive(Serialize, Deserialize)]
+struct WireSdp {
+    #[serde(rename = "type")]
+    sdp_type: String,
+    sdp: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireIce {
+    candidate: String,
+    sdp_mid: String,
+    sdp_mline_index: u16,
+    username_fragment: String,
+}
+
+#[derive(Serialize, Deserialize)]
+enum WireAddr {
+    #[serde(rename = "SDP")]
+    Sdp(WireSdp),
+    #[serde(rename = "ICE")]
+    Ice(WireIce),
+    EndOfCandidates,
+}
+
+fn get_string(target: &JsValue, key: &str) -> Option<String> {
+    Reflect::get(target, &JsValue::from_str(key))
+        .ok()
+        .and_then(|v| v.as_string())
+}
+
+impl Serialize for WebrtcAddr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let wire = match self {
+            WebrtcAddr::SDP(s) => WireAddr::Sdp(WireSdp {
+                sdp_type: get_string(s, "type").unwrap_or_default(),
+                sdp: get_string(s, "sdp").unwrap_or_default(),
+            }),
+            WebrtcAddr::ICE(i) => WireAddr::Ice(WireIce {
+                candidate: i.candidate(),
+                sdp_mid: i.sdp_mid().unwrap_or_default(),
+                sdp_mline_index: i.sdp_m_line_index().unwrap_or_default(),
+                username_fragment: get_string(i, "usernameFragment").unwrap_or_default(),
+            }),
+            WebrtcAddr::EndOfCandidates => WireAddr::EndOfCandidates,
+            _ => {
+                return Err(ser::Error::custom(
+                    "only SDP, ICE and EndOfCandidates are serialized",
+                ))
+            }
+        };
+
+        wire.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for WebrtcAddr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let addr = match WireAddr::deserialize(deserializer)? {
+            WireAddr::Sdp(s) => {
+                let sdp_type = RtcSdpType::from_js_value(&JsValue::from_str(&s.sdp_type))
+                    .ok_or_else(|| de::Error::custom("unknown sdp type"))?;
+
+                let mut obj = RtcSessionDescriptionInit::new(sdp_type);
+                obj.sdp(&s.sdp);
+
+                WebrtcAddr::SDP(obj)
+            }
+            WireAddr::Ice(i) => {
+                let mut init = RtcIceCandidateInit::new(&i.candidate);
+                init.sdp_mid(Some(&i.sdp_mid));
+                init.sdp_m_line_index(Some(i.sdp_mline_index));
+
+                if !i.username_fragment.is_empty() {
+                    let _ = Reflect::set(
+                        &init,
+                        &JsValue::from_str("usernameFragment"),
+                        &JsValue::from_str(&i.username_fragment),
+                    );
+                }
+
+                let candidate = RtcIceCandidate::new(&init)
+                    .map_err(|_| de::Error::custom("invalid ice candidate"))?;
+
+                WebrtcAddr::ICE(candidate)
+            }
+            WireAddr::EndOfCandidates => WebrtcAddr::EndOfCandidates,
+        };
+
+        Ok(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WebrtcAddr;
+
+    #[test]
+    fn serialize_as_native() {
+        let end = serde_json::to_string(&WebrtcAddr::EndOfCandidates).unwrap();
+        assert_eq!(end, "\"EndOfCandidates\"");
+
+        let addr: WebrtcAddr = serde_json::from_str(&end).unwrap();
+        assert!(matches!(addr, WebrtcAddr::EndOfCandidates));
+
+        // Local only addresses are never signaled.
+        assert!(serde_json::to_string(&WebrtcAddr::Label(String::from("x"))).is_err());
+        assert!(serde_json::to_string(&WebrtcAddr::Bootstrap(Vec::new())).is_err());
+        assert!(serde_json::from_str::<WebrtcAddr>("{\"Label\":\"x\"}").is_err());
+    }
+}
@@ -1,6 +1,8 @@
 mod socket;
 pub use socket::*;
 
+pub use karma_p2p::ReconnectPolicy;
+
 mod stream;
 pub use stream::*;
 
@@ -1,43 +1,69 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::VecDeque,
-    mem,
     pin::Pin,
     rc::Rc,
     task::{Context, Poll, Waker},
 };
 
-use futures_lite::FutureExt;
-use js_sys::Reflect;
-use karma_p2p::P2pSocket;
+use futures_lite::{future, ready, Future, FutureExt};
+use futures_timer::Delay;
+use js_sys::{Reflect, JSON};
+use karma_p2p::{
+    EventQueue, P2pSocket, P2pSocketEvents, ReconnectBackoff, ReconnectPolicy, SocketEvent,
+};
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    RtcConfiguration, RtcDataChannelInit, RtcPeerConnection, RtcPeerConnectionIceEvent, RtcSdpType,
-    RtcSessionDescriptionInit,
+    RtcConfiguration, RtcDataChannelEvent, RtcDataChannelInit, RtcIceConnectionState,
+    RtcOfferOptions, RtcPeerConnection, RtcPeerConnectionIceEvent, RtcPeerConnectionState,
+    RtcSdpType, RtcSessionDescriptionInit,
 };
 
 use crate::{Error, Result, WebrtcAddr, WebrtcStream};
 
+type OpFuture<T> = Pin<Box<dyn Future<Output = Result<T>>>>;
+
+#[derive(Default)]
 struct AddressFutureInner {
     pub waker: Option<Waker>,
     pub address: VecDeque<WebrtcAddr>,
+
+    // Candidates gathered while restart offer is created, remote would drop them if
+    // they arrived before the offer.
+    pub held: Option<Vec<WebrtcAddr>>,
 }
 
-impl Default for AddressFutureInner {
-    fn default() -> Self {
-        Self {
-            waker: None,
-            address: VecDeque::new(),
+impl AddressFutureInner {
+    pub fn push_candidate(&mut self, addr: WebrtcAddr) {
+        match &mut self.held {
+            Some(held) => held.push(addr),
+            None => self.set_addr(addr),
         }
     }
-}
 
-impl AddressFutureInner {
     pub fn set_addr(&mut self, addr: WebrtcAddr) {
         self.address.push_back(addr);
 
-        let waker = mem::replace(&mut self.waker, None);
+        let waker = self.waker.take();
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct AcceptFutureInner {
+    pub waker: Option<Waker>,
+    pub streams: VecDeque<WebrtcStream>,
+}
+
+impl AcceptFutureInner {
+    pub fn set_stream(&mut self, stream: WebrtcStream) {
+        self.streams.push_back(stream);
+
+        let waker = self.waker.take();
 
         if let Some(waker) = waker {
             waker.wake();
@@ -48,12 +74,72 @@ impl AddressFutureInner {
 pub struct WebrtcSocket {
     pc: RtcPeerConnection,
     inner: Rc<RefCell<AddressFutureInner>>,
+    accept: Rc<RefCell<AcceptFutureInner>>,
+    events: EventQueue,
+    policy: Rc<Cell<ReconnectPolicy>>,
+    offerer: Rc<Cell<bool>>,
+
+    // In-flight start, kept until it completes.
+    starting: Option<OpFuture<()>>,
+}
+
+/// Create and set local offer, new ICE credentials are used on `ice_restart`.
+///
+/// Candidates of restart are held until its offer is queued, as on native sockets.
+async fn offer(
+    pc: &RtcPeerConnection,
+    inner: &Rc<RefCell<AddressFutureInner>>,
+    ice_restart: bool,
+) -> Result<()> {
+    if ice_restart {
+        let mut re = inner.borrow_mut();
+
+        // Restart in progress, its offer carries new credentials already.
+        if re.held.is_some() {
+            return Ok(());
+        }
+
+        re.held = Some(Vec::new());
+    }
+
+    let res = set_offer(pc, ice_restart).await;
+
+    let mut re = inner.borrow_mut();
+
+    let held = re.held.take().unwrap_or_default();
+
+    re.set_addr(WebrtcAddr::SDP(res?));
+
+    for addr in held {
+        re.set_addr(addr);
+    }
+
+    Ok(())
+}
+
+/// Offer set as local description.
+async fn set_offer(pc: &RtcPeerConnection, ice_restart: bool) -> Result<RtcSessionDescriptionInit> {
+    let mut options = RtcOfferOptions::new();
+    options.ice_restart(ice_restart);
+
+    let offer = JsFuture::from(pc.create_offer_with_rtc_offer_options(&options)).await?;
+
+    let offer_sdp = Reflect::get(&offer, &JsValue::from_str("sdp"))?
+        .as_string()
+        .unwrap();
+
+    let mut offer_obj = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+    offer_obj.sdp(&offer_sdp);
+
+    JsFuture::from(pc.set_local_description(&offer_obj)).await?;
+
+    Ok(offer_obj)
 }
 
 impl WebrtcSocket {
     async fn _bind(bootstrap: WebrtcAddr) -> Result<Self> {
         if let WebrtcAddr::Bootstrap(addr) = bootstrap {
-            let ice_servers = JsValue::from_serde(&addr)?;
+            let ice_servers = JSON::parse(&serde_json::to_string(&addr)?)?;
 
             let mut config = RtcConfiguration::new();
             config.ice_servers(&ice_servers);
@@ -65,31 +151,150 @@ impl WebrtcSocket {
             let inner_clone = inner.clone();
 
             let on_ice_candidate = Closure::wrap(Box::new(move |ev: RtcPeerConnectionIceEvent| {
-                if let Some(candidate) = ev.candidate() {
-                    let inner = inner_clone.clone();
-                    {
-                        let mut re = inner.borrow_mut();
-                        let addr = WebrtcAddr::ICE(candidate);
-
-                        re.set_addr(addr);
-                    }
-                }
+                let addr = match ev.candidate() {
+                    Some(candidate) => WebrtcAddr::ICE(candidate),
+                    None => WebrtcAddr::EndOfCandidates,
+                };
+
+                let mut re = inner_clone.borrow_mut();
+
+                re.push_candidate(addr);
             })
                 as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
 
             pc.set_onicecandidate(Some(on_ice_candidate.as_ref().unchecked_ref()));
 
-            Ok(WebrtcSocket { pc, inner })
+            let events = EventQueue::new();
+
+            // Listeners are added rather than set, handlers set by users are kept.
+            let events_clone = events.clone();
+            let pc_clone = pc.clone();
+
+            let on_ice_state = Closure::wrap(Box::new(move || {
+                if pc_clone.ice_connection_state() == RtcIceConnectionState::Checking {
+                    events_clone.push(SocketEvent::Checking);
+                }
+            }) as Box<dyn FnMut()>);
+
+            pc.add_event_listener_with_callback(
+                "iceconnectionstatechange",
+                on_ice_state.as_ref().unchecked_ref(),
+            )?;
+            on_ice_state.forget();
+
+            let policy = Rc::new(Cell::new(ReconnectPolicy::default()));
+            let offerer = Rc::new(Cell::new(false));
+
+            // Automatic restart is pending or running, losses reported meanwhile are
+            // covered by it.
+            let restarting = Rc::new(Cell::new(false));
+            let backoff = Rc::new(RefCell::new(ReconnectBackoff::new()));
+
+            let events_clone = events.clone();
+            let pc_clone = pc.clone();
+            let inner_clone = inner.clone();
+            let (policy_clone, offerer_clone) = (policy.clone(), offerer.clone());
+
+            let on_state = Closure::wrap(Box::new(move || {
+                let state = pc_clone.connection_state();
+
+                let event = match state {
+                    RtcPeerConnectionState::Connected => Some(SocketEvent::Connected),
+                    RtcPeerConnectionState::Disconnected => Some(SocketEvent::Disconnected),
+                    RtcPeerConnectionState::Failed => Some(SocketEvent::Failed),
+                    RtcPeerConnectionState::Closed => Some(SocketEvent::Closed),
+                    _ => None,
+                };
+
+                let event = match event {
+                    Some(event) => event,
+                    None => return,
+                };
+
+                if event == SocketEvent::Connected {
+                    backoff.borrow_mut().reset();
+                }
+
+                // Only the side which sent the first offer restarts.
+                if offerer_clone.get()
+                    && policy_clone.get().applies(&event)
+                    && !restarting.replace(true)
+                {
+                    let (pc, inner) = (pc_clone.clone(), inner_clone.clone());
+                    let restarting = restarting.clone();
+                    let delay = backoff.borrow_mut().next_delay();
+
+                    wasm_bindgen_futures::spawn_local(async move {
+                        Delay::new(delay).await;
+
+                        // Connection stays as reported by events if restart fails.
+                        let _ = offer(&pc, &inner, true).await;
+
+                        restarting.set(false);
+                    });
+                }
+
+                events_clone.push(event);
+
+                // Connection can't come back once closed, so events end.
+                if state == RtcPeerConnectionState::Closed {
+                    events_clone.close();
+                }
+            }) as Box<dyn FnMut()>);
+
+            pc.add_event_listener_with_callback(
+                "connectionstatechange",
+                on_state.as_ref().unchecked_ref(),
+            )?;
+            on_state.forget();
+
+            let accept = Rc::new(RefCell::new(AcceptFutureInner::default()));
+
+            let accept_clone = accept.clone();
+            let events_clone = events.clone();
+
+            let on_data_channel = Closure::wrap(Box::new(move |ev: RtcDataChannelEvent| {
+                let ws = WebrtcStream::new(ev.channel());
+                ws.init();
+
+                // Channel is still accepted if its events can't be listened.
+                let _ = ws.report_events(&events_clone);
+
+                let mut re = accept_clone.borrow_mut();
+
+                re.set_stream(ws);
+            })
+                as Box<dyn FnMut(RtcDataChannelEvent)>);
+
+            pc.set_ondatachannel(Some(on_data_channel.as_ref().unchecked_ref()));
+            on_data_channel.forget();
+
+            Ok(WebrtcSocket {
+                pc,
+                inner,
+                accept,
+                events,
+                policy,
+                offerer,
+                starting: None,
+            })
         } else {
             Err(Error::ErrAddrType)
         }
     }
 
-    async fn _connect(&self, label: WebrtcAddr, port: u16) -> Result<WebrtcStream> {
+    /// Open data channel `label`.
+    ///
+    /// Non-zero `port` is used as the negotiated channel id, so both sides must connect
+    /// with the same `label` and `port`. Port `0` announces the channel to the remote,
+    /// which receives it from `accept`.
+    fn _connect(&self, label: WebrtcAddr, port: u16) -> Result<WebrtcStream> {
         if let WebrtcAddr::Label(label) = label {
             let mut dc_init = RtcDataChannelInit::new();
 
-            dc_init.id(port).negotiated(true);
+            if port != 0 {
+                dc_init.id(port).negotiated(true);
+            }
 
             let dc = self
                 .pc
@@ -97,6 +302,7 @@ impl WebrtcSocket {
 
             let ws = WebrtcStream::new(dc);
             ws.init();
+            ws.report_events(&self.events)?;
 
             Ok(ws)
         } else {
@@ -104,50 +310,52 @@ impl WebrtcSocket {
         }
     }
 
-    async fn _start(&mut self) -> Result<()> {
-        let offer = JsFuture::from(self.pc.create_offer()).await?;
-
-        let offer_sdp = Reflect::get(&offer, &JsValue::from_str("sdp"))?
-            .as_string()
-            .unwrap();
-
-        let mut offer_obj = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
-        offer_obj.sdp(&offer_sdp);
-
-        JsFuture::from(self.pc.set_local_description(&offer_obj)).await?;
-
-        let addr = WebrtcAddr::SDP(offer_obj);
-        {
-            let mut re = self.inner.borrow_mut();
-
-            re.set_addr(addr);
-        }
+    /// Restart ICE, streams are kept while candidates are gathered again.
+    ///
+    /// The restart offer and new candidates come from `fetch_local_addr`, so signaling
+    /// must still be exchanged. Only one side should restart at a time, a restart asked
+    /// while one is in progress is covered by it.
+    pub async fn restart_ice(&self) -> Result<()> {
+        offer(&self.pc, &self.inner, true).await
+    }
 
-        Ok(())
+    /// Restart ICE by itself on connection loss, see [`ReconnectPolicy`].
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        self.policy.set(policy)
     }
 
-    async fn _set_remote_addr(&self, remote: WebrtcAddr) -> Result<()> {
+    async fn _set_remote_addr(
+        pc: RtcPeerConnection,
+        inner: Rc<RefCell<AddressFutureInner>>,
+        remote: WebrtcAddr,
+    ) -> Result<()> {
         match remote {
             WebrtcAddr::SDP(s) => {
-                JsFuture::from(self.pc.set_remote_description(&s)).await?;
+                let sdp_type = Reflect::get(&s, &JsValue::from_str("type"))?.as_string();
 
-                let answer = JsFuture::from(self.pc.create_answer()).await?;
+                JsFuture::from(pc.set_remote_description(&s)).await?;
 
-                let sdp = Reflect::get(&answer, &JsValue::from_str("sdp"))?
-                    .as_string()
-                    .unwrap();
+                // Answer of remote needs no reply.
+                if sdp_type.as_deref() == Some("offer") {
+                    let answer = JsFuture::from(pc.create_answer()).await?;
 
-                let mut obj = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
-                obj.sdp(&sdp);
+                    let sdp = Reflect::get(&answer, &JsValue::from_str("sdp"))?
+                        .as_string()
+                        .unwrap();
 
-                JsFuture::from(self.pc.set_local_description(&obj)).await?;
+                    let mut obj = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                    obj.sdp(&sdp);
+
+                    JsFuture::from(pc.set_local_description(&obj)).await?;
+
+                    inner.borrow_mut().set_addr(WebrtcAddr::SDP(obj));
+                }
             }
             WebrtcAddr::ICE(ice) => {
-                JsFuture::from(
-                    self.pc
-                        .add_ice_candidate_with_opt_rtc_ice_candidate(Some(&ice)),
-                )
-                .await?;
+                JsFuture::from(pc.add_ice_candidate_with_opt_rtc_ice_candidate(Some(&ice))).await?;
+            }
+            WebrtcAddr::EndOfCandidates => {
+                JsFuture::from(pc.add_ice_candidate_with_opt_rtc_ice_candidate(None)).await?;
             }
             _ => return Err(Error::ErrAddrType),
         }
@@ -156,6 +364,20 @@ impl WebrtcSocket {
     }
 }
 
+impl P2pSocketEvents for WebrtcSocket {
+    fn poll_event(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        self.events.poll_next(cx)
+    }
+
+    fn poll_state(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>> {
+        self.events.poll_state(cx, seen)
+    }
+}
+
 impl P2pSocket for WebrtcSocket {
     type Error = Error;
 
@@ -163,29 +385,50 @@ impl P2pSocket for WebrtcSocket {
 
     type Addr = WebrtcAddr;
 
-    fn poll_bind(cx: &mut Context<'_>, bootstrap: Self::Addr) -> Poll<Result<Self>> {
-        let mut fu = Box::pin(async move { WebrtcSocket::_bind(bootstrap).await });
+    type Binding = OpFuture<Self>;
+
+    type Connecting = future::Ready<Result<WebrtcStream>>;
+
+    type SettingRemoteAddr = OpFuture<()>;
 
-        fu.poll(cx)
+    fn binding(bootstrap: Self::Addr) -> Self::Binding {
+        Box::pin(WebrtcSocket::_bind(bootstrap))
     }
 
-    fn poll_connect(
-        self: std::pin::Pin<&Self>,
-        cx: &mut Context<'_>,
-        label: Self::Addr,
-        port: u16,
-    ) -> Poll<Result<Self::Stream>> {
-        let mut fu = Box::pin(async move { self._connect(label, port).await });
+    fn connecting(&self, label: Self::Addr, port: u16) -> Self::Connecting {
+        future::ready(self._connect(label, port))
+    }
+
+    fn poll_accept(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Result<Self::Stream>> {
+        let mut re = self.accept.borrow_mut();
 
-        fu.poll(cx)
+        re.waker.replace(cx.waker().clone());
+
+        if let Some(stream) = re.streams.pop_front() {
+            Poll::Ready(Ok(stream))
+        } else {
+            Poll::Pending
+        }
     }
 
     fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        let mut this = self;
+        let this = self.get_mut();
+
+        let fu = this.starting.get_or_insert_with(|| {
+            let (pc, inner) = (this.pc.clone(), this.inner.clone());
+
+            Box::pin(async move { offer(&pc, &inner, false).await })
+        });
 
-        let mut fu = Box::pin(async move { this._start().await });
+        let res = ready!(fu.poll(cx));
 
-        fu.poll(cx)
+        this.starting = None;
+
+        if res.is_ok() {
+            this.offerer.set(true);
+        }
+
+        Poll::Ready(res)
     }
 
     fn poll_fetch_local_addr(
@@ -194,7 +437,7 @@ impl P2pSocket for WebrtcSocket {
     ) -> Poll<Result<Self::Addr>> {
         let mut re = self.inner.borrow_mut();
 
-        let _ = mem::replace(&mut re.waker, Some(cx.waker().clone()));
+        re.waker.replace(cx.waker().clone());
 
         if let Some(addr) = re.address.pop_front() {
             Poll::Ready(Ok(addr))
@@ -203,13 +446,15 @@ impl P2pSocket for WebrtcSocket {
         }
     }
 
-    fn poll_set_remote_addr(
-        self: Pin<&Self>,
-        cx: &mut Context<'_>,
-        remote: Self::Addr,
-    ) -> Poll<Result<()>> {
-        let mut fu = Box::pin(async move { self._set_remote_addr(remote).await });
+    fn setting_remote_addr(&self, remote: Self::Addr) -> Self::SettingRemoteAddr {
+        Box::pin(Self::_set_remote_addr(
+            self.pc.clone(),
+            self.inner.clone(),
+            remote,
+        ))
+    }
 
-        fu.poll(cx)
+    fn is_last_addr(addr: &Self::Addr) -> bool {
+        matches!(addr, WebrtcAddr::EndOfCandidates)
     }
 }
@@ -1,27 +1,86 @@
 use std::{
     cell::RefCell,
+    cmp,
     collections::VecDeque,
-    mem,
     pin::Pin,
     rc::Rc,
     task::{Context, Poll, Waker},
 };
 
+use bytes::{Buf, Bytes};
 use futures_lite::{AsyncRead, AsyncWrite};
 use js_sys::Uint8Array;
+use karma_p2p::{EventQueue, P2pMessageStream, SocketEvent};
 use wasm_bindgen::{prelude::Closure, JsCast};
-use web_sys::{MessageEvent, RtcDataChannel};
+use web_sys::{MessageEvent, RtcDataChannel, RtcDataChannelType};
 
+use crate::{Error, Result};
+
+#[derive(Default)]
 pub struct ReadFutureInner {
     pub waker: Option<Waker>,
-    pub data: VecDeque<Vec<u8>>,
+    pub data: VecDeque<Bytes>,
+    pub closed: bool,
 }
 
-impl Default for ReadFutureInner {
-    fn default() -> Self {
-        Self {
-            waker: None,
-            data: VecDeque::new(),
+impl ReadFutureInner {
+    pub fn push_data(&mut self, data: Bytes) {
+        self.data.push_back(data);
+        self.wake();
+    }
+
+    pub fn set_closed(&mut self) {
+        self.closed = true;
+        self.wake();
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Receive next message, begin with rest of partially read message.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        if let Some(data) = self.data.pop_front() {
+            Poll::Ready(Some(data))
+        } else if self.closed {
+            Poll::Ready(None)
+        } else {
+            self.waker.replace(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Read bytes of received messages, keep what not fit into `buf` for next read.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<usize> {
+        if buf.is_empty() {
+            return Poll::Ready(0);
+        }
+
+        while let Some(data) = self.data.front_mut() {
+            if data.is_empty() {
+                self.data.pop_front();
+                continue;
+            }
+
+            let size = cmp::min(buf.len(), data.len());
+
+            buf[..size].copy_from_slice(&data[..size]);
+            data.advance(size);
+
+            if data.is_empty() {
+                self.data.pop_front();
+            }
+
+            return Poll::Ready(size);
+        }
+
+        if self.closed {
+            Poll::Ready(0)
+        } else {
+            self.waker.replace(cx.waker().clone());
+            Poll::Pending
         }
     }
 }
@@ -42,6 +101,8 @@ impl WebrtcStream {
 
 impl WebrtcStream {
     pub fn init(&self) {
+        self.dc.set_binary_type(RtcDataChannelType::Arraybuffer);
+
         let inner = self.inner.clone();
 
         let on_message = Closure::wrap(Box::new(move |ev: MessageEvent| {
@@ -51,24 +112,57 @@ impl WebrtcStream {
 
             let mut re = inner.borrow_mut();
 
-            re.data.push_back(data_vec);
-
-            let waker = mem::replace(&mut re.waker, None);
-
-            if let Some(waker) = waker {
-                waker.wake();
-            }
+            re.push_data(Bytes::from(data_vec));
         }) as Box<dyn FnMut(MessageEvent)>);
 
         self.dc
             .set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        on_message.forget();
+
+        let inner = self.inner.clone();
+
+        let on_close = Closure::wrap(Box::new(move || {
+            let mut re = inner.borrow_mut();
+
+            re.set_closed();
+        }) as Box<dyn FnMut()>);
+
+        self.dc.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        on_close.forget();
+    }
+
+    /// Report open and close of channel to `events` of socket, as listeners so
+    /// handlers of `init` are kept.
+    pub(crate) fn report_events(&self, events: &EventQueue) -> Result<()> {
+        let label = self.dc.label();
+        let events_clone = events.clone();
+
+        let on_open = Closure::wrap(Box::new(move || {
+            events_clone.push(SocketEvent::ChannelOpened(label.clone()));
+        }) as Box<dyn FnMut()>);
+
+        self.dc
+            .add_event_listener_with_callback("open", on_open.as_ref().unchecked_ref())?;
+        on_open.forget();
+
+        let label = self.dc.label();
+        let events = events.clone();
+
+        let on_close = Closure::wrap(Box::new(move || {
+            events.push(SocketEvent::ChannelClosed(label.clone()));
+        }) as Box<dyn FnMut()>);
+
+        self.dc
+            .add_event_listener_with_callback("close", on_close.as_ref().unchecked_ref())?;
+        on_close.forget();
+
+        Ok(())
     }
 
     pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
         let size = buf.len();
         if let Err(e) = self.dc.send_with_u8_array(buf) {
-            let value: String = e.into_serde()?;
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, value));
+            return Err(std::io::Error::other(format!("{:?}", e)));
         }
         Ok(size)
     }
@@ -87,15 +181,7 @@ impl AsyncRead for WebrtcStream {
     ) -> Poll<std::io::Result<usize>> {
         let mut re = self.inner.borrow_mut();
 
-        let _ = mem::replace(&mut re.waker, Some(cx.waker().clone()));
-
-        if let Some(data) = re.data.pop_front() {
-            let size = data.len();
-            buf.copy_from_slice(&data);
-            Poll::Ready(Ok(size))
-        } else {
-            Poll::Pending
-        }
+        re.poll_read(cx, buf).map(Ok)
     }
 }
 
@@ -117,3 +203,153 @@ impl AsyncWrite for WebrtcStream {
         Poll::Ready(Ok(()))
     }
 }
+
+impl P2pMessageStream for WebrtcStream {
+    type Error = Error;
+
+    fn poll_recv(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes>>> {
+        let mut re = self.inner.borrow_mut();
+
+        re.poll_recv(cx).map(|m| m.map(Ok))
+    }
+
+    fn poll_send_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Bytes) -> Result<()> {
+        self.dc.send_with_u8_array(&message)?;
+        Ok(())
+    }
+
+    fn poll_send_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_send_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.dc.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use bytes::Bytes;
+    use futures_lite::future::{block_on, poll_fn};
+
+    use super::ReadFutureInner;
+
+    async fn read(inner: &mut ReadFutureInner, buf: &mut [u8]) -> usize {
+        poll_fn(|cx| inner.poll_read(cx, buf)).await
+    }
+
+    #[test]
+    fn small_buffer() {
+        let mut inner = ReadFutureInner::default();
+
+        inner.push_data(Bytes::from_static(b"hello world"));
+
+        block_on(async {
+            let mut buf = [0u8; 4];
+
+            assert_eq!(read(&mut inner, &mut buf).await, 4);
+            assert_eq!(&buf, b"hell");
+            assert_eq!(read(&mut inner, &mut buf).await, 4);
+            assert_eq!(&buf, b"o wo");
+            assert_eq!(read(&mut inner, &mut buf).await, 3);
+            assert_eq!(&buf[..3], b"rld");
+        });
+    }
+
+    #[test]
+    fn large_message() {
+        let mut inner = ReadFutureInner::default();
+
+        let data: Vec<u8> = (0..256 * 1024).map(|i| i as u8).collect();
+
+        inner.push_data(Bytes::from(data.clone()));
+        inner.push_data(Bytes::new());
+        inner.push_data(Bytes::from(data.clone()));
+        inner.set_closed();
+
+        block_on(async {
+            let mut out = Vec::new();
+            let mut buf = [0u8; 1000];
+
+            loop {
+                let size = read(&mut inner, &mut buf).await;
+                if size == 0 {
+                    break;
+                }
+                out.extend_from_slice(&buf[..size]);
+            }
+
+            assert_eq!(out.len(), data.len() * 2);
+            assert_eq!(&out[..data.len()], &data[..]);
+            assert_eq!(&out[data.len()..], &data[..]);
+        });
+    }
+
+    #[test]
+    fn pending_until_data_or_close() {
+        let mut inner = ReadFutureInner::default();
+        let waker = block_on(poll_fn(|cx| Poll::Ready(cx.waker().clone())));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut buf = [0u8; 8];
+
+        assert_eq!(inner.poll_read(&mut cx, &mut buf), Poll::Pending);
+        assert!(inner.waker.is_some());
+
+        inner.set_closed();
+
+        assert_eq!(inner.poll_read(&mut cx, &mut buf), Poll::Ready(0));
+        assert_eq!(inner.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn message_boundaries() {
+        let mut inner = ReadFutureInner::default();
+
+        let messages = [&b"a"[..], &b""[..], &[7u8; 1000][..], &b"tail"[..]];
+
+        for message in messages {
+            inner.push_data(Bytes::copy_from_slice(message));
+        }
+        inner.set_closed();
+
+        block_on(async {
+            for message in messages {
+                let recv = poll_fn(|cx| inner.poll_recv(cx)).await;
+                assert_eq!(recv.as_deref(), Some(message));
+            }
+
+            assert_eq!(poll_fn(|cx| inner.poll_recv(cx)).await, None);
+        });
+    }
+
+    #[test]
+    fn recv_rest_of_message() {
+        let mut inner = ReadFutureInner::default();
+
+        inner.push_data(Bytes::from_static(b"abcdef"));
+        inner.push_data(Bytes::from_static(b"gh"));
+        inner.set_closed();
+
+        block_on(async {
+            let mut buf = [0u8; 2];
+            assert_eq!(read(&mut inner, &mut buf).await, 2);
+
+            let rest = poll_fn(|cx| inner.poll_recv(cx)).await;
+            assert_eq!(rest, Some(Bytes::from_static(b"cdef")));
+
+            let next = poll_fn(|cx| inner.poll_recv(cx)).await;
+            assert_eq!(next, Some(Bytes::from_static(b"gh")));
+
+            let end = poll_fn(|cx| inner.poll_recv(cx)).await;
+            assert_eq!(end, None);
+        });
+    }
+}
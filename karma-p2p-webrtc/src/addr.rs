@@ -5,7 +5,10 @@ use webrtc::{
 
 use serde::{Deserialize, Serialize};
 
+use crate::Envelope;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum WebrtcAddr {
     #[serde(skip)]
     Bootstrap(Vec<RTCIceServer>),
@@ -15,4 +18,11 @@ pub enum WebrtcAddr {
 
     SDP(RTCSessionDescription),
     ICE(RTCIceCandidateInit),
+
+    /// Gathering is complete, no more `ICE` follows until ICE restarts.
+    EndOfCandidates,
+
+    /// `SDP`, `ICE` or `EndOfCandidates` signed by sender, emitted once socket has an
+    /// identity.
+    Signed(Envelope),
 }
@@ -0,0 +1,153 @@
+use karma_p2p::ReconnectPolicy;
+use webrtc::{
+    api::{
+        interceptor_registry::register_default_interceptors, media_engine::MediaEngine,
+        setting_engine::SettingEngine, APIBuilder,
+    },
+    ice::{
+        mdns::MulticastDnsMode,
+        network_type::NetworkType,
+        udp_network::{EphemeralUDP, UDPNetwork},
+    },
+    ice_transport::{ice_candidate_type::RTCIceCandidateType, ice_server::RTCIceServer},
+    interceptor::registry::Registry,
+    peer_connection::{
+        configuration::RTCConfiguration,
+        policy::{bundle_policy::RTCBundlePolicy, ice_transport_policy::RTCIceTransportPolicy},
+    },
+};
+
+use crate::{DtlsCertificate, Result, WebrtcSocket};
+
+/// Build [`WebrtcSocket`] with peer connection configured beyond ice servers.
+///
+/// Binding by `WebrtcAddr::Bootstrap` is the same as a default builder with ice
+/// servers set.
+#[derive(Default)]
+pub struct WebrtcSocketBuilder {
+    config: RTCConfiguration,
+    setting: SettingEngine,
+    port_range: Option<(u16, u16)>,
+    certificate: Option<DtlsCertificate>,
+    data_only: bool,
+    reconnect_policy: ReconnectPolicy,
+}
+
+impl WebrtcSocketBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace whole configuration, options set before on it are dropped.
+    pub fn with_configuration(mut self, config: RTCConfiguration) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Replace whole setting engine, options set before on it are dropped.
+    pub fn with_setting_engine(mut self, setting: SettingEngine) -> Self {
+        self.setting = setting;
+        self
+    }
+
+    pub fn ice_servers(mut self, ice_servers: Vec<RTCIceServer>) -> Self {
+        self.config.ice_servers = ice_servers;
+        self
+    }
+
+    /// Use `Relay` to only connect through TURN servers.
+    pub fn ice_transport_policy(mut self, policy: RTCIceTransportPolicy) -> Self {
+        self.config.ice_transport_policy = policy;
+        self
+    }
+
+    pub fn bundle_policy(mut self, policy: RTCBundlePolicy) -> Self {
+        self.config.bundle_policy = policy;
+        self
+    }
+
+    pub fn ice_candidate_pool_size(mut self, size: u8) -> Self {
+        self.config.ice_candidate_pool_size = size;
+        self
+    }
+
+    /// Networks candidates are gathered on, all of them by default.
+    pub fn network_types(mut self, types: Vec<NetworkType>) -> Self {
+        self.setting.set_network_types(types);
+        self
+    }
+
+    /// Restrict local UDP ports to `min..=max`, checked when socket is bound.
+    pub fn ephemeral_udp_port_range(mut self, min: u16, max: u16) -> Self {
+        self.port_range = Some((min, max));
+        self
+    }
+
+    /// Advertise `ips` as candidates of `candidate_type` in place of local ones, for
+    /// hosts behind 1:1 NAT.
+    pub fn nat_1to1_ips(mut self, ips: Vec<String>, candidate_type: RTCIceCandidateType) -> Self {
+        self.setting.set_nat_1to1_ips(ips, candidate_type);
+        self
+    }
+
+    pub fn mdns_mode(mut self, mode: MulticastDnsMode) -> Self {
+        self.setting.set_ice_multicast_dns_mode(mode);
+        self
+    }
+
+    /// Use persistent `certificate` in place of one generated on bind, so fingerprint
+    /// of socket is stable across runs.
+    pub fn certificate(mut self, certificate: DtlsCertificate) -> Self {
+        self.certificate = Some(certificate);
+        self
+    }
+
+    /// Skip media codecs and interceptors, sockets only carry data channels.
+    pub fn data_only(mut self, data_only: bool) -> Self {
+        self.data_only = data_only;
+        self
+    }
+
+    /// Restart ICE by itself on connection loss, see [`ReconnectPolicy`].
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    pub async fn bind(self) -> Result<WebrtcSocket> {
+        let mut setting = self.setting;
+        let mut config = self.config;
+
+        if let Some(certificate) = &self.certificate {
+            config.certificates = vec![certificate.to_rtc()?];
+        }
+
+        if let Some((min, max)) = self.port_range {
+            setting.set_udp_network(UDPNetwork::Ephemeral(
+                EphemeralUDP::new(min, max).map_err(webrtc::Error::from)?,
+            ));
+        }
+
+        let mut builder = APIBuilder::new().with_setting_engine(setting);
+
+        if !self.data_only {
+            let mut m = MediaEngine::default();
+
+            m.register_default_codecs()?;
+
+            let registry = register_default_interceptors(Registry::default(), &mut m)?;
+
+            builder = builder
+                .with_media_engine(m)
+                .with_interceptor_registry(registry);
+        }
+
+        let pc = builder.build().new_peer_connection(config).await?;
+
+        let socket = WebrtcSocket::from_peer_connection(pc).await?;
+
+        socket.set_reconnect_policy(self.reconnect_policy);
+
+        Ok(socket)
+    }
+}
@@ -0,0 +1,300 @@
+use karma_p2p::{Keypair, PeerId};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, WebrtcAddr};
+
+const ENVELOPE_CONTEXT: &str = "karma-signal-v1";
+
+/// Serialized `WebrtcAddr` signed by its sender for one recipient.
+///
+/// Session is random for each sending socket and sequence number increases for
+/// each address it emits, so a relay can't replay older addresses of the same
+/// sender, nor addresses sent to another peer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub sender: String,
+    pub recipient: String,
+    pub session: String,
+    pub seq: u64,
+    pub payload: String,
+    pub signature: String,
+}
+
+impl Envelope {
+    pub fn seal(
+        keypair: &Keypair,
+        recipient: &PeerId,
+        session: &str,
+        seq: u64,
+        addr: &WebrtcAddr,
+    ) -> Result<Self> {
+        if !matches!(
+            addr,
+            WebrtcAddr::SDP(_) | WebrtcAddr::ICE(_) | WebrtcAddr::EndOfCandidates
+        ) {
+            return Err(Error::ErrAddrType);
+        }
+
+        let payload = serde_json::to_string(addr).map_err(|_| Error::ErrInvalidEnvelope)?;
+
+        let recipient = recipient.to_string();
+
+        let signature = keypair.sign(&signed_message(&recipient, session, seq, &payload));
+
+        Ok(Self {
+            sender: keypair.peer_id().to_string(),
+            recipient,
+            session: String::from(session),
+            seq,
+            payload,
+            signature: bs58::encode(signature).into_string(),
+        })
+    }
+
+    /// Check wrapped address is end of candidates, without verifying it.
+    pub fn is_end_of_candidates(&self) -> bool {
+        matches!(
+            serde_json::from_str(&self.payload),
+            Ok(WebrtcAddr::EndOfCandidates)
+        )
+    }
+
+    /// Verify signature and get sender with wrapped address.
+    pub fn open(&self) -> Result<(PeerId, WebrtcAddr)> {
+        let sender: PeerId = self.sender.parse().map_err(|_| Error::ErrInvalidEnvelope)?;
+
+        let signature = bs58::decode(&self.signature)
+            .into_vec()
+            .map_err(|_| Error::ErrInvalidEnvelope)?;
+
+        sender
+            .verify(
+                &signed_message(&self.recipient, &self.session, self.seq, &self.payload),
+                &signature,
+            )
+            .map_err(|_| Error::ErrInvalidSignature)?;
+
+        let addr = serde_json::from_str(&self.payload).map_err(|_| Error::ErrInvalidEnvelope)?;
+
+        match addr {
+            WebrtcAddr::SDP(_) | WebrtcAddr::ICE(_) | WebrtcAddr::EndOfCandidates => {
+                Ok((sender, addr))
+            }
+            _ => Err(Error::ErrAddrType),
+        }
+    }
+}
+
+fn signed_message(recipient: &str, session: &str, seq: u64, payload: &str) -> Vec<u8> {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        ENVELOPE_CONTEXT, recipient, session, seq, payload
+    )
+    .into_bytes()
+}
+
+/// Random session of a sending socket.
+pub(crate) fn new_session() -> String {
+    let mut session = [0u8; 16];
+    OsRng.fill_bytes(&mut session);
+
+    bs58::encode(session).into_string()
+}
+
+/// Signer of remote addresses accepted by socket.
+#[derive(Debug, Default)]
+pub(crate) struct RemoteSigner {
+    peer: Option<PeerId>,
+    session: Option<String>,
+    seq: Option<u64>,
+}
+
+impl RemoteSigner {
+    pub(crate) fn expect(&mut self, peer: PeerId) {
+        if self.peer != Some(peer) {
+            self.peer = Some(peer);
+            self.session = None;
+            self.seq = None;
+        }
+    }
+
+    pub(crate) fn peer(&self) -> Option<PeerId> {
+        self.peer
+    }
+
+    /// Unwrap `addr` for socket with identity `local`.
+    ///
+    /// Unsigned addresses are only accepted when neither side has an identity. Once
+    /// either is set, both are required and `addr` must be signed by expected peer
+    /// for `local`. First accepted address pins the session of remote socket.
+    pub(crate) fn open(&mut self, local: Option<PeerId>, addr: &WebrtcAddr) -> Result<WebrtcAddr> {
+        let (local, peer) = match (local, self.peer, addr) {
+            (None, None, WebrtcAddr::Signed(_)) => return Err(Error::ErrUnexpectedSigner),
+            (None, None, _) => return Ok(addr.clone()),
+            (Some(local), Some(peer), _) => (local, peer),
+            _ => return Err(Error::ErrMissingIdentity),
+        };
+
+        let envelope = match addr {
+            WebrtcAddr::Signed(e) => e,
+            _ => return Err(Error::ErrUnsignedAddr),
+        };
+
+        let (sender, inner) = envelope.open()?;
+
+        if sender != peer {
+            return Err(Error::ErrUnexpectedSigner);
+        }
+
+        if envelope.recipient != local.to_string() {
+            return Err(Error::ErrUnexpectedRecipient);
+        }
+
+        if self
+            .session
+            .as_ref()
+            .is_some_and(|s| *s != envelope.session)
+        {
+            return Err(Error::ErrUnexpectedSession);
+        }
+
+        if matches!(self.seq, Some(seq) if envelope.seq <= seq) {
+            return Err(Error::ErrStaleSequence);
+        }
+
+        self.session = Some(envelope.session.clone());
+        self.seq = Some(envelope.seq);
+
+        Ok(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use karma_p2p::Keypair;
+    use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
+
+    use super::*;
+
+    fn ice(candidate: &str) -> WebrtcAddr {
+        WebrtcAddr::ICE(RTCIceCandidateInit {
+            candidate: String::from(candidate),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let (keypair, bob) = (Keypair::generate(), Keypair::generate().peer_id());
+
+        let envelope = Envelope::seal(&keypair, &bob, "s", 3, &ice("a")).unwrap();
+
+        let (sender, addr) = envelope.open().unwrap();
+        assert_eq!(sender, keypair.peer_id());
+        assert!(matches!(addr, WebrtcAddr::ICE(i) if i.candidate == "a"));
+
+        // Payload swapped on signaling path.
+        let mut forged = envelope.clone();
+        forged.payload = serde_json::to_string(&ice("b")).unwrap();
+        assert!(matches!(forged.open(), Err(Error::ErrInvalidSignature)));
+
+        let mut forged = envelope.clone();
+        forged.seq = 4;
+        assert!(matches!(forged.open(), Err(Error::ErrInvalidSignature)));
+
+        // Redirected to another peer or replayed from another session.
+        let mut forged = envelope.clone();
+        forged.recipient = keypair.peer_id().to_string();
+        assert!(matches!(forged.open(), Err(Error::ErrInvalidSignature)));
+
+        let mut forged = envelope;
+        forged.session = String::from("t");
+        assert!(matches!(forged.open(), Err(Error::ErrInvalidSignature)));
+
+        let envelope =
+            Envelope::seal(&keypair, &bob, "s", 5, &WebrtcAddr::EndOfCandidates).unwrap();
+        assert!(matches!(
+            envelope.open(),
+            Ok((_, WebrtcAddr::EndOfCandidates))
+        ));
+
+        assert!(matches!(
+            Envelope::seal(
+                &keypair,
+                &bob,
+                "s",
+                0,
+                &WebrtcAddr::Label(String::from("x"))
+            ),
+            Err(Error::ErrAddrType)
+        ));
+    }
+
+    #[test]
+    fn remote_signer() {
+        let (alice, bob, mallory) = (
+            Keypair::generate(),
+            Keypair::generate(),
+            Keypair::generate(),
+        );
+
+        let signed = |k: &Keypair, to: &Keypair, session: &str, seq| {
+            WebrtcAddr::Signed(Envelope::seal(k, &to.peer_id(), session, seq, &ice("a")).unwrap())
+        };
+
+        let local = Some(bob.peer_id());
+
+        // Fail closed while only one side is known.
+        let mut signer = RemoteSigner::default();
+        assert!(matches!(
+            signer.open(local, &ice("a")),
+            Err(Error::ErrMissingIdentity)
+        ));
+        assert!(matches!(
+            signer.open(local, &signed(&alice, &bob, "s", 0)),
+            Err(Error::ErrMissingIdentity)
+        ));
+
+        signer.expect(alice.peer_id());
+
+        assert!(matches!(
+            signer.open(None, &signed(&alice, &bob, "s", 0)),
+            Err(Error::ErrMissingIdentity)
+        ));
+        assert!(matches!(
+            signer.open(local, &ice("a")),
+            Err(Error::ErrUnsignedAddr)
+        ));
+        assert!(matches!(
+            signer.open(local, &signed(&mallory, &bob, "s", 0)),
+            Err(Error::ErrUnexpectedSigner)
+        ));
+        assert!(matches!(
+            signer.open(local, &signed(&alice, &mallory, "s", 0)),
+            Err(Error::ErrUnexpectedRecipient)
+        ));
+
+        assert!(signer.open(local, &signed(&alice, &bob, "s", 0)).is_ok());
+        assert!(signer.open(local, &signed(&alice, &bob, "s", 1)).is_ok());
+        assert!(matches!(
+            signer.open(local, &signed(&alice, &bob, "s", 1)),
+            Err(Error::ErrStaleSequence)
+        ));
+
+        // Sequence restarts in another session, which is not accepted.
+        assert!(matches!(
+            signer.open(local, &signed(&alice, &bob, "t", 2)),
+            Err(Error::ErrUnexpectedSession)
+        ));
+
+        // Without identities only unsigned addresses pass.
+        let mut signer = RemoteSigner::default();
+
+        assert!(signer.open(None, &ice("a")).is_ok());
+        assert!(matches!(
+            signer.open(None, &signed(&mallory, &bob, "s", 0)),
+            Err(Error::ErrUnexpectedSigner)
+        ));
+    }
+}
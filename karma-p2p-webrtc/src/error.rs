@@ -2,7 +2,19 @@
 pub enum Error {
     ErrAddrType,
     ErrChannelClosed,
+    ErrReservedLabel,
+    ErrInvalidEnvelope,
+    ErrInvalidSignature,
+    ErrUnsignedAddr,
+    ErrUnexpectedSigner,
+    ErrStaleSequence,
+    ErrMissingIdentity,
+    ErrUnexpectedRecipient,
+    ErrUnexpectedSession,
+    ErrInvalidCertificate,
+    ErrInvalidFingerprint,
     WebrtcError(webrtc::Error),
+    IoError(std::io::Error),
 }
 
 impl From<smol::channel::RecvError> for Error {
@@ -17,4 +29,37 @@ impl From<webrtc::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WebrtcError(e) => Some(e),
+            Error::IoError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::ErrChannelClosed => std::io::ErrorKind::BrokenPipe.into(),
+            Error::WebrtcError(e) => std::io::Error::other(e),
+            Error::IoError(e) => e,
+            e => std::io::Error::other(format!("{:?}", e)),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,249 @@
+use std::sync::{Arc, Mutex, Weak};
+
+use bytes::Bytes;
+use karma_p2p::{EventQueue, Keypair, PeerId, SocketEvent};
+use webrtc::{data_channel::RTCDataChannel, peer_connection::RTCPeerConnection};
+
+use crate::{Error, Result};
+
+/// Label of channel used by identity handshake, can't be used by `connect`.
+pub const HANDSHAKE_LABEL: &str = "karma-handshake";
+
+const HANDSHAKE_CONTEXT: &str = "karma-handshake-v1";
+
+/// Identity handshake on reserved channel.
+///
+/// Each side sends its public key and a signature over its own DTLS fingerprint and
+/// the fingerprint of remote, both taken from exchanged SDP. Remote verifies it with
+/// fingerprints it negotiated, so a signaling path swapping fingerprints is detected.
+/// When proof can't be made or verified, connection is closed and
+/// `SocketEvent::IdentityFailed` is reported.
+///
+/// Each side announces its own channel rather than using a negotiated id, sctp of
+/// webrtc drops data arriving on a negotiated stream before it's opened locally.
+#[derive(Clone)]
+pub(crate) struct Handshake {
+    identity: Arc<Mutex<Option<Keypair>>>,
+    remote: Arc<Mutex<Option<PeerId>>>,
+    pc: Weak<RTCPeerConnection>,
+    events: EventQueue,
+    _dc: Arc<RTCDataChannel>,
+}
+
+impl Handshake {
+    pub(crate) async fn new(pc: &Arc<RTCPeerConnection>, events: EventQueue) -> Result<Self> {
+        let dc = pc.create_data_channel(HANDSHAKE_LABEL, None).await?;
+
+        let identity = Arc::new(Mutex::new(None::<Keypair>));
+
+        // Handlers are owned by channel and connection, keep weak references to them.
+        let weak_pc = Arc::downgrade(pc);
+        let weak_dc = Arc::downgrade(&dc);
+        let open_identity = identity.clone();
+        let open_events = events.clone();
+
+        dc.on_open(Box::new(move || {
+            Box::pin(async move {
+                let keypair = open_identity.lock().unwrap().clone();
+
+                if let Some(keypair) = keypair {
+                    if let Err(e) = send_proof(weak_pc.clone(), weak_dc, keypair).await {
+                        log::error!("Send handshake failed: {:?}", e);
+
+                        fail(&weak_pc, &open_events).await;
+                    }
+                }
+            })
+        }))
+        .await;
+
+        Ok(Self {
+            identity,
+            remote: Arc::new(Mutex::new(None)),
+            pc: Arc::downgrade(pc),
+            events,
+            _dc: dc,
+        })
+    }
+
+    /// Verify proof sent on handshake channel announced by remote.
+    pub(crate) async fn accept(&self, dc: Arc<RTCDataChannel>) {
+        let weak_pc = self.pc.clone();
+        let remote = self.remote.clone();
+        let events = self.events.clone();
+
+        dc.on_message(Box::new(move |m| {
+            let weak_pc = weak_pc.clone();
+            let remote = remote.clone();
+            let events = events.clone();
+
+            Box::pin(async move {
+                let pc = match weak_pc.upgrade() {
+                    Some(pc) => pc,
+                    None => return,
+                };
+
+                let res = match fingerprints(&pc).await {
+                    Ok((local, peer)) => verify_proof(&m.data, &peer, &local),
+                    Err(_) => None,
+                };
+
+                match res {
+                    Some(id) => *remote.lock().unwrap() = Some(id),
+                    None => {
+                        log::error!("Remote identity handshake failed");
+
+                        fail(&weak_pc, &events).await;
+                    }
+                }
+            })
+        }))
+        .await;
+    }
+
+    pub(crate) fn set_identity(&self, keypair: Keypair) {
+        *self.identity.lock().unwrap() = Some(keypair);
+    }
+
+    pub(crate) fn identity(&self) -> Option<Keypair> {
+        self.identity.lock().unwrap().clone()
+    }
+
+    pub(crate) fn local_peer_id(&self) -> Option<PeerId> {
+        self.identity.lock().unwrap().as_ref().map(Keypair::peer_id)
+    }
+
+    pub(crate) fn remote_peer_id(&self) -> Option<PeerId> {
+        *self.remote.lock().unwrap()
+    }
+}
+
+async fn send_proof(
+    pc: Weak<RTCPeerConnection>,
+    dc: Weak<RTCDataChannel>,
+    keypair: Keypair,
+) -> Result<()> {
+    let (pc, dc) = match (pc.upgrade(), dc.upgrade()) {
+        (Some(pc), Some(dc)) => (pc, dc),
+        _ => return Ok(()),
+    };
+
+    let (local, remote) = fingerprints(&pc).await?;
+
+    let proof = make_proof(&keypair, &local, &remote);
+
+    dc.send(&Bytes::from(proof)).await?;
+
+    Ok(())
+}
+
+/// Report failed handshake and close connection.
+async fn fail(pc: &Weak<RTCPeerConnection>, events: &EventQueue) {
+    events.push(SocketEvent::IdentityFailed);
+
+    if let Some(pc) = pc.upgrade() {
+        if let Err(e) = pc.close().await {
+            log::error!("Close connection failed: {:?}", e);
+        }
+    }
+}
+
+/// Get DTLS fingerprints of local and remote SDP.
+async fn fingerprints(pc: &RTCPeerConnection) -> Result<(String, String)> {
+    let local = pc.local_description().await;
+    let remote = pc.remote_description().await;
+
+    let (local, remote) = match (local, remote) {
+        (Some(local), Some(remote)) => (local, remote),
+        _ => return Err(Error::ErrInvalidFingerprint),
+    };
+
+    let local = fingerprint(&local.sdp).ok_or(Error::ErrInvalidFingerprint)?;
+    let remote = fingerprint(&remote.sdp).ok_or(Error::ErrInvalidFingerprint)?;
+
+    Ok((String::from(local), String::from(remote)))
+}
+
+/// Get fingerprint of SDP, `None` when it has none or its `a=fingerprint` lines
+/// differ, since any of them may be used by DTLS.
+pub(crate) fn fingerprint(sdp: &str) -> Option<&str> {
+    let mut lines = sdp
+        .lines()
+        .filter_map(|l| l.strip_prefix("a=fingerprint:"))
+        .map(str::trim);
+
+    let first = lines.next()?;
+
+    lines
+        .all(|f| f.eq_ignore_ascii_case(first))
+        .then_some(first)
+}
+
+fn proof_message(signer: &str, verifier: &str) -> Vec<u8> {
+    format!("{}\n{}\n{}", HANDSHAKE_CONTEXT, signer, verifier).into_bytes()
+}
+
+/// Public key followed by signature.
+fn make_proof(keypair: &Keypair, local: &str, remote: &str) -> Vec<u8> {
+    let mut proof = keypair.peer_id().as_bytes().to_vec();
+
+    proof.extend_from_slice(&keypair.sign(&proof_message(local, remote)));
+
+    proof
+}
+
+fn verify_proof(proof: &[u8], remote: &str, local: &str) -> Option<PeerId> {
+    if proof.len() != 96 {
+        return None;
+    }
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&proof[..32]);
+
+    let id = PeerId::from_bytes(&key).ok()?;
+
+    id.verify(&proof_message(remote, local), &proof[32..])
+        .ok()
+        .map(|_| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use karma_p2p::Keypair;
+
+    use super::*;
+
+    #[test]
+    fn parse_fingerprint() {
+        let sdp =
+            "v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\na=fingerprint:sha-256 AB:CD\r\na=setup:actpass\r\n";
+
+        assert_eq!(fingerprint(sdp), Some("sha-256 AB:CD"));
+        assert_eq!(fingerprint("v=0\r\n"), None);
+
+        // Every media section repeats same fingerprint.
+        let media = format!("{}m=application 9\r\na=fingerprint:sha-256 AB:CD\r\n", sdp);
+        assert_eq!(fingerprint(&media), Some("sha-256 AB:CD"));
+
+        // Second fingerprint added on signaling path.
+        let forged = format!("{}m=application 9\r\na=fingerprint:sha-256 EF:01\r\n", sdp);
+        assert_eq!(fingerprint(&forged), None);
+    }
+
+    #[test]
+    fn proof_bound_to_fingerprints() {
+        let keypair = Keypair::generate();
+
+        let proof = make_proof(&keypair, "sha-256 AA", "sha-256 BB");
+
+        assert_eq!(
+            verify_proof(&proof, "sha-256 AA", "sha-256 BB"),
+            Some(keypair.peer_id())
+        );
+
+        // Fingerprint swapped on signaling path.
+        assert_eq!(verify_proof(&proof, "sha-256 CC", "sha-256 BB"), None);
+        assert_eq!(verify_proof(&proof, "sha-256 AA", "sha-256 CC"), None);
+        assert_eq!(verify_proof(&proof[..64], "sha-256 AA", "sha-256 BB"), None);
+    }
+}
@@ -1,17 +1,40 @@
 mod socket;
 pub use socket::*;
 
+mod builder;
+pub use builder::*;
+
+mod certificate;
+pub use certificate::*;
+
+mod reconnect;
+pub use karma_p2p::ReconnectPolicy;
+
 mod stream;
 pub use stream::*;
 
+mod reader;
+
+mod handshake;
+pub use handshake::HANDSHAKE_LABEL;
+
 mod addr;
 pub use addr::*;
 
+mod envelope;
+pub use envelope::Envelope;
+
 mod error;
 pub use error::*;
 
 pub mod types {
-    pub use webrtc::ice_transport::{
-        ice_candidate_type::RTCIceCandidateType, ice_server::RTCIceServer,
+    pub use webrtc::{
+        api::setting_engine::SettingEngine,
+        ice::{mdns::MulticastDnsMode, network_type::NetworkType},
+        ice_transport::{ice_candidate_type::RTCIceCandidateType, ice_server::RTCIceServer},
+        peer_connection::{
+            configuration::RTCConfiguration,
+            policy::{bundle_policy::RTCBundlePolicy, ice_transport_policy::RTCIceTransportPolicy},
+        },
     };
 }
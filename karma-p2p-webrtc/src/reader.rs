@@ -0,0 +1,181 @@
+use std::{
+    cmp,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures_lite::{ready, AsyncRead, Stream};
+use smol::channel::Receiver;
+
+/// Turn received messages into byte stream.
+///
+/// Bytes of a message which not fit into caller's buffer are kept for next read.
+pub(crate) struct MessageReader {
+    data_rx: Receiver<Bytes>,
+    buffer: Bytes,
+}
+
+impl MessageReader {
+    pub fn new(data_rx: Receiver<Bytes>) -> Self {
+        Self {
+            data_rx,
+            buffer: Bytes::new(),
+        }
+    }
+
+    /// Receive next message, begin with rest of partially read message.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        if !self.buffer.is_empty() {
+            return Poll::Ready(Some(self.buffer.split_off(0)));
+        }
+
+        Pin::new(&mut self.data_rx).poll_next(cx)
+    }
+}
+
+impl AsyncRead for MessageReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        while this.buffer.is_empty() {
+            match ready!(Pin::new(&mut this.data_rx).poll_next(cx)) {
+                Some(b) => this.buffer = b,
+                // Channel closed.
+                None => return Poll::Ready(Ok(0)),
+            }
+        }
+
+        let size = cmp::min(buf.len(), this.buffer.len());
+
+        buf[..size].copy_from_slice(&this.buffer[..size]);
+        this.buffer.advance(size);
+
+        Poll::Ready(Ok(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures_lite::{future::block_on, io::BufReader, AsyncBufReadExt, AsyncReadExt};
+    use smol::channel::unbounded;
+
+    use super::MessageReader;
+
+    #[test]
+    fn small_buffer() {
+        let (tx, rx) = unbounded();
+        let mut reader = MessageReader::new(rx);
+
+        tx.try_send(Bytes::from_static(b"hello world")).unwrap();
+
+        block_on(async {
+            let mut buf = [0u8; 4];
+
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 4);
+            assert_eq!(&buf, b"hell");
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 4);
+            assert_eq!(&buf, b"o wo");
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 3);
+            assert_eq!(&buf[..3], b"rld");
+        });
+    }
+
+    #[test]
+    fn large_message() {
+        let (tx, rx) = unbounded();
+        let mut reader = MessageReader::new(rx);
+
+        let data: Vec<u8> = (0..256 * 1024).map(|i| i as u8).collect();
+
+        tx.try_send(Bytes::from(data.clone())).unwrap();
+        tx.try_send(Bytes::from(data.clone())).unwrap();
+        tx.close();
+
+        block_on(async {
+            let mut out = Vec::new();
+
+            reader.read_to_end(&mut out).await.unwrap();
+
+            assert_eq!(out.len(), data.len() * 2);
+            assert_eq!(&out[..data.len()], &data[..]);
+            assert_eq!(&out[data.len()..], &data[..]);
+        });
+    }
+
+    #[test]
+    fn lines_across_messages() {
+        let (tx, rx) = unbounded();
+        let mut reader = BufReader::new(MessageReader::new(rx));
+
+        tx.try_send(Bytes::from_static(b"first\nsec")).unwrap();
+        tx.try_send(Bytes::new()).unwrap();
+        tx.try_send(Bytes::from_static(b"ond\n")).unwrap();
+        tx.close();
+
+        block_on(async {
+            let mut line = String::new();
+
+            reader.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "first\n");
+
+            line.clear();
+            reader.read_line(&mut line).await.unwrap();
+            assert_eq!(line, "second\n");
+
+            line.clear();
+            assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn eof_after_close() {
+        let (tx, rx) = unbounded();
+        let mut reader = MessageReader::new(rx);
+
+        tx.try_send(Bytes::from_static(b"abc")).unwrap();
+        drop(tx);
+
+        block_on(async {
+            let mut buf = [0u8; 2];
+
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 2);
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 1);
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+            assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn recv_rest_of_message() {
+        let (tx, rx) = unbounded();
+        let mut reader = MessageReader::new(rx);
+
+        tx.try_send(Bytes::from_static(b"abcdef")).unwrap();
+        tx.try_send(Bytes::from_static(b"gh")).unwrap();
+        tx.close();
+
+        block_on(async {
+            let mut buf = [0u8; 2];
+            reader.read_exact(&mut buf).await.unwrap();
+
+            let rest = futures_lite::future::poll_fn(|cx| reader.poll_recv(cx)).await;
+            assert_eq!(rest, Some(Bytes::from_static(b"cdef")));
+
+            let next = futures_lite::future::poll_fn(|cx| reader.poll_recv(cx)).await;
+            assert_eq!(next, Some(Bytes::from_static(b"gh")));
+
+            let end = futures_lite::future::poll_fn(|cx| reader.poll_recv(cx)).await;
+            assert_eq!(end, None);
+        });
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, Weak,
+};
+
+use karma_p2p::{ReconnectBackoff, ReconnectPolicy, SocketEvent};
+use smol::channel::Sender;
+use webrtc::peer_connection::{
+    offer_answer_options::RTCOfferOptions, sdp::session_description::RTCSessionDescription,
+    RTCPeerConnection,
+};
+
+use crate::{Error, Result, WebrtcAddr};
+
+/// Restart of ICE shared by socket and handlers of peer connection.
+///
+/// Streams are kept over restart, only candidates are gathered again and the DTLS
+/// and SCTP association on top move to the new pair.
+pub(crate) struct Restart {
+    pc: Weak<RTCPeerConnection>,
+    addr_tx: Sender<WebrtcAddr>,
+    policy: Mutex<ReconnectPolicy>,
+    offerer: AtomicBool,
+
+    // Automatic restart is pending or running, losses reported meanwhile are covered
+    // by it.
+    restarting: AtomicBool,
+    backoff: Mutex<ReconnectBackoff>,
+
+    // Candidates gathered while restart offer is created, with their end, remote would
+    // drop them if they arrived before the offer.
+    held: Mutex<Option<Vec<WebrtcAddr>>>,
+}
+
+impl Restart {
+    pub fn new(pc: &Arc<RTCPeerConnection>, addr_tx: Sender<WebrtcAddr>) -> Arc<Self> {
+        Arc::new(Self {
+            pc: Arc::downgrade(pc),
+            addr_tx,
+            policy: Mutex::new(ReconnectPolicy::default()),
+            offerer: AtomicBool::new(false),
+            restarting: AtomicBool::new(false),
+            backoff: Mutex::new(ReconnectBackoff::new()),
+            held: Mutex::new(None),
+        })
+    }
+
+    pub fn set_policy(&self, policy: ReconnectPolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Local side sent the first offer, so it owns automatic restarts.
+    pub fn set_offerer(&self) {
+        self.offerer.store(true, Ordering::SeqCst);
+    }
+
+    pub fn send_candidate(&self, addr: WebrtcAddr) {
+        if let Some(held) = &mut *self.held.lock().unwrap() {
+            return held.push(addr);
+        }
+
+        if let Err(e) = self.addr_tx.try_send(addr) {
+            log::error!("Got error when send ice: {:?}", e);
+        }
+    }
+
+    pub async fn restart(&self) -> Result<()> {
+        let pc = self.pc.upgrade().ok_or(Error::ErrChannelClosed)?;
+
+        {
+            let mut held = self.held.lock().unwrap();
+
+            // Restart in progress, its offer carries new credentials already.
+            if held.is_some() {
+                return Ok(());
+            }
+
+            *held = Some(Vec::new());
+        }
+
+        let res = Self::offer(&pc).await;
+
+        let held = self.held.lock().unwrap().take().unwrap_or_default();
+
+        let sdp = res?;
+
+        for addr in std::iter::once(WebrtcAddr::SDP(sdp)).chain(held) {
+            if let Err(e) = self.addr_tx.try_send(addr) {
+                log::error!("Send to channel addr_tx failed: {:?}", e);
+                return Err(Error::ErrChannelClosed);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn offer(pc: &RTCPeerConnection) -> Result<RTCSessionDescription> {
+        let options = RTCOfferOptions {
+            ice_restart: true,
+            ..Default::default()
+        };
+
+        let sdp = pc.create_offer(Some(options)).await?;
+
+        pc.set_local_description(sdp.clone()).await?;
+
+        Ok(sdp)
+    }
+
+    /// Restart in background if policy applies to `event`, delayed by backoff when
+    /// earlier restarts didn't connect.
+    pub fn on_event(self: &Arc<Self>, event: &SocketEvent) {
+        if *event == SocketEvent::Connected {
+            self.backoff.lock().unwrap().reset();
+            return;
+        }
+
+        let policy = *self.policy.lock().unwrap();
+
+        if !policy.applies(event) || !self.offerer.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if self.restarting.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let delay = self.backoff.lock().unwrap().next_delay();
+        let (this, event) = (self.clone(), event.clone());
+
+        // Handlers of peer connection are awaited while it updates state, restart
+        // can't run inside them.
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+
+            if let Err(e) = this.restart().await {
+                log::error!("Restart ice on {:?} failed: {:?}", event, e);
+            }
+
+            this.restarting.store(false, Ordering::SeqCst);
+        });
+    }
+}
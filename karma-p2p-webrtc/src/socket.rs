@@ -1,138 +1,292 @@
 use std::{
+    future::Future,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex},
     task::{Context, Poll},
 };
 
-use futures_lite::FutureExt;
-use karma_p2p::P2pSocket;
+use futures_lite::{future, ready, FutureExt, StreamExt};
+use karma_p2p::{
+    EventQueue, Keypair, P2pSocket, P2pSocketEvents, PeerId, ReconnectPolicy, SocketEvent,
+};
 use smol::channel::{unbounded, Receiver, Sender};
 use webrtc::{
-    api::{
-        interceptor_registry::register_default_interceptors, media_engine::MediaEngine, APIBuilder,
-    },
     data_channel::data_channel_init::RTCDataChannelInit,
-    interceptor::registry::Registry,
-    peer_connection::{configuration::RTCConfiguration, RTCPeerConnection},
+    ice_transport::{
+        ice_candidate::RTCIceCandidateInit, ice_connection_state::RTCIceConnectionState,
+    },
+    peer_connection::{
+        peer_connection_state::RTCPeerConnectionState, sdp::sdp_type::RTCSdpType, RTCPeerConnection,
+    },
 };
 
-use crate::{Error, Result, WebrtcAddr, WebrtcStream};
+use crate::{
+    envelope::{new_session, RemoteSigner},
+    handshake::{fingerprint, Handshake, HANDSHAKE_LABEL},
+    reconnect::Restart,
+    Envelope, Error, Result, WebrtcAddr, WebrtcSocketBuilder, WebrtcStream,
+};
+
+type OpFuture<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
 
 pub struct WebrtcSocket {
     pc: Arc<RTCPeerConnection>,
     addr_tx: Sender<WebrtcAddr>,
     addr_rx: Receiver<WebrtcAddr>,
+    restart: Arc<Restart>,
+    accept_rx: Mutex<Receiver<WebrtcStream>>,
+    events: EventQueue,
+    handshake: Handshake,
+    local_session: String,
+    local_seq: u64,
+    remote_signer: Mutex<RemoteSigner>,
+
+    // In-flight start, kept until it completes.
+    starting: Mutex<Option<OpFuture<()>>>,
 }
 
 impl WebrtcSocket {
     async fn _bind(bootstrap: WebrtcAddr) -> Result<Self> {
-        let mut m = MediaEngine::default();
-
-        m.register_default_codecs()?;
-
-        let mut registry = Registry::default();
-
-        registry = register_default_interceptors(registry, &mut m)?;
+        if let WebrtcAddr::Bootstrap(bs) = bootstrap {
+            WebrtcSocketBuilder::new().ice_servers(bs).bind().await
+        } else {
+            Err(Error::ErrAddrType)
+        }
+    }
 
-        let api = APIBuilder::new()
-            .with_media_engine(m)
-            .with_interceptor_registry(registry)
-            .build();
+    pub(crate) async fn from_peer_connection(pc: RTCPeerConnection) -> Result<Self> {
+        let pc = Arc::new(pc);
 
-        if let WebrtcAddr::Bootstrap(bs) = bootstrap {
-            let pc = api
-                .new_peer_connection(RTCConfiguration {
-                    ice_servers: bs,
-                    ..Default::default()
-                })
-                .await?;
+        let (addr_tx, addr_rx) = unbounded();
 
-            let (addr_tx, addr_rx) = unbounded();
+        let restart = Restart::new(&pc, addr_tx.clone());
 
-            let atc = addr_tx.clone();
+        let ice_restart = restart.clone();
 
-            pc.on_ice_candidate(Box::new(move |ice| {
-                let atc = addr_tx.clone();
+        pc.on_ice_candidate(Box::new(move |ice| {
+            let restart = ice_restart.clone();
 
-                Box::pin(async move {
-                    if let Some(i) = ice {
+            Box::pin(async move {
+                match ice {
+                    Some(i) => {
                         if let Ok(iii) = i.to_json().await {
-                            if let Ok(e) = atc.try_send(WebrtcAddr::ICE(iii)) {
-                                log::error!("Got error when send ice: {:?}", e);
-                            }
+                            restart.send_candidate(WebrtcAddr::ICE(iii));
                         }
                     }
-                })
-            }))
-            .await;
+                    None => restart.send_candidate(WebrtcAddr::EndOfCandidates),
+                }
+            })
+        }))
+        .await;
+
+        let events = EventQueue::new();
+
+        let ice_events = events.clone();
+
+        // Checking is only seen on ice, the rest follows DTLS as well on connection.
+        pc.on_ice_connection_state_change(Box::new(move |state| {
+            if state == RTCIceConnectionState::Checking {
+                ice_events.push(SocketEvent::Checking);
+            }
+
+            Box::pin(async move {})
+        }))
+        .await;
+
+        let pc_events = events.clone();
+        let pc_restart = restart.clone();
 
-            let s = Self {
-                pc: Arc::new(pc),
-                addr_tx: atc,
-                addr_rx,
+        pc.on_peer_connection_state_change(Box::new(move |state| {
+            let event = match state {
+                RTCPeerConnectionState::Connected => Some(SocketEvent::Connected),
+                RTCPeerConnectionState::Disconnected => Some(SocketEvent::Disconnected),
+                RTCPeerConnectionState::Failed => Some(SocketEvent::Failed),
+                RTCPeerConnectionState::Closed => Some(SocketEvent::Closed),
+                _ => None,
             };
 
-            Ok(s)
-        } else {
-            Err(Error::ErrAddrType)
-        }
+            if let Some(event) = event {
+                pc_restart.on_event(&event);
+                pc_events.push(event);
+            }
+
+            // Connection can't come back once closed, so events end.
+            if state == RTCPeerConnectionState::Closed {
+                pc_events.close();
+            }
+
+            Box::pin(async move {})
+        }))
+        .await;
+
+        let handshake = Handshake::new(&pc, events.clone()).await?;
+
+        let (accept_tx, accept_rx) = unbounded();
+
+        let accept_handshake = handshake.clone();
+        let accept_events = events.clone();
+
+        pc.on_data_channel(Box::new(move |dc| {
+            let accept_tx = accept_tx.clone();
+            let handshake = accept_handshake.clone();
+            let events = accept_events.clone();
+
+            Box::pin(async move {
+                if dc.label() == HANDSHAKE_LABEL {
+                    return handshake.accept(dc).await;
+                }
+
+                let stream = WebrtcStream::new(dc, events).await;
+
+                if let Err(e) = accept_tx.send(stream).await {
+                    log::error!("Got error when send accepted stream: {:?}", e);
+                }
+            })
+        }))
+        .await;
+
+        Ok(Self {
+            pc,
+            addr_tx,
+            addr_rx,
+            restart,
+            accept_rx: Mutex::new(accept_rx),
+            events,
+            handshake,
+            local_session: new_session(),
+            local_seq: 0,
+            remote_signer: Mutex::new(RemoteSigner::default()),
+            starting: Mutex::new(None),
+        })
     }
 
-    async fn _start(&self) -> Result<()> {
-        let sdp = self.pc.create_offer(None).await?;
-        self.pc.set_local_description(sdp.clone()).await?;
-        if let Err(e) = self.addr_tx.send(WebrtcAddr::SDP(sdp)).await {
-            log::error!("Send to channel addr_tx failed: {:?}", e);
-            Err(Error::ErrChannelClosed)
-        } else {
-            Ok(())
-        }
+    /// Set identity proven to remote by handshake, must be set before connected.
+    ///
+    /// Local addresses fetched afterwards are signed with it as `WebrtcAddr::Signed`,
+    /// so remote identity must be set as well.
+    pub fn set_identity(&self, keypair: Keypair) {
+        self.handshake.set_identity(keypair)
     }
 
-    async fn _connect(&self, label: WebrtcAddr, port: u16) -> Result<WebrtcStream> {
-        if let WebrtcAddr::Label(s) = label {
-            let dc_init = RTCDataChannelInit {
-                id: Some(port),
-                negotiated: Some(true),
-                ..Default::default()
-            };
+    pub fn local_peer_id(&self) -> Option<PeerId> {
+        self.handshake.local_peer_id()
+    }
 
-            let dc = self.pc.create_data_channel(&s, Some(dc_init)).await?;
+    /// Identity of remote, `None` until handshake succeeds.
+    ///
+    /// Remote proves it holds the key and the key is bound to DTLS fingerprint in
+    /// exchanged SDP.
+    pub fn remote_peer_id(&self) -> Option<PeerId> {
+        self.handshake.remote_peer_id()
+    }
 
-            let (data_tx, data_rx) = unbounded();
+    /// DTLS fingerprint in local SDP, `None` until local description is set.
+    pub async fn local_fingerprint(&self) -> Option<String> {
+        let sdp = self.pc.local_description().await?;
 
-            dc.on_message(Box::new(move |m| {
-                if let Err(e) = data_tx.try_send(m.data) {
-                    log::error!("Got error when send data: {:?}", e);
-                }
-                Box::pin(async move {})
-            }))
-            .await;
+        fingerprint(&sdp.sdp).map(String::from)
+    }
 
-            Ok(WebrtcStream { dc, data_rx })
-        } else {
-            Err(Error::ErrAddrType)
+    /// DTLS fingerprint in remote SDP, `None` until remote description is set.
+    ///
+    /// Compare it with [`DtlsCertificate::fingerprint`] of a known peer to pin it.
+    pub async fn remote_fingerprint(&self) -> Option<String> {
+        let sdp = self.pc.remote_description().await?;
+
+        fingerprint(&sdp.sdp).map(String::from)
+    }
+
+    /// Only accept remote addresses signed by `peer`, local addresses are sealed for it.
+    ///
+    /// Needs local identity, without either one signed addresses are refused and with
+    /// only one of them every address is. Addresses are verified before they reach
+    /// the connection, so fingerprints in SDP can't be swapped by an untrusted
+    /// signaling path.
+    pub fn set_remote_identity(&self, peer: PeerId) {
+        self.remote_signer.lock().unwrap().expect(peer)
+    }
+
+    /// Restart ICE, streams are kept while candidates are gathered again.
+    ///
+    /// The restart offer and new candidates come from `fetch_local_addr`, so signaling
+    /// must still be exchanged. Only one side should restart at a time, a restart asked
+    /// while one is in progress is covered by it.
+    pub async fn restart_ice(&self) -> Result<()> {
+        self.restart.restart().await
+    }
+
+    /// Restart ICE by itself on connection loss, see [`ReconnectPolicy`].
+    pub fn set_reconnect_policy(&self, policy: ReconnectPolicy) {
+        self.restart.set_policy(policy)
+    }
+
+    async fn _start(pc: Arc<RTCPeerConnection>, addr_tx: Sender<WebrtcAddr>) -> Result<()> {
+        let sdp = pc.create_offer(None).await?;
+
+        // Queue sdp before gathering begins, so it arrives ahead of ice candidates.
+        if let Err(e) = addr_tx.send(WebrtcAddr::SDP(sdp.clone())).await {
+            log::error!("Send to channel addr_tx failed: {:?}", e);
+            return Err(Error::ErrChannelClosed);
         }
+
+        pc.set_local_description(sdp).await?;
+
+        Ok(())
     }
 
-    async fn _fetch_local_addr(&self) -> Result<WebrtcAddr> {
-        let addr = self.addr_rx.recv().await?;
-        Ok(addr)
+    /// Open data channel `label`.
+    ///
+    /// Non-zero `port` is used as the negotiated channel id, so both sides must connect
+    /// with the same `label` and `port`. Port `0` announces the channel to the remote,
+    /// which receives it from `accept`.
+    async fn _connect(
+        pc: Arc<RTCPeerConnection>,
+        events: EventQueue,
+        label: String,
+        port: u16,
+    ) -> Result<WebrtcStream> {
+        let dc_init = if port == 0 {
+            RTCDataChannelInit::default()
+        } else {
+            RTCDataChannelInit {
+                id: Some(port),
+                negotiated: Some(true),
+                ..Default::default()
+            }
+        };
+
+        let dc = pc.create_data_channel(&label, Some(dc_init)).await?;
+
+        Ok(WebrtcStream::new(dc, events).await)
     }
 
-    async fn _set_remote_addr(&self, remote: WebrtcAddr) -> Result<()> {
+    async fn _set_remote_addr(
+        pc: Arc<RTCPeerConnection>,
+        addr_tx: Sender<WebrtcAddr>,
+        remote: WebrtcAddr,
+    ) -> Result<()> {
         match remote {
             WebrtcAddr::SDP(s) => {
-                self.pc.set_remote_description(s).await?;
-                let sdp = self.pc.create_answer(None).await?;
-                self.pc.set_local_description(sdp.clone()).await?;
+                let is_offer = s.sdp_type == RTCSdpType::Offer;
+
+                pc.set_remote_description(s).await?;
 
-                if let Err(e) = self.addr_tx.send(WebrtcAddr::SDP(sdp)).await {
-                    log::error!("send {:?}", e);
-                    return Err(Error::ErrChannelClosed);
+                if is_offer {
+                    let sdp = pc.create_answer(None).await?;
+
+                    if let Err(e) = addr_tx.send(WebrtcAddr::SDP(sdp.clone())).await {
+                        log::error!("send {:?}", e);
+                        return Err(Error::ErrChannelClosed);
+                    }
+
+                    pc.set_local_description(sdp).await?;
                 }
             }
-            WebrtcAddr::ICE(i) => self.pc.add_ice_candidate(i).await?,
+            WebrtcAddr::ICE(i) => pc.add_ice_candidate(i).await?,
+            // Empty candidate marks end of candidates.
+            WebrtcAddr::EndOfCandidates => {
+                pc.add_ice_candidate(RTCIceCandidateInit::default()).await?
+            }
             _ => return Err(Error::ErrAddrType),
         }
 
@@ -140,6 +294,20 @@ impl WebrtcSocket {
     }
 }
 
+impl P2pSocketEvents for WebrtcSocket {
+    fn poll_event(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        self.events.poll_next(cx)
+    }
+
+    fn poll_state(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>> {
+        self.events.poll_state(cx, seen)
+    }
+}
+
 impl P2pSocket for WebrtcSocket {
     type Addr = WebrtcAddr;
 
@@ -147,45 +315,1071 @@ impl P2pSocket for WebrtcSocket {
 
     type Error = Error;
 
-    fn poll_bind(cx: &mut Context<'_>, bootstrap: Self::Addr) -> Poll<Result<Self>> {
-        let mut fu = Box::pin(async move { WebrtcSocket::_bind(bootstrap).await });
+    type Binding = OpFuture<Self>;
 
-        fu.poll(cx)
+    type Connecting = OpFuture<WebrtcStream>;
+
+    type SettingRemoteAddr = OpFuture<()>;
+
+    fn binding(bootstrap: Self::Addr) -> Self::Binding {
+        Box::pin(Self::_bind(bootstrap))
     }
 
-    fn poll_connect(
-        self: Pin<&Self>,
-        cx: &mut Context<'_>,
-        label: Self::Addr,
-        port: u16,
-    ) -> Poll<Result<Self::Stream>> {
-        let mut fu = Box::pin(async move { self._connect(label, port).await });
+    fn connecting(&self, label: Self::Addr, port: u16) -> Self::Connecting {
+        let label = match label {
+            WebrtcAddr::Label(label) if label == HANDSHAKE_LABEL => {
+                return Box::pin(future::ready(Err(Error::ErrReservedLabel)))
+            }
+            WebrtcAddr::Label(label) => label,
+            _ => return Box::pin(future::ready(Err(Error::ErrAddrType))),
+        };
 
-        fu.poll(cx)
+        Box::pin(Self::_connect(
+            self.pc.clone(),
+            self.events.clone(),
+            label,
+            port,
+        ))
+    }
+
+    fn poll_accept(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Result<Self::Stream>> {
+        let mut accept_rx = self.accept_rx.lock().unwrap();
+
+        match ready!(accept_rx.poll_next(cx)) {
+            Some(stream) => Poll::Ready(Ok(stream)),
+            None => Poll::Ready(Err(Error::ErrChannelClosed)),
+        }
     }
 
     fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        let mut fu = Box::pin(async move { self._start().await });
+        let this = self.get_mut();
+
+        let (pc, addr_tx) = (&this.pc, &this.addr_tx);
+
+        let starting = this.starting.get_mut().unwrap();
+
+        let fu =
+            starting.get_or_insert_with(|| Box::pin(Self::_start(pc.clone(), addr_tx.clone())));
+
+        let res = ready!(fu.poll(cx));
+
+        *starting = None;
+
+        if res.is_ok() {
+            this.restart.set_offerer();
+        }
 
-        fu.poll(cx)
+        Poll::Ready(res)
     }
 
     fn poll_fetch_local_addr(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<Self::Addr>> {
-        let mut fu = Box::pin(async move { self._fetch_local_addr().await });
+        let this = self.get_mut();
+
+        let identity = this.handshake.identity();
+        let recipient = this.remote_signer.lock().unwrap().peer();
+
+        // Checked before taking an address, so it isn't lost.
+        if identity.is_some() != recipient.is_some() {
+            return Poll::Ready(Err(Error::ErrMissingIdentity));
+        }
+
+        let addr = match ready!(this.addr_rx.poll_next(cx)) {
+            Some(addr) => addr,
+            None => return Poll::Ready(Err(Error::ErrChannelClosed)),
+        };
+
+        match identity.zip(recipient) {
+            Some((keypair, recipient)) => {
+                let envelope = Envelope::seal(
+                    &keypair,
+                    &recipient,
+                    &this.local_session,
+                    this.local_seq,
+                    &addr,
+                );
+
+                this.local_seq += 1;
 
-        fu.poll(cx)
+                Poll::Ready(envelope.map(WebrtcAddr::Signed))
+            }
+            None => Poll::Ready(Ok(addr)),
+        }
     }
 
-    fn poll_set_remote_addr(
-        self: Pin<&Self>,
-        cx: &mut Context<'_>,
-        remote: Self::Addr,
-    ) -> Poll<Result<()>> {
-        let mut fu = Box::pin(async move { self._set_remote_addr(remote).await });
+    fn setting_remote_addr(&self, remote: Self::Addr) -> Self::SettingRemoteAddr {
+        // Signed address is opened once, its sequence can't be replayed.
+        let local = self.handshake.local_peer_id();
+
+        let remote = match self.remote_signer.lock().unwrap().open(local, &remote) {
+            Ok(remote) => remote,
+            Err(e) => return Box::pin(future::ready(Err(e))),
+        };
+
+        Box::pin(Self::_set_remote_addr(
+            self.pc.clone(),
+            self.addr_tx.clone(),
+            remote,
+        ))
+    }
+
+    fn is_last_addr(addr: &Self::Addr) -> bool {
+        match addr {
+            WebrtcAddr::EndOfCandidates => true,
+            WebrtcAddr::Signed(envelope) => envelope.is_end_of_candidates(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use futures_lite::{future, ready, AsyncReadExt, AsyncWriteExt, FutureExt, StreamExt};
+    use karma_p2p::{
+        establish,
+        testkit::{self, Harness},
+        BoxedSocket, DynAddr, EstablishState, Format, Keypair, P2pMessageStreamExt,
+        P2pSocketEvents, P2pSocketExt, ReconnectPolicy, SerdeAddrCodec, Signaling, SocketEvent,
+    };
+    use smol::channel::{unbounded, Receiver, Sender};
+    use webrtc::{
+        data_channel::data_channel_state::RTCDataChannelState,
+        ice_transport::ice_connection_state::RTCIceConnectionState,
+        peer_connection::{sdp::sdp_type::RTCSdpType, RTCPeerConnection},
+    };
+
+    use crate::{
+        types::{MulticastDnsMode, NetworkType},
+        DtlsCertificate, Envelope, Error, WebrtcAddr, WebrtcSocket, WebrtcSocketBuilder,
+        WebrtcStream, HANDSHAKE_LABEL,
+    };
+
+    struct ChannelSignaling {
+        tx: Sender<WebrtcAddr>,
+        rx: Receiver<WebrtcAddr>,
+    }
+
+    fn signaling_pair() -> (ChannelSignaling, ChannelSignaling) {
+        let (a_tx, a_rx) = unbounded();
+        let (b_tx, b_rx) = unbounded();
+
+        (
+            ChannelSignaling { tx: a_tx, rx: b_rx },
+            ChannelSignaling { tx: b_tx, rx: a_rx },
+        )
+    }
+
+    impl Signaling<WebrtcAddr> for ChannelSignaling {
+        type Error = Error;
+
+        fn poll_send(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _remote: &str,
+            addr: &WebrtcAddr,
+        ) -> Poll<Result<(), Error>> {
+            let res = self.tx.try_send(addr.clone());
+
+            Poll::Ready(res.map_err(|_| Error::ErrChannelClosed))
+        }
+
+        fn poll_recv(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            _remote: &str,
+        ) -> Poll<Result<Option<WebrtcAddr>, Error>> {
+            Poll::Ready(Ok(ready!(self.rx.poll_next(cx))))
+        }
+    }
+
+    /// Exchange addresses of both sockets until test is done, restarts included.
+    async fn exchange(
+        a: &mut WebrtcSocket,
+        sig_a: &mut ChannelSignaling,
+        b: &mut WebrtcSocket,
+        sig_b: &mut ChannelSignaling,
+    ) {
+        let (mut state_a, mut state_b) = (EstablishState::new(true), EstablishState::new(false));
+
+        future::poll_fn(|cx| {
+            if let Poll::Ready(Err(e)) = state_a.poll_establish(cx, a, sig_a, "b") {
+                panic!("establish a: {:?}", e);
+            }
+
+            if let Poll::Ready(Err(e)) = state_b.poll_establish(cx, b, sig_b, "a") {
+                panic!("establish b: {:?}", e);
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
+    async fn wait_open(streams: &[&WebrtcStream]) {
+        while streams
+            .iter()
+            .any(|s| s.dc.ready_state() != RTCDataChannelState::Open)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn loopback_round_trip() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let test = async {
+            wait_open(&[&sa, &sb]).await;
+
+            let data: Vec<u8> = (0..64 * 1024).map(|i| i as u8).collect();
+
+            for chunk in data.chunks(1000) {
+                sa.write_all(chunk).await.unwrap();
+            }
+            sa.flush().await.unwrap();
+
+            let mut out = vec![0u8; data.len()];
+            sb.read_exact(&mut out).await.unwrap();
+
+            assert_eq!(out, data);
+        };
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("loopback connection timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn establish_on_live_signaling() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let sa = a.connect(label.clone(), 1).await.unwrap();
+        let sb = b.connect(label, 1).await.unwrap();
+
+        // Signaling stays open, establish is done after end of candidates of both.
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let test = async {
+            let (ra, rb) = future::zip(
+                establish(&mut a, &mut sig_a, "b", true),
+                establish(&mut b, &mut sig_b, "a", false),
+            )
+            .await;
+
+            ra.unwrap();
+            rb.unwrap();
+
+            wait_open(&[&sa, &sb]).await;
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+
+        res.expect("establish timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn accept_announced_channel() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        // Only `a` opens a channel, `b` listens for it.
+        let mut sa = a
+            .connect(WebrtcAddr::Label(String::from("announced")), 0)
+            .await
+            .unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+        let (mut state_a, mut state_b) = (EstablishState::new(true), EstablishState::new(false));
+
+        let test = async {
+            // Socket of `b` is lent to establish only while polled, accept between.
+            let mut sb = future::poll_fn(|cx| {
+                let _ = state_a.poll_establish(cx, &mut a, &mut sig_a, "b");
+                let _ = state_b.poll_establish(cx, &mut b, &mut sig_b, "a");
+
+                b.accept().poll(cx)
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(sb.dc.label(), "announced");
+
+            wait_open(&[&sa, &sb]).await;
+
+            sa.write_all(b"ping").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            sb.write_all(b"pong").await.unwrap();
+            sb.flush().await.unwrap();
+
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+        res.expect("accept timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn message_boundaries() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let messages: Vec<Bytes> = [1, 7, 1000, 16 * 1024, 60 * 1024]
+            .iter()
+            .map(|size| (0..*size).map(|i| i as u8).collect::<Vec<u8>>().into())
+            .collect();
+
+        let test = async {
+            // Sent before channel is open, messages wait for it.
+            for message in &messages {
+                sa.send_message(message.clone()).await.unwrap();
+            }
+
+            for message in &messages {
+                assert_eq!(sb.recv_message().await.unwrap().unwrap(), *message);
+            }
+
+            sa.close_message().await.unwrap();
+
+            assert!(sb.recv_message().await.is_none());
+        };
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("message connection timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn boxed_round_trip() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut sockets = Vec::new();
+        for _ in 0..2 {
+            let socket = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+            let codec = SerdeAddrCodec::new(Format::Json, WebrtcAddr::Label);
+            sockets.push(BoxedSocket::new(socket, codec));
+        }
+
+        let mut b = sockets.pop().unwrap();
+        let mut a = sockets.pop().unwrap();
+
+        let label = DynAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let test = async {
+            sa.write_all(b"ping").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        };
+
+        let driver = testkit::exchange(&mut a, &mut b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("boxed connection timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn socket_resume_after_pending() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let test = async {
+            // Start dropped after one poll resumes, only one offer is queued.
+            if future::poll_once(a.start()).await.is_none() {
+                a.start().await.unwrap();
+            }
+
+            let offer = a.fetch_local_addr().await.unwrap();
+            assert!(matches!(offer, WebrtcAddr::SDP(_)));
+
+            loop {
+                match a.fetch_local_addr().await.unwrap() {
+                    WebrtcAddr::EndOfCandidates => break,
+                    addr => assert!(matches!(addr, WebrtcAddr::ICE(_))),
+                }
+            }
+
+            // Other remote polled meanwhile doesn't take over offer in flight.
+            let mut set_offer = b.set_remote_addr(offer);
+            let first = future::poll_once(&mut set_offer).await;
+
+            assert!(matches!(
+                b.set_remote_addr(WebrtcAddr::Label(String::from("test")))
+                    .await,
+                Err(Error::ErrAddrType)
+            ));
+
+            match first {
+                Some(res) => res.unwrap(),
+                None => set_offer.await.unwrap(),
+            }
+
+            assert!(matches!(
+                b.fetch_local_addr().await.unwrap(),
+                WebrtcAddr::SDP(_)
+            ));
+
+            // Connections in flight are kept by their futures, whatever label and port.
+            let (x, y) = (String::from("x"), String::from("y"));
+
+            let mut connect_x = a.connect(WebrtcAddr::Label(x.clone()), 1);
+            let mut connect_y = a.connect(WebrtcAddr::Label(y.clone()), 2);
+
+            let first_x = future::poll_once(&mut connect_x).await;
+            let first_y = future::poll_once(&mut connect_y).await;
+
+            let sx = match first_x {
+                Some(res) => res.unwrap(),
+                None => connect_x.await.unwrap(),
+            };
+            let sy = match first_y {
+                Some(res) => res.unwrap(),
+                None => connect_y.await.unwrap(),
+            };
+
+            assert_eq!(sx.dc.label(), x);
+            assert_eq!(sy.dc.label(), y);
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+
+        res.expect("resume timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn concurrent_connect_same_key() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let a = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        // Each caller drives its own connection, so both are woken and complete.
+        let test = future::zip(a.connect(label.clone(), 1), a.connect(label, 1));
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+
+        let (first, second) = res.expect("concurrent connect timeout");
+
+        for stream in [first.unwrap(), second.unwrap()] {
+            assert_eq!(stream.dc.label(), "test");
+            assert_eq!(stream.dc.id(), 1);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_resume_after_pending() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        // Channel isn't open before signaling, so send and read stay in flight.
+        sa.write_all(b"ping").await.unwrap();
+        assert!(future::poll_once(sa.flush()).await.is_none());
+
+        let mut buf = [0u8; 4];
+        assert!(future::poll_once(sb.read(&mut buf)).await.is_none());
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let test = async {
+            sa.flush().await.unwrap();
+
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            sa.write_all(b"pong").await.unwrap();
+            sa.flush().await.unwrap();
+
+            // Message sent once, though its send was polled before open.
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+        };
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("stream resume timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn builder_data_only() {
+        let builder = || {
+            WebrtcSocketBuilder::new()
+                .data_only(true)
+                .network_types(vec![NetworkType::Udp4])
+                .mdns_mode(MulticastDnsMode::Disabled)
+                .ephemeral_udp_port_range(40000, 40999)
+        };
+
+        assert!(builder()
+            .ephemeral_udp_port_range(2000, 1000)
+            .bind()
+            .await
+            .is_err());
+
+        let mut a = builder().bind().await.unwrap();
+        let mut b = builder().bind().await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let test = async {
+            wait_open(&[&sa, &sb]).await;
+
+            sa.write_all(b"ping").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        };
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("data only connection timeout");
+    }
+
+    async fn ice_ufrag(pc: &RTCPeerConnection, local: bool) -> Option<String> {
+        let sdp = if local {
+            pc.local_description().await
+        } else {
+            pc.remote_description().await
+        };
+
+        sdp?.sdp
+            .lines()
+            .find_map(|l| l.strip_prefix("a=ice-ufrag:"))
+            .map(String::from)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ice_restart() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        // Restart is driven without the socket, which is lent to establish.
+        let (pc_a, pc_b, restart) = (a.pc.clone(), b.pc.clone(), a.restart.clone());
+
+        let test = async {
+            wait_open(&[&sa, &sb]).await;
+
+            sa.write_all(b"ping").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            let (local, remote) = (ice_ufrag(&pc_a, true).await, ice_ufrag(&pc_a, false).await);
+
+            restart.restart().await.unwrap();
+
+            let restarted = ice_ufrag(&pc_a, true).await;
+            assert_ne!(restarted, local);
+
+            // Remote took the offer and answered with new credentials of its own.
+            while ice_ufrag(&pc_b, false).await != restarted
+                || ice_ufrag(&pc_a, false).await == remote
+                || pc_a.ice_connection_state() != RTCIceConnectionState::Connected
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            sb.write_all(b"pong").await.unwrap();
+            sb.flush().await.unwrap();
+
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+        };
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("ice restart timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn automatic_restart() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        a.set_reconnect_policy(ReconnectPolicy::OnFailed);
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let (pc_a, pc_b, restart) = (a.pc.clone(), b.pc.clone(), a.restart.clone());
+
+        let test = async {
+            wait_open(&[&sa, &sb]).await;
+
+            let local = ice_ufrag(&pc_a, true).await;
+
+            // Losses reported while restart is pending start it once.
+            restart.on_event(&SocketEvent::Disconnected);
+            restart.on_event(&SocketEvent::Failed);
+            restart.on_event(&SocketEvent::Failed);
+
+            while ice_ufrag(&pc_a, true).await == local {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            let restarted = ice_ufrag(&pc_a, true).await;
+
+            while ice_ufrag(&pc_b, false).await != restarted
+                || pc_a.ice_connection_state() != RTCIceConnectionState::Connected
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            assert_eq!(ice_ufrag(&pc_a, true).await, restarted);
+
+            sb.write_all(b"pong").await.unwrap();
+            sb.flush().await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"pong");
+        };
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("automatic restart timeout");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pinned_fingerprint() {
+        let cert = DtlsCertificate::generate().unwrap();
+
+        let mut a = WebrtcSocketBuilder::new()
+            .certificate(cert.clone())
+            .bind()
+            .await
+            .unwrap();
+        let mut b = WebrtcSocket::bind(WebrtcAddr::Bootstrap(Vec::new()))
+            .await
+            .unwrap();
+
+        assert_eq!(b.remote_fingerprint().await, None);
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let sa = a.connect(label.clone(), 1).await.unwrap();
+        let sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+
+        let driver = exchange(&mut a, &mut sig_a, &mut b, &mut sig_b);
+
+        let res = tokio::time::timeout(
+            Duration::from_secs(30),
+            future::or(wait_open(&[&sa, &sb]), driver),
+        )
+        .await;
+        res.expect("pinned connection timeout");
+
+        let fingerprint = Some(cert.fingerprint().unwrap());
+
+        assert_eq!(a.local_fingerprint().await, fingerprint);
+        assert_eq!(b.remote_fingerprint().await, fingerprint);
+        assert_eq!(a.remote_fingerprint().await, b.local_fingerprint().await);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn connection_events() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("test"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let _sb = b.connect(label, 1).await.unwrap();
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+        let (mut state_a, mut state_b) = (EstablishState::new(true), EstablishState::new(false));
+
+        let (mut seen, mut seen_b) = (Vec::new(), Vec::new());
+        let opened = SocketEvent::ChannelOpened(String::from("test"));
+        let closed = SocketEvent::ChannelClosed(String::from("test"));
+        let mut closing = false;
+
+        let test = async {
+            // Closer of channel isn't always told by webrtc, wait for remote instead.
+            while !seen_b.contains(&closed) {
+                // Reset of stream is only answered once remote has opened it too.
+                if !closing && seen.contains(&opened) && seen_b.contains(&opened) {
+                    sa.close().await.unwrap();
+                    closing = true;
+                }
+
+                // Sockets are lent to establish only while polled, take events between.
+                let event = future::poll_fn(|cx| {
+                    let _ = state_a.poll_establish(cx, &mut a, &mut sig_a, "b");
+                    let _ = state_b.poll_establish(cx, &mut b, &mut sig_b, "a");
+
+                    if let Poll::Ready(event) = Pin::new(&b).poll_event(cx) {
+                        return Poll::Ready((false, event));
+                    }
+
+                    Pin::new(&a).poll_event(cx).map(|event| (true, event))
+                });
+
+                match event.await {
+                    (true, event) => seen.push(event.unwrap()),
+                    (false, event) => seen_b.push(event.unwrap()),
+                }
+            }
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+        res.expect("connection events timeout");
+
+        let position = |seen: &Vec<SocketEvent>, event: &SocketEvent| {
+            seen.iter().position(|e| e == event).unwrap()
+        };
+
+        assert!(position(&seen, &SocketEvent::Checking) < position(&seen, &SocketEvent::Connected));
+        assert!(position(&seen, &SocketEvent::Connected) < position(&seen, &opened));
+        assert!(position(&seen_b, &opened) < position(&seen_b, &closed));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn identity_handshake() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let (ka, kb) = (Keypair::generate(), Keypair::generate());
+
+        a.set_identity(ka.clone());
+        b.set_identity(kb.clone());
+
+        a.set_remote_identity(kb.peer_id());
+        b.set_remote_identity(ka.peer_id());
+
+        assert_eq!(a.local_peer_id(), Some(ka.peer_id()));
+        assert!(a.remote_peer_id().is_none());
+
+        let label = WebrtcAddr::Label(String::from(HANDSHAKE_LABEL));
+        assert!(matches!(
+            a.connect(label, 0).await,
+            Err(Error::ErrReservedLabel)
+        ));
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+        let (mut state_a, mut state_b) = (EstablishState::new(true), EstablishState::new(false));
+
+        let test = async {
+            // Sockets are lent to establish only while polled, check identities between.
+            while a.remote_peer_id().is_none() || b.remote_peer_id().is_none() {
+                let drive = future::poll_fn(|cx| {
+                    let _ = state_a.poll_establish(cx, &mut a, &mut sig_a, "b");
+                    let _ = state_b.poll_establish(cx, &mut b, &mut sig_b, "a");
+
+                    Poll::<()>::Pending
+                });
+
+                future::or(drive, tokio::time::sleep(Duration::from_millis(10))).await;
+            }
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+        res.expect("identity handshake timeout");
+
+        assert_eq!(a.remote_peer_id(), Some(kb.peer_id()));
+        assert_eq!(b.remote_peer_id(), Some(ka.peer_id()));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn forged_identity_closes() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        // Remote sends garbage as proof on a handshake channel of its own.
+        let forged =
+            b.pc.create_data_channel(HANDSHAKE_LABEL, None)
+                .await
+                .unwrap();
+        let weak = Arc::downgrade(&forged);
+
+        forged
+            .on_open(Box::new(move || {
+                Box::pin(async move {
+                    if let Some(dc) = weak.upgrade() {
+                        let _ = dc.send(&Bytes::from_static(b"forged")).await;
+                    }
+                })
+            }))
+            .await;
+
+        let (mut sig_a, mut sig_b) = signaling_pair();
+        let (mut state_a, mut state_b) = (EstablishState::new(true), EstablishState::new(false));
+
+        let mut seen = Vec::new();
+
+        let test = async {
+            while !seen.contains(&SocketEvent::Closed) {
+                let event = future::poll_fn(|cx| {
+                    let _ = state_a.poll_establish(cx, &mut a, &mut sig_a, "b");
+                    let _ = state_b.poll_establish(cx, &mut b, &mut sig_b, "a");
+
+                    Pin::new(&a).poll_event(cx)
+                });
+
+                seen.push(event.await.unwrap());
+            }
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+        res.expect("forged identity timeout");
+
+        // Nothing is reported after close.
+        let next = future::poll_fn(|cx| Pin::new(&a).poll_event(cx));
+        assert_eq!(next.await, None);
+
+        assert!(seen.contains(&SocketEvent::IdentityFailed));
+        assert!(a.remote_peer_id().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn signed_remote_addr() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let (ka, kb, kc) = (
+            Keypair::generate(),
+            Keypair::generate(),
+            Keypair::generate(),
+        );
+
+        b.start().await.unwrap();
+
+        let offer = b.fetch_local_addr().await.unwrap();
+        assert!(matches!(offer, WebrtcAddr::SDP(_)));
+
+        // Identity without expected remote fails closed on both directions.
+        b.set_identity(kb.clone());
+        assert!(matches!(
+            b.fetch_local_addr().await,
+            Err(Error::ErrMissingIdentity)
+        ));
+
+        a.set_identity(ka.clone());
+        assert!(matches!(
+            a.set_remote_addr(offer.clone()).await,
+            Err(Error::ErrMissingIdentity)
+        ));
+
+        a.set_remote_identity(kb.peer_id());
+        assert!(matches!(
+            a.set_remote_addr(offer.clone()).await,
+            Err(Error::ErrUnsignedAddr)
+        ));
+
+        // Relay re-signs offer with its own key.
+        let forged = Envelope::seal(&kc, &ka.peer_id(), "s", 0, &offer).unwrap();
+        assert!(matches!(
+            a.set_remote_addr(WebrtcAddr::Signed(forged)).await,
+            Err(Error::ErrUnexpectedSigner)
+        ));
+
+        // Offer sent by b to another peer is forwarded to a.
+        let forged = Envelope::seal(&kb, &kc.peer_id(), "s", 0, &offer).unwrap();
+        assert!(matches!(
+            a.set_remote_addr(WebrtcAddr::Signed(forged)).await,
+            Err(Error::ErrUnexpectedRecipient)
+        ));
+
+        let signed = Envelope::seal(&kb, &ka.peer_id(), "s", 0, &offer).unwrap();
+        a.set_remote_addr(WebrtcAddr::Signed(signed.clone()))
+            .await
+            .unwrap();
+
+        // Replayed by relay.
+        assert!(matches!(
+            a.set_remote_addr(WebrtcAddr::Signed(signed)).await,
+            Err(Error::ErrStaleSequence)
+        ));
+
+        // Replayed from an older session of b, where sequence was further.
+        let stale = Envelope::seal(&kb, &ka.peer_id(), "t", 7, &offer).unwrap();
+        assert!(matches!(
+            a.set_remote_addr(WebrtcAddr::Signed(stale)).await,
+            Err(Error::ErrUnexpectedSession)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn end_of_candidates() {
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        assert!(matches!(
+            a.set_remote_addr(WebrtcAddr::EndOfCandidates).await,
+            Err(Error::WebrtcError(_))
+        ));
+
+        let test = async {
+            b.start().await.unwrap();
+
+            let mut addrs = vec![b.fetch_local_addr().await.unwrap()];
+
+            while !matches!(addrs.last(), Some(WebrtcAddr::EndOfCandidates)) {
+                addrs.push(b.fetch_local_addr().await.unwrap());
+            }
+
+            assert!(matches!(addrs[0], WebrtcAddr::SDP(_)));
+            assert!(matches!(addrs[1], WebrtcAddr::ICE(_)));
+
+            for addr in addrs {
+                a.set_remote_addr(addr).await.unwrap();
+            }
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), test).await;
+
+        res.expect("gathering timeout");
+    }
+
+    struct WebrtcHarness;
+
+    impl Harness for WebrtcHarness {
+        type Socket = WebrtcSocket;
+
+        type Stream = WebrtcStream;
+
+        type Addr = WebrtcAddr;
+
+        type Error = Error;
+
+        fn bootstrap(&self) -> WebrtcAddr {
+            WebrtcAddr::Bootstrap(Vec::new())
+        }
+
+        fn label(&self, name: &str) -> WebrtcAddr {
+            WebrtcAddr::Label(String::from(name))
+        }
+
+        fn wrong_addr(&self) -> WebrtcAddr {
+            WebrtcAddr::Bootstrap(Vec::new())
+        }
+
+        fn is_offer(&self, addr: &WebrtcAddr) -> bool {
+            matches!(addr, WebrtcAddr::SDP(s) if s.sdp_type == RTCSdpType::Offer)
+        }
+
+        fn is_answer(&self, addr: &WebrtcAddr) -> bool {
+            matches!(addr, WebrtcAddr::SDP(s) if s.sdp_type == RTCSdpType::Answer)
+        }
+
+        fn is_candidate(&self, addr: &WebrtcAddr) -> bool {
+            matches!(addr, WebrtcAddr::ICE(_))
+        }
+
+        fn is_addr_type_error(&self, err: &Error) -> bool {
+            matches!(err, Error::ErrAddrType)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn conformance() {
+        let res =
+            tokio::time::timeout(Duration::from_secs(60), testkit::check_all(&WebrtcHarness)).await;
 
-        fu.poll(cx)
+        res.expect("conformance timeout");
     }
 }
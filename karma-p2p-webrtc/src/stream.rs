@@ -1,70 +1,165 @@
-use std::{sync::Arc, task::Poll};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 use bytes::Bytes;
-use futures_lite::{AsyncRead, AsyncWrite, FutureExt};
-use smol::channel::Receiver;
+use futures_lite::{ready, AsyncRead, AsyncWrite, FutureExt};
+use karma_p2p::{EventQueue, P2pMessageStream, SocketEvent};
+use smol::channel::{unbounded, Receiver};
 use webrtc::data_channel::RTCDataChannel;
 
+use crate::{reader::MessageReader, Error, Result};
+
+type SendFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
 pub struct WebrtcStream {
     pub(crate) dc: Arc<RTCDataChannel>,
-    pub(crate) data_rx: Receiver<Bytes>,
+    pub(crate) reader: MessageReader,
+    pub(crate) open_rx: Receiver<()>,
+    pub(crate) sending: Option<SendFuture>,
+    pub(crate) closing: bool,
+}
+
+impl WebrtcStream {
+    /// Wrap `dc`, its open and close are reported to `events` of socket.
+    pub(crate) async fn new(dc: Arc<RTCDataChannel>, events: EventQueue) -> Self {
+        let (data_tx, data_rx) = unbounded();
+
+        let close_tx = data_tx.clone();
+
+        // Closed once data channel is open or closed, sending waits for it.
+        let (open_tx, open_rx) = unbounded();
+        let close_open_tx = open_tx.clone();
+
+        let label = String::from(dc.label());
+        let (open_label, open_events) = (label.clone(), events.clone());
+
+        dc.on_open(Box::new(move || {
+            open_tx.close();
+            open_events.push(SocketEvent::ChannelOpened(open_label));
+            Box::pin(async move {})
+        }))
+        .await;
+
+        dc.on_close(Box::new(move || {
+            // Received messages are still readable, then reader reports EOF.
+            close_tx.close();
+            close_open_tx.close();
+
+            events.push(SocketEvent::ChannelClosed(label.clone()));
+
+            Box::pin(async move {})
+        }))
+        .await;
+
+        dc.on_message(Box::new(move |m| {
+            if let Err(e) = data_tx.try_send(m.data) {
+                log::error!("Got error when send data: {:?}", e);
+            }
+            Box::pin(async move {})
+        }))
+        .await;
+
+        Self {
+            dc,
+            reader: MessageReader::new(data_rx),
+            open_rx,
+            sending: None,
+            closing: false,
+        }
+    }
 }
 
 impl AsyncRead for WebrtcStream {
     fn poll_read(
-        self: std::pin::Pin<&mut Self>,
+        mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buf: &mut [u8],
     ) -> Poll<std::io::Result<usize>> {
-        let mut fu = Box::pin(async {
-            match self.data_rx.recv().await {
-                Ok(b) => {
-                    buf.copy_from_slice(&b);
-                    Ok(b.len())
-                }
-                Err(e) => Err(std::io::Error::new(std::io::ErrorKind::Other, e)),
-            }
-        });
-        fu.poll(cx)
+        Pin::new(&mut self.reader).poll_read(cx, buf)
     }
 }
 
 impl AsyncWrite for WebrtcStream {
     fn poll_write(
-        self: std::pin::Pin<&mut Self>,
+        mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
-        let mut fu = Box::pin(async {
-            let bytes = Bytes::copy_from_slice(buf);
+        // Wait for previous message, then keep this one in flight.
+        ready!(self.as_mut().poll_send_ready(cx))?;
 
-            self.dc
-                .send(&bytes)
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-        });
+        self.start_send(Bytes::copy_from_slice(buf))?;
 
-        fu.poll(cx)
+        Poll::Ready(Ok(buf.len()))
     }
 
     fn poll_flush(
         self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
+        cx: &mut std::task::Context<'_>,
     ) -> Poll<std::io::Result<()>> {
-        Poll::Ready(Ok(()))
+        self.poll_send_flush(cx).map_err(Into::into)
     }
 
     fn poll_close(
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<std::io::Result<()>> {
-        let mut fu = Box::pin(async {
-            self.dc
-                .close()
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-        });
-
-        fu.poll(cx)
+        self.poll_send_close(cx).map_err(Into::into)
+    }
+}
+
+impl P2pMessageStream for WebrtcStream {
+    type Error = Error;
+
+    fn poll_recv(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes>>> {
+        self.reader.poll_recv(cx).map(|m| m.map(Ok))
+    }
+
+    fn poll_send_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_send_flush(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, message: Bytes) -> Result<()> {
+        let dc = self.dc.clone();
+        let open_rx = self.open_rx.clone();
+
+        self.sending = Some(Box::pin(async move {
+            let _ = open_rx.recv().await;
+
+            dc.send(&message).await?;
+            Ok(())
+        }));
+
+        Ok(())
+    }
+
+    fn poll_send_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if let Some(fu) = self.sending.as_mut() {
+            let res = ready!(fu.poll(cx));
+            self.sending = None;
+            res?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_send_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.as_mut().poll_send_flush(cx))?;
+
+        if !self.closing {
+            let dc = self.dc.clone();
+
+            self.closing = true;
+            self.sending = Some(Box::pin(async move {
+                dc.close().await?;
+                Ok(())
+            }));
+        }
+
+        self.poll_send_flush(cx)
     }
 }
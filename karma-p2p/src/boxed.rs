@@ -0,0 +1,652 @@
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{future, ready, FutureExt};
+
+use crate::{P2pSocket, P2pSocketEvents, P2pStream, SocketEvent};
+
+/// Stream of [`BoxedSocket`].
+pub type BoxedStream = Pin<Box<dyn P2pStream + Send>>;
+
+/// Stream of [`BoxedSocket`] being connected.
+pub type BoxedConnecting =
+    Pin<Box<dyn Future<Output = Result<BoxedStream, DynSocketError>> + Send>>;
+
+/// Remote address of [`BoxedSocket`] being set.
+pub type BoxedSettingRemoteAddr = Pin<Box<dyn Future<Output = Result<(), DynSocketError>> + Send>>;
+
+#[derive(Debug)]
+pub enum DynSocketError {
+    /// Label given where encoded address is expected, or the other way.
+    ErrAddrType,
+    /// Boxed socket wraps bound socket, it can't be bound itself.
+    ErrBind,
+    ErrEncode(String),
+    ErrDecode(String),
+    /// Error of backend socket, typed error is kept and can be downcast.
+    SocketError(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for DynSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynSocketError::ErrAddrType => write!(f, "wrong address variant"),
+            DynSocketError::ErrBind => write!(f, "boxed socket can't be bound"),
+            DynSocketError::ErrEncode(e) => write!(f, "encode address failed: {}", e),
+            DynSocketError::ErrDecode(e) => write!(f, "decode address failed: {}", e),
+            DynSocketError::SocketError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DynSocketError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DynSocketError::SocketError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl DynSocketError {
+    fn socket<E>(e: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        DynSocketError::SocketError(Box::new(e))
+    }
+
+    /// Error of backend socket if it's of type `E`.
+    pub fn downcast_ref<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        match self {
+            DynSocketError::SocketError(e) => e.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DynSocketError {
+    fn from(e: io::Error) -> Self {
+        DynSocketError::socket(e)
+    }
+}
+
+impl From<DynSocketError> for io::Error {
+    fn from(e: DynSocketError) -> Self {
+        match e {
+            DynSocketError::SocketError(e) => match e.downcast::<io::Error>() {
+                Ok(e) => *e,
+                Err(e) => io::Error::other(e),
+            },
+            e => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+/// Address of [`BoxedSocket`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DynAddr {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Label(String),
+
+    /// Address of backend encoded by its [`AddrCodec`], `last` tells whether
+    /// backend reported it as last address, see [`P2pSocket::is_last_addr`].
+    Encoded { addr: Vec<u8>, last: bool },
+}
+
+/// Convert addresses of backend to and from [`DynAddr`].
+pub trait AddrCodec<A> {
+    /// Label address of stream `name`.
+    fn label(&self, name: &str) -> A;
+
+    fn encode(&self, addr: &A) -> Result<Vec<u8>, DynSocketError>;
+
+    fn decode(&self, buf: &[u8]) -> Result<A, DynSocketError>;
+}
+
+/// Encode addresses with serde, labels are built by `label`, usually the `Label`
+/// variant of backend address.
+#[cfg(any(feature = "json", feature = "cbor", feature = "bincode"))]
+pub struct SerdeAddrCodec<A> {
+    format: crate::Format,
+    label: fn(String) -> A,
+}
+
+#[cfg(any(feature = "json", feature = "cbor", feature = "bincode"))]
+impl<A> SerdeAddrCodec<A> {
+    pub fn new(format: crate::Format, label: fn(String) -> A) -> Self {
+        Self { format, label }
+    }
+}
+
+#[cfg(any(feature = "json", feature = "cbor", feature = "bincode"))]
+impl<A> AddrCodec<A> for SerdeAddrCodec<A>
+where
+    A: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn label(&self, name: &str) -> A {
+        (self.label)(String::from(name))
+    }
+
+    fn encode(&self, addr: &A) -> Result<Vec<u8>, DynSocketError> {
+        self.format
+            .encode(addr)
+            .map(|buf| buf.to_vec())
+            .map_err(|e| DynSocketError::ErrEncode(e.to_string()))
+    }
+
+    fn decode(&self, buf: &[u8]) -> Result<A, DynSocketError> {
+        self.format
+            .decode(buf)
+            .map_err(|e| DynSocketError::ErrDecode(e.to_string()))
+    }
+}
+
+/// Object safe [`P2pSocket`] of bound socket, with addresses encoded and streams
+/// boxed. Labels are passed by name.
+///
+/// It's `Send`, so it's for native backends only. Browser sockets hold `Rc` and
+/// can't be boxed.
+pub trait DynP2pSocket: Send {
+    fn connecting(&self, label: &str, port: u16) -> BoxedConnecting;
+
+    fn poll_accept(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<BoxedStream, DynSocketError>>;
+
+    fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), DynSocketError>>;
+
+    /// Encoded address, and whether it's last address of backend.
+    fn poll_fetch_local_addr(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Vec<u8>, bool), DynSocketError>>;
+
+    fn setting_remote_addr(&self, remote: &[u8]) -> BoxedSettingRemoteAddr;
+
+    fn poll_event(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>>;
+
+    fn poll_state(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>>;
+}
+
+struct Erased<P, C> {
+    socket: P,
+    codec: C,
+}
+
+impl<P, C> DynP2pSocket for Erased<P, C>
+where
+    P: P2pSocket + P2pSocketEvents + Send + Unpin,
+    P::Stream: Send + 'static,
+    P::Error: std::error::Error + Send + Sync + 'static,
+    P::Connecting: Send + 'static,
+    P::SettingRemoteAddr: Send + 'static,
+    C: AddrCodec<P::Addr> + Send + Unpin,
+{
+    fn connecting(&self, label: &str, port: u16) -> BoxedConnecting {
+        let connecting = self.socket.connecting(self.codec.label(label), port);
+
+        Box::pin(async move {
+            let stream = connecting.await.map_err(DynSocketError::socket)?;
+
+            Ok(Box::pin(stream) as BoxedStream)
+        })
+    }
+
+    fn poll_accept(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<BoxedStream, DynSocketError>> {
+        let stream = ready!(Pin::new(&self.get_ref().socket).poll_accept(cx))
+            .map_err(DynSocketError::socket)?;
+
+        Poll::Ready(Ok(Box::pin(stream)))
+    }
+
+    fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), DynSocketError>> {
+        Pin::new(&mut self.get_mut().socket)
+            .poll_start(cx)
+            .map_err(DynSocketError::socket)
+    }
+
+    fn poll_fetch_local_addr(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(Vec<u8>, bool), DynSocketError>> {
+        let this = self.get_mut();
+
+        let addr = ready!(Pin::new(&mut this.socket).poll_fetch_local_addr(cx))
+            .map_err(DynSocketError::socket)?;
+
+        let last = P::is_last_addr(&addr);
+
+        Poll::Ready(this.codec.encode(&addr).map(|buf| (buf, last)))
+    }
+
+    fn setting_remote_addr(&self, remote: &[u8]) -> BoxedSettingRemoteAddr {
+        let remote = match self.codec.decode(remote) {
+            Ok(remote) => remote,
+            Err(e) => return future::ready(Err(e)).boxed(),
+        };
+
+        let setting = self.socket.setting_remote_addr(remote);
+
+        Box::pin(async move { setting.await.map_err(DynSocketError::socket) })
+    }
+
+    fn poll_event(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        Pin::new(&self.get_ref().socket).poll_event(cx)
+    }
+
+    fn poll_state(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>> {
+        Pin::new(&self.get_ref().socket).poll_state(cx, seen)
+    }
+}
+
+/// Bound socket of any backend behind one type, so peers over several transports
+/// are kept together.
+///
+/// Backend socket is bound first then boxed, `bind` of boxed socket fails with
+/// `ErrBind`.
+///
+/// Boxed socket, its streams and operations are `Send`, so only native backends can
+/// be boxed, the wasm `WebrtcSocket` can't.
+pub struct BoxedSocket {
+    inner: Pin<Box<dyn DynP2pSocket>>,
+}
+
+impl BoxedSocket {
+    /// Box `socket`, its addresses are converted by `codec`.
+    pub fn new<P, C>(socket: P, codec: C) -> Self
+    where
+        P: P2pSocket + P2pSocketEvents + Send + Unpin + 'static,
+        P::Stream: Send + 'static,
+        P::Error: std::error::Error + Send + Sync + 'static,
+        P::Connecting: Send + 'static,
+        P::SettingRemoteAddr: Send + 'static,
+        C: AddrCodec<P::Addr> + Send + Unpin + 'static,
+    {
+        Self::from_dyn(Box::pin(Erased { socket, codec }))
+    }
+
+    pub fn from_dyn(inner: Pin<Box<dyn DynP2pSocket>>) -> Self {
+        Self { inner }
+    }
+}
+
+impl P2pSocket for BoxedSocket {
+    type Addr = DynAddr;
+
+    type Stream = BoxedStream;
+
+    type Error = DynSocketError;
+
+    type Binding = future::Ready<Result<Self, DynSocketError>>;
+
+    type Connecting = BoxedConnecting;
+
+    type SettingRemoteAddr = BoxedSettingRemoteAddr;
+
+    fn binding(_bootstrap: DynAddr) -> Self::Binding {
+        future::ready(Err(DynSocketError::ErrBind))
+    }
+
+    fn is_last_addr(addr: &DynAddr) -> bool {
+        matches!(addr, DynAddr::Encoded { last: true, .. })
+    }
+
+    fn connecting(&self, label: DynAddr, port: u16) -> BoxedConnecting {
+        match label {
+            DynAddr::Label(label) => self.inner.connecting(&label, port),
+            DynAddr::Encoded { .. } => future::ready(Err(DynSocketError::ErrAddrType)).boxed(),
+        }
+    }
+
+    fn poll_accept(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<BoxedStream, DynSocketError>> {
+        self.get_ref().inner.as_ref().poll_accept(cx)
+    }
+
+    fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), DynSocketError>> {
+        self.get_mut().inner.as_mut().poll_start(cx)
+    }
+
+    fn poll_fetch_local_addr(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<DynAddr, DynSocketError>> {
+        self.get_mut()
+            .inner
+            .as_mut()
+            .poll_fetch_local_addr(cx)
+            .map_ok(|(addr, last)| DynAddr::Encoded { addr, last })
+    }
+
+    fn setting_remote_addr(&self, remote: DynAddr) -> BoxedSettingRemoteAddr {
+        match remote {
+            DynAddr::Encoded { addr: remote, .. } => self.inner.setting_remote_addr(&remote),
+            DynAddr::Label(_) => future::ready(Err(DynSocketError::ErrAddrType)).boxed(),
+        }
+    }
+}
+
+impl P2pSocketEvents for BoxedSocket {
+    fn poll_event(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        self.get_ref().inner.as_ref().poll_event(cx)
+    }
+
+    fn poll_state(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>> {
+        self.get_ref().inner.as_ref().poll_state(cx, seen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use futures_lite::{future::block_on, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{
+        pipe::{pipe, Pipe},
+        Format, P2pSocketExt,
+    };
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    enum FakeAddr {
+        Label(String),
+        Offer(u64),
+        Answer(u64),
+    }
+
+    #[derive(Debug)]
+    enum FakeError {
+        ErrAddrType,
+        ErrPeerNotFound(u64),
+    }
+
+    impl fmt::Display for FakeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    impl std::error::Error for FakeError {}
+
+    /// Socket of a linked pair, ends of channels not yet connected by other side
+    /// wait in `link`.
+    struct FakeSocket {
+        link: Arc<Mutex<HashMap<(String, u16), Pipe>>>,
+        id: u64,
+        peer: u64,
+        started: bool,
+    }
+
+    fn fake_pair() -> (FakeSocket, FakeSocket) {
+        let link = Arc::new(Mutex::new(HashMap::new()));
+
+        let socket = |id, peer| FakeSocket {
+            link: link.clone(),
+            id,
+            peer,
+            started: false,
+        };
+
+        (socket(1, 2), socket(2, 1))
+    }
+
+    impl P2pSocket for FakeSocket {
+        type Addr = FakeAddr;
+
+        type Stream = Pipe;
+
+        type Error = FakeError;
+
+        type Binding = future::Ready<Result<Self, FakeError>>;
+
+        type Connecting = future::Ready<Result<Pipe, FakeError>>;
+
+        type SettingRemoteAddr = future::Ready<Result<(), FakeError>>;
+
+        fn binding(_bootstrap: FakeAddr) -> Self::Binding {
+            future::ready(Err(FakeError::ErrAddrType))
+        }
+
+        fn is_last_addr(addr: &FakeAddr) -> bool {
+            !matches!(addr, FakeAddr::Label(_))
+        }
+
+        fn connecting(&self, label: FakeAddr, port: u16) -> Self::Connecting {
+            let label = match label {
+                FakeAddr::Label(label) => label,
+                _ => return future::ready(Err(FakeError::ErrAddrType)),
+            };
+
+            let mut link = self.link.lock().unwrap();
+
+            let stream = link.remove(&(label.clone(), port)).unwrap_or_else(|| {
+                let (local, remote) = pipe();
+                link.insert((label, port), remote);
+                local
+            });
+
+            future::ready(Ok(stream))
+        }
+
+        fn poll_accept(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Result<Pipe, FakeError>> {
+            Poll::Pending
+        }
+
+        fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), FakeError>> {
+            self.get_mut().started = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_fetch_local_addr(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<FakeAddr, FakeError>> {
+            if self.started {
+                Poll::Ready(Ok(FakeAddr::Offer(self.id)))
+            } else {
+                Poll::Ready(Ok(FakeAddr::Answer(self.id)))
+            }
+        }
+
+        fn setting_remote_addr(&self, remote: FakeAddr) -> Self::SettingRemoteAddr {
+            future::ready(match remote {
+                FakeAddr::Offer(id) | FakeAddr::Answer(id) if id == self.peer => Ok(()),
+                FakeAddr::Offer(id) | FakeAddr::Answer(id) => Err(FakeError::ErrPeerNotFound(id)),
+                FakeAddr::Label(_) => Err(FakeError::ErrAddrType),
+            })
+        }
+    }
+
+    impl P2pSocketEvents for FakeSocket {
+        fn poll_event(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+            Poll::Ready(None)
+        }
+    }
+
+    struct FakeCodec;
+
+    impl AddrCodec<FakeAddr> for FakeCodec {
+        fn label(&self, name: &str) -> FakeAddr {
+            FakeAddr::Label(String::from(name))
+        }
+
+        fn encode(&self, addr: &FakeAddr) -> Result<Vec<u8>, DynSocketError> {
+            let (tag, id) = match addr {
+                FakeAddr::Offer(id) => (0, id),
+                FakeAddr::Answer(id) => (1, id),
+                FakeAddr::Label(_) => return Err(DynSocketError::ErrAddrType),
+            };
+
+            let mut buf = vec![tag];
+            buf.extend(id.to_be_bytes());
+
+            Ok(buf)
+        }
+
+        fn decode(&self, buf: &[u8]) -> Result<FakeAddr, DynSocketError> {
+            let id = buf
+                .get(1..9)
+                .map(|id| u64::from_be_bytes(id.try_into().unwrap()))
+                .ok_or_else(|| DynSocketError::ErrDecode("short address".into()))?;
+
+            match buf[0] {
+                0 => Ok(FakeAddr::Offer(id)),
+                1 => Ok(FakeAddr::Answer(id)),
+                _ => Err(DynSocketError::ErrDecode("unknown tag".into())),
+            }
+        }
+    }
+
+    async fn send_until_last(local: &mut BoxedSocket, remote: &BoxedSocket) {
+        loop {
+            let addr = local.fetch_local_addr().await.unwrap();
+            let last = BoxedSocket::is_last_addr(&addr);
+            remote.set_remote_addr(addr).await.unwrap();
+
+            if last {
+                break;
+            }
+        }
+    }
+
+    async fn pair_boxed(a: &mut BoxedSocket, b: &mut BoxedSocket) {
+        a.start().await.unwrap();
+        send_until_last(a, b).await;
+        send_until_last(b, a).await;
+    }
+
+    #[test]
+    fn boxed_sockets() {
+        block_on(async {
+            let (a, b) = fake_pair();
+            let mut a = BoxedSocket::new(a, FakeCodec);
+            let mut b = BoxedSocket::new(b, FakeCodec);
+
+            let label = DynAddr::Label("data".into());
+            let mut sa = a.connect(label.clone(), 1).await.unwrap();
+
+            a.start().await.unwrap();
+            let offer = a.fetch_local_addr().await.unwrap();
+            assert!(BoxedSocket::is_last_addr(&offer));
+
+            b.set_remote_addr(offer).await.unwrap();
+            let answer = b.fetch_local_addr().await.unwrap();
+            a.set_remote_addr(answer).await.unwrap();
+
+            let mut sb = b.connect(label.clone(), 1).await.unwrap();
+
+            sa.write_all(b"ping").await.unwrap();
+
+            let mut buf = [0u8; 4];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            assert!(matches!(
+                a.set_remote_addr(label).await,
+                Err(DynSocketError::ErrAddrType)
+            ));
+            assert!(matches!(
+                a.connect(
+                    DynAddr::Encoded {
+                        addr: vec![],
+                        last: true,
+                    },
+                    1
+                )
+                .await,
+                Err(DynSocketError::ErrAddrType)
+            ));
+            assert!(matches!(
+                a.set_remote_addr(DynAddr::Encoded {
+                    addr: vec![7],
+                    last: true,
+                })
+                .await,
+                Err(DynSocketError::ErrDecode(_))
+            ));
+
+            let unknown = DynAddr::Encoded {
+                addr: FakeCodec.encode(&FakeAddr::Offer(u64::MAX)).unwrap(),
+                last: true,
+            };
+            let err = b.set_remote_addr(unknown).await.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<FakeError>(),
+                Some(FakeError::ErrPeerNotFound(u64::MAX))
+            ));
+            assert!(matches!(
+                BoxedSocket::bind(DynAddr::Label("any".into())).await,
+                Err(DynSocketError::ErrBind)
+            ));
+        })
+    }
+
+    #[test]
+    fn mixed_backends() {
+        block_on(async {
+            let mut sockets: Vec<BoxedSocket> = Vec::new();
+
+            let (a, b) = fake_pair();
+            sockets.push(BoxedSocket::new(a, FakeCodec));
+            sockets.push(BoxedSocket::new(b, FakeCodec));
+
+            for format in [Format::Json, Format::Cbor] {
+                let (a, b) = fake_pair();
+                sockets.push(BoxedSocket::new(
+                    a,
+                    SerdeAddrCodec::new(format, FakeAddr::Label),
+                ));
+                sockets.push(BoxedSocket::new(
+                    b,
+                    SerdeAddrCodec::new(format, FakeAddr::Label),
+                ));
+            }
+
+            for pair in sockets.chunks_mut(2) {
+                let (a, b) = pair.split_at_mut(1);
+                let (a, b) = (&mut a[0], &mut b[0]);
+
+                let mut sa = a.connect(DynAddr::Label("data".into()), 1).await.unwrap();
+
+                pair_boxed(a, b).await;
+
+                let mut sb = b.connect(DynAddr::Label("data".into()), 1).await.unwrap();
+
+                sa.write_all(b"ping").await.unwrap();
+
+                let mut buf = [0u8; 4];
+                sb.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ping");
+            }
+        })
+    }
+}
@@ -0,0 +1,32 @@
+use bytes::Bytes;
+
+use crate::FrameError;
+
+/// Converts items to and from frames of `Framed`, each item is one frame.
+pub trait Codec {
+    type Item;
+
+    type Error: From<FrameError>;
+
+    fn encode(&mut self, item: Self::Item) -> Result<Bytes, Self::Error>;
+
+    fn decode(&mut self, frame: Bytes) -> Result<Self::Item, Self::Error>;
+}
+
+/// Codec passing frames as they are.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BytesCodec;
+
+impl Codec for BytesCodec {
+    type Item = Bytes;
+
+    type Error = FrameError;
+
+    fn encode(&mut self, item: Bytes) -> Result<Bytes, FrameError> {
+        Ok(item)
+    }
+
+    fn decode(&mut self, frame: Bytes) -> Result<Bytes, FrameError> {
+        Ok(frame)
+    }
+}
@@ -1,5 +1,6 @@
 use std::net::SocketAddr;
 
+#[derive(Debug, Clone, Default)]
 pub struct Config {
     pub forward_listen: Vec<SocketAddr>,
     pub enable_forward: bool,
@@ -0,0 +1,22 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::Signaling;
+
+/// Change of peers seen through a bootstrap node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeerEvent {
+    Joined(String),
+    Left(String),
+}
+
+/// Signaling connected to a bootstrap node, which also reports peers.
+pub trait Discovery<A>: Signaling<A> {
+    /// Get next change of peers, `None` means connection to bootstrap node is closed.
+    fn poll_next_peer(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<PeerEvent>, Self::Error>>;
+}
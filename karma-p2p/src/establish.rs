@@ -0,0 +1,367 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{futures::EstablishFuture, EstablishError, P2pSocket, Signaling};
+
+/// Exchange addresses of `socket` with peer `remote` through `signaling`.
+///
+/// The `initiator` side starts the socket and sends the offer. Streams should be
+/// connected before, so they are negotiated in the offer. Addresses are exchanged
+/// until both sides sent their last address, see [`P2pSocket::is_last_addr`], or
+/// signaling has no more address from `remote`.
+pub fn establish<'a, S, G>(
+    socket: &'a mut S,
+    signaling: &'a mut G,
+    remote: &'a str,
+    initiator: bool,
+) -> EstablishFuture<'a, S, G>
+where
+    S: P2pSocket,
+    G: Signaling<S::Addr>,
+{
+    EstablishFuture {
+        socket,
+        signaling,
+        remote,
+        state: EstablishState::new(initiator),
+    }
+}
+
+/// Progress of address exchange, used by owners of socket and signaling which can't
+/// lend them to [`establish`].
+pub struct EstablishState<S: P2pSocket> {
+    pub started: bool,
+    pub outgoing: Option<S::Addr>,
+    pub incoming: Option<S::Addr>,
+    /// Incoming address being set, kept until done.
+    pub setting: Option<S::SettingRemoteAddr>,
+    pub recv_done: bool,
+    pub local_done: bool,
+    pub remote_done: bool,
+}
+
+impl<S: P2pSocket> EstablishState<S> {
+    pub fn new(initiator: bool) -> Self {
+        Self {
+            started: !initiator,
+            outgoing: None,
+            incoming: None,
+            setting: None,
+            recv_done: false,
+            local_done: false,
+            remote_done: false,
+        }
+    }
+
+    /// Exchange addresses, ready when both sides sent their last address or signaling
+    /// has no more address from `remote`.
+    ///
+    /// Polled again after ready, exchange goes on, so later addresses such as those of
+    /// an ICE restart are still forwarded.
+    #[allow(clippy::type_complexity)]
+    pub fn poll_establish<G>(
+        &mut self,
+        cx: &mut Context<'_>,
+        socket: &mut S,
+        signaling: &mut G,
+        remote: &str,
+    ) -> Poll<Result<(), EstablishError<S::Error, G::Error>>>
+    where
+        S: Unpin,
+        S::Addr: Clone,
+        G: Signaling<S::Addr> + Unpin,
+    {
+        if !self.started {
+            match Pin::new(&mut *socket).poll_start(cx) {
+                Poll::Ready(Ok(())) => self.started = true,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(EstablishError::SocketError(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        loop {
+            let mut progress = false;
+
+            if self.outgoing.is_none() {
+                match Pin::new(&mut *socket).poll_fetch_local_addr(cx) {
+                    Poll::Ready(Ok(addr)) => self.outgoing = Some(addr),
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(EstablishError::SocketError(e))),
+                    Poll::Pending => {}
+                }
+            }
+
+            if let Some(addr) = &self.outgoing {
+                match Pin::new(&mut *signaling).poll_send(cx, remote, addr) {
+                    Poll::Ready(Ok(())) => {
+                        self.local_done = S::is_last_addr(addr);
+                        self.outgoing = None;
+                        progress = true;
+                    }
+                    Poll::Ready(Err(e)) => {
+                        return Poll::Ready(Err(EstablishError::SignalingError(e)))
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            if self.incoming.is_none() && !self.recv_done {
+                match Pin::new(&mut *signaling).poll_recv(cx, remote) {
+                    Poll::Ready(Ok(Some(addr))) => self.incoming = Some(addr),
+                    Poll::Ready(Ok(None)) => self.recv_done = true,
+                    Poll::Ready(Err(e)) => {
+                        return Poll::Ready(Err(EstablishError::SignalingError(e)))
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            if let Some(addr) = &self.incoming {
+                let setting = self
+                    .setting
+                    .get_or_insert_with(|| socket.setting_remote_addr(addr.clone()));
+
+                match Pin::new(setting).poll(cx) {
+                    Poll::Ready(Ok(())) => {
+                        self.remote_done = S::is_last_addr(addr);
+                        self.incoming = None;
+                        self.setting = None;
+                        progress = true;
+                    }
+                    Poll::Ready(Err(e)) => {
+                        self.setting = None;
+                        return Poll::Ready(Err(EstablishError::SocketError(e)));
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            let exchanged = self.recv_done || (self.local_done && self.remote_done);
+
+            if exchanged && self.incoming.is_none() && self.outgoing.is_none() {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        pin::Pin,
+        sync::Mutex,
+        task::{Context, Poll},
+    };
+
+    use futures_lite::{
+        future::{self, block_on},
+        io::Cursor,
+    };
+
+    use crate::{establish, EstablishState, P2pSocket, Signaling};
+
+    #[derive(Default)]
+    struct FakeSocket {
+        started: bool,
+        local: VecDeque<u16>,
+        remotes: Mutex<Vec<u16>>,
+    }
+
+    impl P2pSocket for FakeSocket {
+        type Stream = Cursor<Vec<u8>>;
+
+        type Addr = u16;
+
+        type Error = ();
+
+        type Binding = future::Ready<Result<Self, ()>>;
+
+        type Connecting = future::Ready<Result<Self::Stream, ()>>;
+
+        type SettingRemoteAddr = future::Ready<Result<(), ()>>;
+
+        fn binding(_bootstrap: u16) -> Self::Binding {
+            future::ready(Ok(Self::default()))
+        }
+
+        fn connecting(&self, _label: u16, _port: u16) -> Self::Connecting {
+            future::ready(Err(()))
+        }
+
+        fn poll_accept(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Result<Self::Stream, ()>> {
+            Poll::Pending
+        }
+
+        fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            let this = self.get_mut();
+
+            this.started = true;
+            this.local.push_front(0);
+
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_fetch_local_addr(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<u16, ()>> {
+            match self.get_mut().local.pop_front() {
+                Some(addr) => Poll::Ready(Ok(addr)),
+                None => Poll::Pending,
+            }
+        }
+
+        fn setting_remote_addr(&self, remote: u16) -> Self::SettingRemoteAddr {
+            self.remotes.lock().unwrap().push(remote);
+            future::ready(Ok(()))
+        }
+
+        fn is_last_addr(addr: &u16) -> bool {
+            *addr >= 100
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeSignaling {
+        sent: Vec<(String, u16)>,
+        incoming: VecDeque<u16>,
+        // Signaling stays open when out of addresses, as a live link does.
+        open: bool,
+    }
+
+    impl Signaling<u16> for FakeSignaling {
+        type Error = ();
+
+        fn poll_send(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            remote: &str,
+            addr: &u16,
+        ) -> Poll<Result<(), ()>> {
+            self.get_mut().sent.push((String::from(remote), *addr));
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_recv(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _remote: &str,
+        ) -> Poll<Result<Option<u16>, ()>> {
+            let this = self.get_mut();
+
+            match this.incoming.pop_front() {
+                None if this.open => Poll::Pending,
+                addr => Poll::Ready(Ok(addr)),
+            }
+        }
+    }
+
+    #[test]
+    fn exchange_addresses() {
+        let mut socket = FakeSocket {
+            local: VecDeque::from(vec![1, 2]),
+            ..Default::default()
+        };
+        let mut signaling = FakeSignaling {
+            incoming: VecDeque::from(vec![10, 11, 12]),
+            ..Default::default()
+        };
+
+        block_on(establish(&mut socket, &mut signaling, "peer", true)).unwrap();
+
+        assert!(socket.started);
+        assert_eq!(*socket.remotes.lock().unwrap(), vec![10, 11, 12]);
+
+        let sent: Vec<u16> = signaling.sent.iter().map(|(_, a)| *a).collect();
+        assert_eq!(sent, vec![0, 1, 2]);
+        assert!(signaling.sent.iter().all(|(r, _)| r == "peer"));
+    }
+
+    #[test]
+    fn answer_side_not_started() {
+        let mut socket = FakeSocket::default();
+        let mut signaling = FakeSignaling {
+            incoming: VecDeque::from(vec![10]),
+            ..Default::default()
+        };
+
+        block_on(establish(&mut socket, &mut signaling, "peer", false)).unwrap();
+
+        assert!(!socket.started);
+        assert!(signaling.sent.is_empty());
+    }
+
+    #[test]
+    fn finish_on_last_addresses() {
+        let mut socket = FakeSocket {
+            local: VecDeque::from(vec![1, 100]),
+            ..Default::default()
+        };
+        let mut signaling = FakeSignaling {
+            incoming: VecDeque::from(vec![10, 101]),
+            open: true,
+            ..Default::default()
+        };
+
+        let mut state = EstablishState::new(true);
+
+        // Signaling stays open, exchange is done once both sides sent their last.
+        block_on(future::poll_fn(|cx| {
+            state.poll_establish(cx, &mut socket, &mut signaling, "peer")
+        }))
+        .unwrap();
+
+        assert_eq!(*socket.remotes.lock().unwrap(), vec![10, 101]);
+
+        // Addresses of a later round are still forwarded when polled again.
+        socket.local.extend([2, 102]);
+        signaling.incoming.extend([11, 103]);
+
+        block_on(future::poll_fn(|cx| {
+            state.poll_establish(cx, &mut socket, &mut signaling, "peer")
+        }))
+        .unwrap();
+
+        let sent: Vec<u16> = signaling.sent.iter().map(|(_, a)| *a).collect();
+        assert_eq!(sent, vec![0, 1, 100, 2, 102]);
+        assert_eq!(*socket.remotes.lock().unwrap(), vec![10, 101, 11, 103]);
+    }
+
+    #[test]
+    fn forward_local_after_remote_done() {
+        let mut socket = FakeSocket {
+            local: VecDeque::from(vec![1]),
+            ..Default::default()
+        };
+        let mut signaling = FakeSignaling {
+            incoming: VecDeque::from(vec![101]),
+            open: true,
+            ..Default::default()
+        };
+
+        let mut state = EstablishState::new(true);
+
+        // Remote is done first, local candidates gathered later are still sent.
+        let res = block_on(future::poll_once(future::poll_fn(|cx| {
+            state.poll_establish(cx, &mut socket, &mut signaling, "peer")
+        })));
+        assert!(res.is_none());
+
+        socket.local.extend([2, 100]);
+
+        block_on(future::poll_fn(|cx| {
+            state.poll_establish(cx, &mut socket, &mut signaling, "peer")
+        }))
+        .unwrap();
+
+        let sent: Vec<u16> = signaling.sent.iter().map(|(_, a)| *a).collect();
+        assert_eq!(sent, vec![0, 1, 2, 100]);
+    }
+}
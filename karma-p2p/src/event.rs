@@ -0,0 +1,223 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::futures::{EventsStream, NextStateFuture};
+
+/// Connection state of socket and its channels, reported by backends tracking it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketEvent {
+    /// Candidate pairs are being checked.
+    Checking,
+    Connected,
+    /// Connection is lost, it may come back without intervention.
+    Disconnected,
+    Failed,
+    Closed,
+    /// Remote failed to prove its identity, socket is closed.
+    IdentityFailed,
+
+    /// Stream of label is open for sending.
+    ChannelOpened(String),
+    ChannelClosed(String),
+}
+
+impl SocketEvent {
+    /// Event is about connection of socket rather than one of its channels.
+    pub fn is_state(&self) -> bool {
+        !matches!(
+            self,
+            SocketEvent::ChannelOpened(_) | SocketEvent::ChannelClosed(_)
+        )
+    }
+}
+
+/// Socket reporting [`SocketEvent`]s, so callers learn about broken connections
+/// before reads fail.
+pub trait P2pSocketEvents {
+    /// Next event, `None` once socket stops reporting.
+    fn poll_event(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>>;
+
+    /// Connection state, which is last event reported about connection, ready once it
+    /// differs from `seen`. Unlike events, state isn't taken by reading it, so every
+    /// reader sees it.
+    ///
+    /// Sockets not tracking connection are always ready with `None`.
+    fn poll_state(
+        self: Pin<&Self>,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>> {
+        let _ = (cx, seen);
+        Poll::Ready(None)
+    }
+
+    fn events(&self) -> EventsStream<'_, Self> {
+        EventsStream { socket: self }
+    }
+
+    fn next_state(&self, seen: Option<SocketEvent>) -> NextStateFuture<'_, Self> {
+        NextStateFuture { socket: self, seen }
+    }
+}
+
+/// Events kept by [`EventQueue`] until polled, oldest are dropped beyond it.
+pub const EVENT_QUEUE_CAPACITY: usize = 64;
+
+#[derive(Default)]
+struct EventQueueInner {
+    events: VecDeque<SocketEvent>,
+    waker: Option<Waker>,
+    closed: bool,
+    state: Option<SocketEvent>,
+    state_wakers: Vec<Waker>,
+}
+
+/// Events of socket waiting to be polled, shared with handlers reporting them.
+///
+/// Queue is bounded so a socket whose events are never polled doesn't grow, oldest
+/// event is dropped when it's full.
+#[derive(Clone, Default)]
+pub struct EventQueue {
+    inner: Arc<Mutex<EventQueueInner>>,
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report `event`, ignored once queue is closed.
+    pub fn push(&self, event: SocketEvent) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.closed {
+            return;
+        }
+
+        if event.is_state() {
+            inner.state = Some(event.clone());
+
+            for waker in inner.state_wakers.drain(..) {
+                waker.wake();
+            }
+        }
+
+        if inner.events.len() == EVENT_QUEUE_CAPACITY {
+            inner.events.pop_front();
+        }
+
+        inner.events.push_back(event);
+
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Stop reporting, queued events are still polled before `None`.
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.closed = true;
+
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        match inner.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if inner.closed => Poll::Ready(None),
+            None => {
+                inner.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Connection state, see [`P2pSocketEvents::poll_state`].
+    pub fn poll_state(
+        &self,
+        cx: &mut Context<'_>,
+        seen: Option<&SocketEvent>,
+    ) -> Poll<Option<SocketEvent>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.state.as_ref() != seen {
+            return Poll::Ready(inner.state.clone());
+        }
+
+        // Every reader waits for same change, so all of them are woken.
+        if !inner.state_wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            inner.state_wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::future::{self, block_on};
+
+    use super::{EventQueue, SocketEvent, EVENT_QUEUE_CAPACITY};
+
+    #[test]
+    fn bounded_and_closed() {
+        block_on(async {
+            let queue = EventQueue::new();
+
+            queue.push(SocketEvent::Checking);
+            for i in 0..EVENT_QUEUE_CAPACITY {
+                queue.push(SocketEvent::ChannelOpened(i.to_string()));
+            }
+
+            queue.close();
+            queue.push(SocketEvent::Closed);
+
+            let mut seen = Vec::new();
+            while let Some(event) = future::poll_fn(|cx| queue.poll_next(cx)).await {
+                seen.push(event);
+            }
+
+            assert_eq!(seen.len(), EVENT_QUEUE_CAPACITY);
+            assert_eq!(seen[0], SocketEvent::ChannelOpened(String::from("0")));
+            assert!(!seen.contains(&SocketEvent::Checking));
+            assert!(!seen.contains(&SocketEvent::Closed));
+        });
+    }
+
+    #[test]
+    fn state_not_taken() {
+        block_on(async {
+            let queue = EventQueue::new();
+
+            let state = |seen: Option<SocketEvent>| {
+                let queue = queue.clone();
+                future::poll_once(future::poll_fn(move |cx| {
+                    queue.poll_state(cx, seen.as_ref())
+                }))
+            };
+
+            assert_eq!(state(None).await, None);
+
+            queue.push(SocketEvent::Connected);
+            queue.push(SocketEvent::ChannelOpened(String::from("a")));
+
+            // Read twice, and events are still there.
+            for _ in 0..2 {
+                assert_eq!(state(None).await, Some(Some(SocketEvent::Connected)));
+            }
+            assert_eq!(state(Some(SocketEvent::Connected)).await, None);
+
+            let event = future::poll_fn(|cx| queue.poll_next(cx)).await;
+            assert_eq!(event, Some(SocketEvent::Connected));
+        });
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::{ready, AsyncRead, AsyncWrite};
+
+/// Default largest frame of `Framed`.
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// Varint of `u64` takes at most 10 bytes.
+const MAX_VARINT_LEN: usize = 10;
+
+#[derive(Debug)]
+pub enum FrameError {
+    ErrFrameTooLarge(usize),
+    ErrInvalidLength,
+    IoError(io::Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::ErrFrameTooLarge(size) => write!(f, "frame of {} bytes too large", size),
+            FrameError::ErrInvalidLength => write!(f, "invalid frame length prefix"),
+            FrameError::IoError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> Self {
+        FrameError::IoError(e)
+    }
+}
+
+impl From<FrameError> for io::Error {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::IoError(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// Write unsigned LEB128 varint of `value`.
+pub fn encode_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+/// Writes one frame prefixed by varint length.
+#[derive(Debug, Default)]
+pub struct FrameWriter {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl FrameWriter {
+    /// Whether previous frame is completely written.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    /// Begin writing `frame`. Must be called after `poll_write` returned
+    /// `Ready(Ok(()))`.
+    pub fn start(&mut self, frame: &[u8], max_size: usize) -> Result<(), FrameError> {
+        if frame.len() > max_size {
+            return Err(FrameError::ErrFrameTooLarge(frame.len()));
+        }
+
+        self.buf.clear();
+        self.pos = 0;
+
+        encode_varint(frame.len() as u64, &mut self.buf);
+        self.buf.extend_from_slice(frame);
+
+        Ok(())
+    }
+
+    /// Write rest of frame and flush `stream`.
+    pub fn poll_write<W>(
+        &mut self,
+        cx: &mut Context<'_>,
+        stream: &mut W,
+    ) -> Poll<Result<(), FrameError>>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        while self.pos < self.buf.len() {
+            let n = ready!(Pin::new(&mut *stream).poll_write(cx, &self.buf[self.pos..]))?;
+
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+
+            self.pos += n;
+        }
+
+        ready!(Pin::new(&mut *stream).poll_flush(cx))?;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads frames prefixed by varint length.
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    prefix: Vec<u8>,
+    body: Option<(Vec<u8>, usize)>,
+}
+
+impl FrameReader {
+    /// Read next frame, `None` means stream ended between frames.
+    ///
+    /// Frames larger than `max_size` fail before their body is read.
+    pub fn poll_read<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        stream: &mut R,
+        max_size: usize,
+    ) -> Poll<Option<Result<Bytes, FrameError>>>
+    where
+        R: AsyncRead + Unpin + ?Sized,
+    {
+        loop {
+            if let Some((body, filled)) = &mut self.body {
+                if *filled == body.len() {
+                    let frame = Bytes::from(std::mem::take(body));
+
+                    self.body = None;
+
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+
+                match ready!(Pin::new(&mut *stream).poll_read(cx, &mut body[*filled..])) {
+                    Ok(0) => return Poll::Ready(Some(Err(eof()))),
+                    Ok(n) => *filled += n,
+                    Err(e) => return Poll::Ready(Some(Err(e.into()))),
+                }
+
+                continue;
+            }
+
+            // Length is read byte by byte, so no byte of body is consumed.
+            let mut byte = [0u8];
+
+            match ready!(Pin::new(&mut *stream).poll_read(cx, &mut byte)) {
+                Ok(0) if self.prefix.is_empty() => return Poll::Ready(None),
+                Ok(0) => return Poll::Ready(Some(Err(eof()))),
+                Ok(_) => self.prefix.push(byte[0]),
+                Err(e) => return Poll::Ready(Some(Err(e.into()))),
+            }
+
+            if byte[0] & 0x80 != 0 {
+                if self.prefix.len() == MAX_VARINT_LEN {
+                    return Poll::Ready(Some(Err(FrameError::ErrInvalidLength)));
+                }
+
+                continue;
+            }
+
+            let len = match decode_varint(&self.prefix) {
+                Some(len) => len,
+                None => return Poll::Ready(Some(Err(FrameError::ErrInvalidLength))),
+            };
+
+            self.prefix.clear();
+
+            if len > max_size as u64 {
+                return Poll::Ready(Some(Err(FrameError::ErrFrameTooLarge(len as usize))));
+            }
+
+            self.body = Some((vec![0; len as usize], 0));
+        }
+    }
+}
+
+fn decode_varint(buf: &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+
+    for (i, b) in buf.iter().enumerate() {
+        let bits = (*b & 0x7f) as u64;
+
+        if i == MAX_VARINT_LEN - 1 && bits > 1 {
+            return None;
+        }
+
+        value |= bits << (7 * i);
+    }
+
+    Some(value)
+}
+
+fn eof() -> FrameError {
+    io::Error::from(io::ErrorKind::UnexpectedEof).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use bytes::Bytes;
+    use futures_lite::{future::block_on, io::Cursor};
+
+    use super::{decode_varint, encode_varint, FrameError};
+    use crate::P2pStreamExt;
+
+    #[test]
+    fn varint() {
+        for value in [0, 1, 127, 128, 300, 16384, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            encode_varint(value, &mut buf);
+
+            assert_eq!(decode_varint(&buf), Some(value));
+        }
+
+        let mut buf = Vec::new();
+        encode_varint(300, &mut buf);
+        assert_eq!(buf, [0xac, 0x02]);
+
+        assert_eq!(
+            decode_varint(&[0xff; 9].iter().chain(&[0x02]).copied().collect::<Vec<_>>()),
+            None
+        );
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        block_on(async {
+            let mut stream = Cursor::new(Vec::new());
+
+            stream
+                .send_frame(Bytes::from_static(b"hello"), 16)
+                .await
+                .unwrap();
+            stream.send_frame(Bytes::new(), 16).await.unwrap();
+            stream
+                .send_frame(Bytes::from(vec![1; 200]), 1024)
+                .await
+                .unwrap();
+
+            let err = stream.send_frame(Bytes::from(vec![1; 17]), 16).await;
+            assert!(matches!(err, Err(FrameError::ErrFrameTooLarge(17))));
+
+            stream.set_position(0);
+
+            assert_eq!(stream.recv_frame(16).await.unwrap().unwrap(), "hello");
+            assert_eq!(stream.recv_frame(16).await.unwrap().unwrap(), "");
+
+            // Oversized frame is refused by prefix, before its body is read.
+            let err = stream.recv_frame(16).await.unwrap();
+            assert!(matches!(err, Err(FrameError::ErrFrameTooLarge(200))));
+            assert_eq!(stream.position(), 9);
+
+            let err = io::Error::from(err.unwrap_err());
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+            stream.set_position(209);
+            assert!(stream.recv_frame(16).await.is_none());
+        });
+    }
+
+    #[test]
+    fn truncated_frame() {
+        block_on(async {
+            let mut stream = Cursor::new(vec![5, b'a', b'b']);
+
+            let err = stream.recv_frame(16).await.unwrap();
+            assert!(matches!(err, Err(FrameError::IoError(_))));
+
+            let mut stream = Cursor::new(vec![0x80; 11]);
+
+            let err = stream.recv_frame(16).await.unwrap();
+            assert!(matches!(err, Err(FrameError::ErrInvalidLength)));
+        });
+    }
+}
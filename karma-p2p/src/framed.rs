@@ -0,0 +1,219 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::ready;
+
+use crate::{
+    futures::{CloseFramedFuture, RecvItemFuture, SendItemFuture},
+    Codec, FrameReader, FrameWriter, P2pStream, MAX_FRAME_SIZE,
+};
+
+/// Items of codec `C` sent as length prefixed frames over `S`.
+///
+/// Frames are the same as `send_frame` and `recv_frame` of `P2pStreamExt`, larger
+/// than `max_frame_size` fail with `FrameError::ErrFrameTooLarge` on both sides.
+pub struct Framed<S, C> {
+    stream: S,
+    codec: C,
+    max_frame_size: usize,
+    reader: FrameReader,
+    writer: FrameWriter,
+}
+
+impl<S, C> Framed<S, C>
+where
+    S: P2pStream + Unpin,
+    C: Codec,
+{
+    pub fn new(stream: S, codec: C) -> Self {
+        Self {
+            stream,
+            codec,
+            max_frame_size: MAX_FRAME_SIZE,
+            reader: FrameReader::default(),
+            writer: FrameWriter::default(),
+        }
+    }
+
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    pub fn set_max_frame_size(&mut self, size: usize) {
+        self.max_frame_size = size;
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// Get underlying stream, frames partially read or written are lost.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Receive next item, `None` means stream closed.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<C::Item, C::Error>>> {
+        let res = ready!(self
+            .reader
+            .poll_read(cx, &mut self.stream, self.max_frame_size));
+
+        Poll::Ready(res.map(|frame| self.codec.decode(frame?)))
+    }
+
+    /// Wait until previous item is written.
+    pub fn poll_send_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        if self.writer.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+
+        self.poll_send_flush(cx)
+    }
+
+    /// Begin send item. Must be called after `poll_send_ready` returned `Ready(Ok(()))`.
+    pub fn start_send(&mut self, item: C::Item) -> Result<(), C::Error> {
+        let frame = self.codec.encode(item)?;
+
+        self.writer.start(&frame, self.max_frame_size)?;
+
+        Ok(())
+    }
+
+    /// Wait until item is written and stream flushed.
+    pub fn poll_send_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        ready!(self.writer.poll_write(cx, &mut self.stream))?;
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flush and close stream.
+    pub fn poll_send_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        ready!(self.poll_send_flush(cx))?;
+
+        ready!(Pin::new(&mut self.stream).poll_close(cx)).map_err(|e| C::Error::from(e.into()))?;
+
+        Poll::Ready(Ok(()))
+    }
+
+    pub fn recv(&mut self) -> RecvItemFuture<'_, S, C> {
+        RecvItemFuture { framed: self }
+    }
+
+    pub fn send(&mut self, item: C::Item) -> SendItemFuture<'_, S, C> {
+        SendItemFuture {
+            framed: self,
+            item: Some(item),
+        }
+    }
+
+    pub fn close(&mut self) -> CloseFramedFuture<'_, S, C> {
+        CloseFramedFuture { framed: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures_lite::{future::block_on, io::Cursor};
+
+    use crate::{BytesCodec, Codec, FrameError, Framed};
+
+    /// Frames are strings, encoding fails on empty ones.
+    struct StringCodec;
+
+    #[derive(Debug)]
+    enum StringError {
+        Empty,
+        Utf8,
+        Frame(FrameError),
+    }
+
+    impl From<FrameError> for StringError {
+        fn from(e: FrameError) -> Self {
+            StringError::Frame(e)
+        }
+    }
+
+    impl Codec for StringCodec {
+        type Item = String;
+
+        type Error = StringError;
+
+        fn encode(&mut self, item: String) -> Result<Bytes, StringError> {
+            if item.is_empty() {
+                return Err(StringError::Empty);
+            }
+
+            Ok(Bytes::from(item))
+        }
+
+        fn decode(&mut self, frame: Bytes) -> Result<String, StringError> {
+            String::from_utf8(frame.to_vec()).map_err(|_| StringError::Utf8)
+        }
+    }
+
+    #[test]
+    fn codec_items() {
+        block_on(async {
+            let mut framed = Framed::new(Cursor::new(Vec::new()), StringCodec);
+            framed.set_max_frame_size(8);
+
+            framed.send(String::from("hello")).await.unwrap();
+            framed.send(String::from("world")).await.unwrap();
+
+            assert!(matches!(
+                framed.send(String::new()).await,
+                Err(StringError::Empty)
+            ));
+            assert!(matches!(
+                framed.send(String::from("too large")).await,
+                Err(StringError::Frame(FrameError::ErrFrameTooLarge(9)))
+            ));
+
+            framed.close().await.unwrap();
+
+            let mut stream = framed.into_inner();
+            stream.set_position(0);
+
+            let mut framed = Framed::new(stream, StringCodec);
+
+            assert_eq!(framed.recv().await.unwrap().unwrap(), "hello");
+            assert_eq!(framed.recv().await.unwrap().unwrap(), "world");
+            assert!(framed.recv().await.is_none());
+        });
+    }
+
+    #[test]
+    fn oversized_frame_from_remote() {
+        block_on(async {
+            let mut framed = Framed::new(Cursor::new(Vec::new()), BytesCodec);
+
+            framed.send(Bytes::from(vec![0; 100])).await.unwrap();
+
+            let mut stream = framed.into_inner();
+            stream.set_position(0);
+
+            let mut framed = Framed::new(stream, BytesCodec);
+            framed.set_max_frame_size(99);
+
+            assert!(matches!(
+                framed.recv().await,
+                Some(Err(FrameError::ErrFrameTooLarge(100)))
+            ));
+        });
+    }
+}
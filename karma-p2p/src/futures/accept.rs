@@ -21,7 +21,7 @@ where
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self;
 
-        let socket = &*this.socket;
+        let socket = this.socket;
 
         Pin::new(socket).poll_accept(cx)
     }
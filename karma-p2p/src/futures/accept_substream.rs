@@ -0,0 +1,24 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{Mux, P2pStream, Substream};
+
+pub struct AcceptSubstreamFuture<'a, S> {
+    pub mux: &'a Mux<S>,
+}
+
+impl<'a, S> Future for AcceptSubstreamFuture<'a, S>
+where
+    S: P2pStream + Unpin,
+{
+    type Output = io::Result<Substream<S>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.mux.poll_accept(cx)
+    }
+}
@@ -1,5 +1,4 @@
 use std::{
-    mem,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -9,21 +8,18 @@ use futures_lite::Future;
 use crate::P2pSocket;
 
 pub struct BindFuture<T: P2pSocket> {
-    pub arg: Option<T::Addr>,
+    pub binding: T::Binding,
 }
 
 impl<T> Future for BindFuture<T>
 where
     T: P2pSocket,
-    T::Addr: Unpin,
 {
     type Output = Result<T, T::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self;
+        let this = self.get_mut();
 
-        let opt_arg = mem::replace(&mut this.arg, None);
-
-        T::poll_bind(cx, opt_arg.unwrap())
+        Pin::new(&mut this.binding).poll(cx)
     }
 }
@@ -0,0 +1,63 @@
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{Future, FutureExt};
+use serde::de::DeserializeOwned;
+
+use crate::{P2pStream, RpcClient, RpcError};
+
+pub struct CallFuture<'a, S, R> {
+    pub client: &'a RpcClient<S>,
+    pub id: Option<u64>,
+    pub error: Option<RpcError>,
+    pub delay: Option<futures_timer::Delay>,
+    pub result: PhantomData<fn() -> R>,
+}
+
+impl<'a, S, R> Future for CallFuture<'a, S, R>
+where
+    S: P2pStream + Unpin,
+    R: DeserializeOwned,
+{
+    type Output = Result<R, RpcError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let id = match (this.id, this.error.take()) {
+            (_, Some(e)) => return Poll::Ready(Err(e)),
+            (Some(id), None) => id,
+            (None, None) => return Poll::Ready(Err(RpcError::ErrClosed)),
+        };
+
+        if let Poll::Ready(res) = this.client.poll_response(cx, id) {
+            this.id = None;
+
+            let format = this.client.format();
+
+            return Poll::Ready(res.and_then(|frame| Ok(format.decode(&frame)?)));
+        }
+
+        if let Some(delay) = &mut this.delay {
+            if delay.poll(cx).is_ready() {
+                this.id = None;
+                this.client.cancel(id);
+
+                return Poll::Ready(Err(RpcError::ErrTimeout));
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<'a, S, R> Drop for CallFuture<'a, S, R> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.client.cancel(id);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{Codec, Framed, P2pStream};
+
+pub struct CloseFramedFuture<'a, S, C> {
+    pub framed: &'a mut Framed<S, C>,
+}
+
+impl<'a, S, C> Future for CloseFramedFuture<'a, S, C>
+where
+    S: P2pStream + Unpin,
+    C: Codec,
+{
+    type Output = Result<(), C::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().framed.poll_send_close(cx)
+    }
+}
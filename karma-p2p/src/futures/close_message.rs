@@ -0,0 +1,25 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::P2pMessageStream;
+
+pub struct CloseMessageFuture<'a, T: P2pMessageStream + ?Sized> {
+    pub stream: &'a mut T,
+}
+
+impl<'a, T> Future for CloseMessageFuture<'a, T>
+where
+    T: P2pMessageStream + Unpin + ?Sized,
+{
+    type Output = Result<(), T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        Pin::new(&mut *this.stream).poll_send_close(cx)
+    }
+}
@@ -0,0 +1,24 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{Mux, P2pStream};
+
+pub struct CloseMuxFuture<'a, S> {
+    pub mux: &'a Mux<S>,
+}
+
+impl<'a, S> Future for CloseMuxFuture<'a, S>
+where
+    S: P2pStream + Unpin,
+{
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.mux.poll_close(cx)
+    }
+}
@@ -0,0 +1,23 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{P2pStream, RpcClient, RpcError};
+
+pub struct CloseRpcFuture<'a, S> {
+    pub client: &'a RpcClient<S>,
+}
+
+impl<'a, S> Future for CloseRpcFuture<'a, S>
+where
+    S: P2pStream + Unpin,
+{
+    type Output = Result<(), RpcError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.client.poll_close(cx)
+    }
+}
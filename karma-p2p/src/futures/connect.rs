@@ -1,5 +1,4 @@
 use std::{
-    mem,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -8,25 +7,19 @@ use futures_lite::Future;
 
 use crate::P2pSocket;
 
-pub struct ConnectFuture<'a, T: P2pSocket> {
-    pub socket: &'a T,
-    pub label: Option<T::Addr>,
-    pub port: u16,
+pub struct ConnectFuture<T: P2pSocket> {
+    pub connecting: T::Connecting,
 }
 
-impl<'a, T> Future for ConnectFuture<'a, T>
+impl<T> Future for ConnectFuture<T>
 where
-    T: P2pSocket + Unpin,
-    T::Addr: Unpin,
+    T: P2pSocket,
 {
     type Output = Result<T::Stream, T::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self;
+        let this = self.get_mut();
 
-        let opt_label = mem::replace(&mut this.label, None);
-        let socket = &*this.socket;
-
-        Pin::new(socket).poll_connect(cx, opt_label.unwrap(), this.port)
+        Pin::new(&mut this.connecting).poll(cx)
     }
 }
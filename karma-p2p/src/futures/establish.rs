@@ -0,0 +1,31 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{EstablishError, EstablishState, P2pSocket, Signaling};
+
+pub struct EstablishFuture<'a, S: P2pSocket, G> {
+    pub socket: &'a mut S,
+    pub signaling: &'a mut G,
+    pub remote: &'a str,
+    pub state: EstablishState<S>,
+}
+
+impl<'a, S, G> Future for EstablishFuture<'a, S, G>
+where
+    S: P2pSocket + Unpin,
+    S::Addr: Clone + Unpin,
+    G: Signaling<S::Addr> + Unpin,
+{
+    type Output = Result<(), EstablishError<S::Error, G::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        this.state
+            .poll_establish(cx, this.socket, this.signaling, this.remote)
+    }
+}
@@ -0,0 +1,23 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Stream;
+
+use crate::{P2pSocketEvents, SocketEvent};
+
+pub struct EventsStream<'a, T: ?Sized> {
+    pub socket: &'a T,
+}
+
+impl<'a, T> Stream for EventsStream<'a, T>
+where
+    T: P2pSocketEvents + Unpin + ?Sized,
+{
+    type Item = SocketEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(self.socket).poll_event(cx)
+    }
+}
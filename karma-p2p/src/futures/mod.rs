@@ -4,6 +4,9 @@ pub use bind::*;
 mod connect;
 pub use connect::*;
 
+mod accept;
+pub use accept::*;
+
 mod start;
 pub use start::*;
 
@@ -12,3 +15,69 @@ pub use fetch_local_addr::*;
 
 mod set_remote_addr;
 pub use set_remote_addr::*;
+
+mod events;
+pub use events::*;
+
+mod next_state;
+pub use next_state::*;
+
+mod send_frame;
+pub use send_frame::*;
+
+mod recv_frame;
+pub use recv_frame::*;
+
+mod send_item;
+pub use send_item::*;
+
+mod recv_item;
+pub use recv_item::*;
+
+mod close_framed;
+pub use close_framed::*;
+
+mod recv_message;
+pub use recv_message::*;
+
+mod send_message;
+pub use send_message::*;
+
+mod close_message;
+pub use close_message::*;
+
+mod establish;
+pub use establish::*;
+
+mod next_event;
+pub use next_event::*;
+
+mod negotiate;
+pub use negotiate::*;
+
+mod route;
+pub use route::*;
+
+mod serve_router;
+pub use serve_router::*;
+
+mod accept_substream;
+pub use accept_substream::*;
+
+mod close_mux;
+pub use close_mux::*;
+
+#[cfg(feature = "rpc")]
+mod call;
+#[cfg(feature = "rpc")]
+pub use call::*;
+
+#[cfg(feature = "rpc")]
+mod close_rpc;
+#[cfg(feature = "rpc")]
+pub use close_rpc::*;
+
+#[cfg(feature = "rpc")]
+mod serve;
+#[cfg(feature = "rpc")]
+pub use serve::*;
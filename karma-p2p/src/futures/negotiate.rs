@@ -0,0 +1,32 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{ready, Future};
+
+use crate::{NegotiateError, NegotiateState, Negotiated, P2pStream};
+
+pub struct NegotiateFuture<S> {
+    pub stream: Option<S>,
+    pub state: NegotiateState,
+}
+
+impl<S> Future for NegotiateFuture<S>
+where
+    S: P2pStream + Unpin,
+{
+    type Output = Result<Negotiated<S>, NegotiateError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let stream = this.stream.as_mut().expect("polled after completion");
+
+        let protocol = ready!(this.state.poll_negotiate(cx, stream));
+
+        let stream = this.stream.take().expect("checked is some");
+
+        Poll::Ready(protocol.map(|p| Negotiated::new(stream, p)))
+    }
+}
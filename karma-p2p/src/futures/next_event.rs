@@ -0,0 +1,24 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{Node, NodeErrorOf, NodeEvent, NodeTransport, P2pSocket};
+
+pub struct NextEventFuture<'a, T: NodeTransport> {
+    pub node: &'a mut Node<T>,
+}
+
+impl<'a, T> Future for NextEventFuture<'a, T>
+where
+    T: NodeTransport + Unpin,
+    <T::Socket as P2pSocket>::Addr: Clone,
+{
+    type Output = NodeEvent<NodeErrorOf<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.get_mut().node.poll_next_event(cx)
+    }
+}
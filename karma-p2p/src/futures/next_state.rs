@@ -0,0 +1,26 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{P2pSocketEvents, SocketEvent};
+
+pub struct NextStateFuture<'a, T: ?Sized> {
+    pub socket: &'a T,
+    pub seen: Option<SocketEvent>,
+}
+
+impl<'a, T> Future for NextStateFuture<'a, T>
+where
+    T: P2pSocketEvents + Unpin + ?Sized,
+{
+    type Output = Option<SocketEvent>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        Pin::new(this.socket).poll_state(cx, this.seen.as_ref())
+    }
+}
@@ -0,0 +1,28 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::Future;
+
+use crate::{FrameError, FrameReader, P2pStream};
+
+pub struct RecvFrameFuture<'a, T: P2pStream + ?Sized> {
+    pub stream: &'a mut T,
+    pub max_size: usize,
+    pub reader: FrameReader,
+}
+
+impl<'a, T> Future for RecvFrameFuture<'a, T>
+where
+    T: P2pStream + Unpin + ?Sized,
+{
+    type Output = Option<Result<Bytes, FrameError>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        this.reader.poll_read(cx, this.stream, this.max_size)
+    }
+}
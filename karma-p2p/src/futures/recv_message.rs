@@ -0,0 +1,26 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::Future;
+
+use crate::P2pMessageStream;
+
+pub struct RecvMessageFuture<'a, T: P2pMessageStream + ?Sized> {
+    pub stream: &'a mut T,
+}
+
+impl<'a, T> Future for RecvMessageFuture<'a, T>
+where
+    T: P2pMessageStream + Unpin + ?Sized,
+{
+    type Output = Option<Result<Bytes, T::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        Pin::new(&mut *this.stream).poll_recv(cx)
+    }
+}
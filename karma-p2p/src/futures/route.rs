@@ -0,0 +1,26 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{NegotiateError, P2pStream, RouteState, Router};
+
+pub struct RouteFuture<'a, S> {
+    pub router: &'a Router<S>,
+    pub state: RouteState<S>,
+}
+
+impl<'a, S> Future for RouteFuture<'a, S>
+where
+    S: P2pStream + Unpin,
+{
+    type Output = Result<(), NegotiateError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        this.router.poll_route(cx, &mut this.state)
+    }
+}
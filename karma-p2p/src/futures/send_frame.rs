@@ -0,0 +1,33 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::Future;
+
+use crate::{FrameError, FrameWriter, P2pStream};
+
+pub struct SendFrameFuture<'a, T: P2pStream + ?Sized> {
+    pub stream: &'a mut T,
+    pub frame: Option<Bytes>,
+    pub max_size: usize,
+    pub writer: FrameWriter,
+}
+
+impl<'a, T> Future for SendFrameFuture<'a, T>
+where
+    T: P2pStream + Unpin + ?Sized,
+{
+    type Output = Result<(), FrameError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Some(frame) = this.frame.take() {
+            this.writer.start(&frame, this.max_size)?;
+        }
+
+        this.writer.poll_write(cx, this.stream)
+    }
+}
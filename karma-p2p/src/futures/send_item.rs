@@ -0,0 +1,36 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{ready, Future};
+
+use crate::{Codec, Framed, P2pStream};
+
+pub struct SendItemFuture<'a, S, C: Codec> {
+    pub framed: &'a mut Framed<S, C>,
+    pub item: Option<C::Item>,
+}
+
+impl<'a, S, C> Future for SendItemFuture<'a, S, C>
+where
+    S: P2pStream + Unpin,
+    C: Codec,
+    C::Item: Unpin,
+{
+    type Output = Result<(), C::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.item.is_some() {
+            ready!(this.framed.poll_send_ready(cx))?;
+
+            if let Some(item) = this.item.take() {
+                this.framed.start_send(item)?;
+            }
+        }
+
+        this.framed.poll_send_flush(cx)
+    }
+}
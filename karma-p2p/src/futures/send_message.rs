@@ -0,0 +1,35 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::{ready, Future};
+
+use crate::P2pMessageStream;
+
+pub struct SendMessageFuture<'a, T: P2pMessageStream + ?Sized> {
+    pub stream: &'a mut T,
+    pub message: Option<Bytes>,
+}
+
+impl<'a, T> Future for SendMessageFuture<'a, T>
+where
+    T: P2pMessageStream + Unpin + ?Sized,
+{
+    type Output = Result<(), T::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.message.is_some() {
+            ready!(Pin::new(&mut *this.stream).poll_send_ready(cx))?;
+
+            if let Some(message) = this.message.take() {
+                Pin::new(&mut *this.stream).start_send(message)?;
+            }
+        }
+
+        Pin::new(&mut *this.stream).poll_send_flush(cx)
+    }
+}
@@ -0,0 +1,26 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{P2pStream, RpcError, RpcServer, ServeState};
+
+pub struct ServeFuture<'a, S> {
+    pub server: &'a RpcServer,
+    pub state: ServeState<S>,
+}
+
+impl<'a, S> Future for ServeFuture<'a, S>
+where
+    S: P2pStream + Unpin,
+{
+    type Output = Result<(), RpcError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        this.server.poll_serve(cx, &mut this.state)
+    }
+}
@@ -0,0 +1,29 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::Future;
+
+use crate::{P2pSocket, RouteState, Router};
+
+pub struct ServeRouterFuture<'a, P: P2pSocket> {
+    pub router: &'a Router<P::Stream>,
+    pub socket: &'a P,
+    pub routes: Vec<RouteState<P::Stream>>,
+}
+
+impl<'a, P> Future for ServeRouterFuture<'a, P>
+where
+    P: P2pSocket + Unpin,
+    P::Stream: Unpin,
+{
+    type Output = P::Error;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        this.router
+            .poll_serve(cx, Pin::new(this.socket), &mut this.routes)
+    }
+}
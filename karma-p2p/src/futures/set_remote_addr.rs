@@ -1,5 +1,4 @@
 use std::{
-    mem,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -8,24 +7,19 @@ use futures_lite::Future;
 
 use crate::P2pSocket;
 
-pub struct SetRemoteAddr<'a, T: P2pSocket> {
-    pub socket: &'a T,
-    pub remote: Option<T::Addr>,
+pub struct SetRemoteAddr<T: P2pSocket> {
+    pub setting: T::SettingRemoteAddr,
 }
 
-impl<'a, T> Future for SetRemoteAddr<'a, T>
+impl<T> Future for SetRemoteAddr<T>
 where
-    T: P2pSocket + Unpin,
-    T::Addr: Unpin,
+    T: P2pSocket,
 {
     type Output = Result<(), T::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self;
+        let this = self.get_mut();
 
-        let opt_remote = mem::replace(&mut this.remote, None);
-        let socket = &*this.socket;
-
-        Pin::new(socket).poll_set_remote_addr(cx, opt_remote.unwrap())
+        Pin::new(&mut this.setting).poll(cx)
     }
 }
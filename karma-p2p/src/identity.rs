@@ -0,0 +1,165 @@
+use std::{fmt, str::FromStr};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand_core::OsRng;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdentityError {
+    ErrInvalidPeerId,
+    ErrInvalidSignature,
+}
+
+/// Ed25519 keypair identifying local peer.
+#[derive(Clone)]
+pub struct Keypair {
+    key: SigningKey,
+}
+
+impl Keypair {
+    pub fn generate() -> Self {
+        Self {
+            key: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    /// Create keypair from 32 bytes secret key.
+    pub fn from_bytes(secret: &[u8; 32]) -> Self {
+        Self {
+            key: SigningKey::from_bytes(secret),
+        }
+    }
+
+    /// Get 32 bytes secret key.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.key.to_bytes()
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        PeerId {
+            key: self.key.verifying_key(),
+        }
+    }
+
+    /// Sign `message`, returns 64 bytes signature.
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        self.key.sign(message).to_bytes()
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keypair")
+            .field("peer_id", &self.peer_id())
+            .finish()
+    }
+}
+
+/// Public key of peer, printed as base58.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerId {
+    key: VerifyingKey,
+}
+
+impl PeerId {
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, IdentityError> {
+        let key = VerifyingKey::from_bytes(bytes).map_err(|_| IdentityError::ErrInvalidPeerId)?;
+
+        Ok(Self { key })
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        self.key.as_bytes()
+    }
+
+    /// Check `signature` of `message` is made by this peer.
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), IdentityError> {
+        let signature =
+            Signature::from_slice(signature).map_err(|_| IdentityError::ErrInvalidSignature)?;
+
+        self.key
+            .verify(message, &signature)
+            .map_err(|_| IdentityError::ErrInvalidSignature)
+    }
+}
+
+impl PartialOrd for PeerId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PeerId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&bs58::encode(self.as_bytes()).into_string())
+    }
+}
+
+impl fmt::Debug for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PeerId({})", self)
+    }
+}
+
+impl FromStr for PeerId {
+    type Err = IdentityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; 32];
+
+        let len = bs58::decode(s)
+            .onto(&mut bytes)
+            .map_err(|_| IdentityError::ErrInvalidPeerId)?;
+
+        if len != bytes.len() {
+            return Err(IdentityError::ErrInvalidPeerId);
+        }
+
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_id_round_trip() {
+        let keypair = Keypair::generate();
+        let id = keypair.peer_id();
+
+        let parsed: PeerId = id.to_string().parse().unwrap();
+        assert_eq!(parsed, id);
+
+        let restored = Keypair::from_bytes(&keypair.to_bytes());
+        assert_eq!(restored.peer_id(), id);
+
+        assert_eq!(
+            "not-base58!".parse::<PeerId>(),
+            Err(IdentityError::ErrInvalidPeerId)
+        );
+        assert_eq!(
+            "abc".parse::<PeerId>(),
+            Err(IdentityError::ErrInvalidPeerId)
+        );
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let keypair = Keypair::generate();
+        let id = keypair.peer_id();
+
+        let signature = keypair.sign(b"hello");
+        assert!(id.verify(b"hello", &signature).is_ok());
+        assert!(id.verify(b"hellO", &signature).is_err());
+
+        let other = Keypair::generate().peer_id();
+        assert!(other.verify(b"hello", &signature).is_err());
+        assert!(id.verify(b"hello", &signature[..10]).is_err());
+    }
+}
@@ -4,13 +4,85 @@ pub use socket::*;
 mod socket_ext;
 pub use socket_ext::*;
 
+mod event;
+pub use event::*;
+
+mod reconnect;
+pub use reconnect::*;
+
 mod stream;
 pub use stream::*;
 
 mod stream_ext;
 pub use stream_ext::*;
 
+mod boxed;
+pub use boxed::*;
+
+mod frame;
+pub use frame::*;
+
+mod codec;
+pub use codec::*;
+
+mod framed;
+pub use framed::*;
+
+#[cfg(any(feature = "json", feature = "cbor", feature = "bincode"))]
+mod typed;
+#[cfg(any(feature = "json", feature = "cbor", feature = "bincode"))]
+pub use typed::*;
+
+#[cfg(feature = "rpc")]
+mod rpc;
+#[cfg(feature = "rpc")]
+pub use rpc::*;
+
+mod message_stream;
+pub use message_stream::*;
+
+mod message_stream_ext;
+pub use message_stream_ext::*;
+
+mod mux;
+pub use mux::*;
+
+mod negotiate;
+pub use negotiate::*;
+
+mod router;
+pub use router::*;
+
+mod signaling;
+pub use signaling::*;
+
+mod establish;
+pub use establish::*;
+
+mod identity;
+pub use identity::*;
+
+mod discovery;
+pub use discovery::*;
+
+mod transport;
+pub use transport::*;
+
+mod node;
+pub use node::*;
+
 mod config;
 pub use config::*;
 
 pub mod futures;
+
+mod task_set;
+pub use task_set::*;
+
+mod waker_set;
+
+#[cfg(test)]
+mod pipe;
+
+#[cfg(feature = "testkit")]
+pub mod testkit;
@@ -0,0 +1,32 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+
+/// Message oriented p2p stream, every message is delivered as a whole.
+pub trait P2pMessageStream {
+    type Error;
+
+    /// Receive next message, `None` means stream closed.
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>>;
+
+    /// Wait until stream can accept next message.
+    fn poll_send_ready(self: Pin<&mut Self>, cx: &mut Context<'_>)
+        -> Poll<Result<(), Self::Error>>;
+
+    /// Begin send message. Must be called after `poll_send_ready` returned `Ready(Ok(()))`.
+    fn start_send(self: Pin<&mut Self>, message: Bytes) -> Result<(), Self::Error>;
+
+    /// Wait until all message sent.
+    fn poll_send_flush(self: Pin<&mut Self>, cx: &mut Context<'_>)
+        -> Poll<Result<(), Self::Error>>;
+
+    /// Flush and close stream.
+    fn poll_send_close(self: Pin<&mut Self>, cx: &mut Context<'_>)
+        -> Poll<Result<(), Self::Error>>;
+}
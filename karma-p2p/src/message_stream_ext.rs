@@ -0,0 +1,25 @@
+use bytes::Bytes;
+
+use crate::{
+    futures::{CloseMessageFuture, RecvMessageFuture, SendMessageFuture},
+    P2pMessageStream,
+};
+
+pub trait P2pMessageStreamExt: P2pMessageStream {
+    fn recv_message(&mut self) -> RecvMessageFuture<'_, Self> {
+        RecvMessageFuture { stream: self }
+    }
+
+    fn send_message(&mut self, message: Bytes) -> SendMessageFuture<'_, Self> {
+        SendMessageFuture {
+            stream: self,
+            message: Some(message),
+        }
+    }
+
+    fn close_message(&mut self) -> CloseMessageFuture<'_, Self> {
+        CloseMessageFuture { stream: self }
+    }
+}
+
+impl<T: P2pMessageStream> P2pMessageStreamExt for T {}
@@ -0,0 +1,828 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use bytes::{Buf, Bytes};
+use futures_lite::{ready, AsyncRead, AsyncWrite};
+
+use crate::{
+    futures::{AcceptSubstreamFuture, CloseMuxFuture},
+    waker_set::WakerSet,
+    P2pStream,
+};
+
+/// Receive window of each substream, also the largest data frame accepted.
+pub const MUX_WINDOW: u32 = 256 * 1024;
+
+/// Substreams opened by remote and not accepted yet, more are reset.
+const MAX_BACKLOG: usize = 256;
+
+/// Largest data frame sent.
+const MAX_FRAME_DATA: usize = 16 * 1024;
+
+/// Writes are paused while this many bytes wait to be written to underlying stream.
+const MAX_PENDING_OUT: usize = 64 * 1024;
+
+const HEADER_LEN: usize = 12;
+
+const TYPE_DATA: u8 = 0;
+const TYPE_WINDOW_UPDATE: u8 = 1;
+const TYPE_PING: u8 = 2;
+const TYPE_GO_AWAY: u8 = 3;
+
+const FLAG_SYN: u16 = 1;
+const FLAG_ACK: u16 = 2;
+const FLAG_FIN: u16 = 4;
+const FLAG_RST: u16 = 8;
+
+/// Yamux frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    ty: u8,
+    flags: u16,
+    id: u32,
+    len: u32,
+}
+
+impl Header {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(0);
+        out.push(self.ty);
+        out.extend_from_slice(&self.flags.to_be_bytes());
+        out.extend_from_slice(&self.id.to_be_bytes());
+        out.extend_from_slice(&self.len.to_be_bytes());
+    }
+
+    fn decode(buf: &[u8; HEADER_LEN]) -> io::Result<Self> {
+        if buf[0] != 0 {
+            return Err(protocol_error("unsupported mux version"));
+        }
+
+        Ok(Self {
+            ty: buf[1],
+            flags: u16::from_be_bytes([buf[2], buf[3]]),
+            id: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            len: u32::from_be_bytes([buf[8], buf[9], buf[10], buf[11]]),
+        })
+    }
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+struct StreamState {
+    recv: VecDeque<Bytes>,
+    // Bytes remote may still send, and bytes read since last window update.
+    recv_window: u32,
+    consumed: u32,
+    send_window: u32,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+    local_closed: bool,
+    remote_closed: bool,
+    reset: bool,
+}
+
+impl StreamState {
+    fn new() -> Self {
+        Self {
+            recv: VecDeque::new(),
+            recv_window: MUX_WINDOW,
+            consumed: 0,
+            send_window: MUX_WINDOW,
+            read_waker: None,
+            write_waker: None,
+            local_closed: false,
+            remote_closed: false,
+            reset: false,
+        }
+    }
+
+    fn wake(&mut self) {
+        if let Some(w) = self.read_waker.take() {
+            w.wake();
+        }
+
+        if let Some(w) = self.write_waker.take() {
+            w.wake();
+        }
+    }
+}
+
+enum ReadState {
+    Header([u8; HEADER_LEN], usize),
+    Body(Header, Vec<u8>, usize),
+}
+
+struct Inner<S> {
+    io: S,
+    next_id: u32,
+    streams: HashMap<u32, StreamState>,
+    backlog: VecDeque<u32>,
+    accept_waker: Option<Waker>,
+    read: ReadState,
+    out: Vec<u8>,
+    out_pos: usize,
+    go_away_sent: bool,
+    remote_gone: bool,
+    // Set once underlying stream fails or reaches end.
+    error: Option<io::ErrorKind>,
+}
+
+impl<S: P2pStream + Unpin> Inner<S> {
+    fn push_data(&mut self, id: u32, data: &[u8]) {
+        let header = Header {
+            ty: TYPE_DATA,
+            flags: 0,
+            id,
+            len: data.len() as u32,
+        };
+
+        header.encode(&mut self.out);
+        self.out.extend_from_slice(data);
+    }
+
+    /// Queue frame without body, `value` is window delta, ping value or go away code.
+    fn push_control(&mut self, ty: u8, flags: u16, id: u32, value: u32) {
+        let header = Header {
+            ty,
+            flags,
+            id,
+            len: value,
+        };
+
+        header.encode(&mut self.out);
+    }
+
+    fn pending_out(&self) -> usize {
+        self.out.len() - self.out_pos
+    }
+
+    fn check(&self) -> io::Result<()> {
+        match self.error {
+            Some(kind) => Err(kind.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn fail(&mut self, kind: io::ErrorKind) {
+        self.error = Some(kind);
+
+        for stream in self.streams.values_mut() {
+            stream.wake();
+        }
+
+        if let Some(w) = self.accept_waker.take() {
+            w.wake();
+        }
+    }
+
+    /// Flush queued frames and dispatch received ones until underlying stream blocks.
+    ///
+    /// Caller's waker is registered to be woken when underlying stream is ready.
+    fn drive(&mut self, cx: &mut Context<'_>, wakers: &Arc<WakerSet>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        wakers.register(cx.waker());
+
+        let waker = Waker::from(wakers.clone());
+        let mut io_cx = Context::from_waker(&waker);
+
+        if let Err(e) = self.drive_io(&mut io_cx) {
+            self.fail(e.kind());
+        }
+    }
+
+    /// Write queued frames without a task to wake, tasks waiting on underlying stream
+    /// are woken instead.
+    fn flush(&mut self, wakers: &Arc<WakerSet>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let waker = Waker::from(wakers.clone());
+        let mut io_cx = Context::from_waker(&waker);
+
+        if let Err(e) = self.poll_flush_out(&mut io_cx) {
+            self.fail(e.kind());
+        }
+    }
+
+    fn drive_io(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        loop {
+            // Stop reading while peer doesn't read what it is answered.
+            if self.poll_flush_out(cx)?.is_pending() && self.pending_out() >= MAX_PENDING_OUT {
+                return Ok(());
+            }
+
+            match self.poll_read_frame(cx)? {
+                Poll::Ready(Some((header, body))) => self.handle_frame(header, body)?,
+                Poll::Ready(None) => {
+                    self.fail(io::ErrorKind::BrokenPipe);
+                    return Ok(());
+                }
+                Poll::Pending => {
+                    let _ = self.poll_flush_out(cx)?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn poll_flush_out(&mut self, cx: &mut Context<'_>) -> io::Result<Poll<()>> {
+        while self.out_pos < self.out.len() {
+            let n = match Pin::new(&mut self.io).poll_write(cx, &self.out[self.out_pos..]) {
+                Poll::Ready(res) => res?,
+                Poll::Pending => return Ok(Poll::Pending),
+            };
+
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+
+            self.out_pos += n;
+        }
+
+        self.out.clear();
+        self.out_pos = 0;
+
+        match Pin::new(&mut self.io).poll_flush(cx) {
+            Poll::Ready(res) => res.map(Poll::Ready),
+            Poll::Pending => Ok(Poll::Pending),
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn poll_read_frame(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<Option<(Header, Vec<u8>)>>> {
+        loop {
+            match &mut self.read {
+                ReadState::Header(buf, filled) if *filled == HEADER_LEN => {
+                    let header = Header::decode(buf)?;
+
+                    self.read = ReadState::Header([0; HEADER_LEN], 0);
+
+                    if header.ty != TYPE_DATA {
+                        return Poll::Ready(Ok(Some((header, Vec::new()))));
+                    }
+
+                    if header.len > MUX_WINDOW {
+                        return Poll::Ready(Err(protocol_error("mux frame too large")));
+                    }
+
+                    let body = vec![0; header.len as usize];
+                    self.read = ReadState::Body(header, body, 0);
+                }
+                ReadState::Header(buf, filled) => {
+                    let n = ready!(Pin::new(&mut self.io).poll_read(cx, &mut buf[*filled..]))?;
+
+                    if n == 0 {
+                        if *filled == 0 {
+                            return Poll::Ready(Ok(None));
+                        }
+
+                        return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                    }
+
+                    *filled += n;
+                }
+                ReadState::Body(header, body, filled) if *filled == body.len() => {
+                    let frame = (*header, std::mem::take(body));
+
+                    self.read = ReadState::Header([0; HEADER_LEN], 0);
+
+                    return Poll::Ready(Ok(Some(frame)));
+                }
+                ReadState::Body(_, body, filled) => {
+                    let n = ready!(Pin::new(&mut self.io).poll_read(cx, &mut body[*filled..]))?;
+
+                    if n == 0 {
+                        return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                    }
+
+                    *filled += n;
+                }
+            }
+        }
+    }
+
+    fn handle_frame(&mut self, header: Header, body: Vec<u8>) -> io::Result<()> {
+        match header.ty {
+            TYPE_DATA | TYPE_WINDOW_UPDATE => {}
+            TYPE_PING => {
+                if header.flags & FLAG_SYN != 0 {
+                    self.push_control(TYPE_PING, FLAG_ACK, 0, header.len);
+                }
+
+                return Ok(());
+            }
+            TYPE_GO_AWAY => {
+                self.remote_gone = true;
+
+                if let Some(w) = self.accept_waker.take() {
+                    w.wake();
+                }
+
+                return Ok(());
+            }
+            _ => return Err(protocol_error("unknown mux frame type")),
+        }
+
+        if header.flags & FLAG_SYN != 0 && !self.incoming(header.id)? {
+            return Ok(());
+        }
+
+        // Frames of streams already dropped locally are ignored.
+        let stream = match self.streams.get_mut(&header.id) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        if header.ty == TYPE_DATA {
+            if header.len > stream.recv_window {
+                return Err(protocol_error("mux receive window exceeded"));
+            }
+
+            stream.recv_window -= header.len;
+
+            if !body.is_empty() {
+                stream.recv.push_back(Bytes::from(body));
+            }
+        } else {
+            stream.send_window = stream.send_window.saturating_add(header.len);
+        }
+
+        if header.flags & FLAG_FIN != 0 {
+            stream.remote_closed = true;
+        }
+
+        if header.flags & FLAG_RST != 0 {
+            stream.reset = true;
+        }
+
+        stream.wake();
+
+        Ok(())
+    }
+
+    /// Register stream opened by remote, `false` if it's refused.
+    fn incoming(&mut self, id: u32) -> io::Result<bool> {
+        if id == 0 || id % 2 == self.next_id % 2 || self.streams.contains_key(&id) {
+            return Err(protocol_error("invalid mux stream id"));
+        }
+
+        if self.go_away_sent || self.backlog.len() >= MAX_BACKLOG {
+            self.push_control(TYPE_WINDOW_UPDATE, FLAG_RST, id, 0);
+            return Ok(false);
+        }
+
+        self.streams.insert(id, StreamState::new());
+        self.backlog.push_back(id);
+
+        self.push_control(TYPE_WINDOW_UPDATE, FLAG_ACK, id, 0);
+
+        if let Some(w) = self.accept_waker.take() {
+            w.wake();
+        }
+
+        Ok(true)
+    }
+}
+
+struct Shared<S> {
+    inner: Mutex<Inner<S>>,
+    wakers: Arc<WakerSet>,
+}
+
+impl<S> Shared<S> {
+    fn lock(&self) -> MutexGuard<'_, Inner<S>> {
+        self.inner.lock().unwrap()
+    }
+}
+
+/// Yamux style multiplexer of logical substreams over one `P2pStream`.
+///
+/// Each substream has its own flow control window, so a substream not being read
+/// doesn't block others. There's no background task, underlying stream is driven by
+/// whichever substream or `accept` is polled, so keep polling `accept` to receive
+/// substreams opened by remote.
+pub struct Mux<S> {
+    shared: Arc<Shared<S>>,
+}
+
+impl<S> Clone for Mux<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S: P2pStream + Unpin> Mux<S> {
+    /// Multiplex `io`, the `initiator` side opens odd substream ids and the other
+    /// side even ones.
+    pub fn new(io: S, initiator: bool) -> Self {
+        let inner = Inner {
+            io,
+            next_id: if initiator { 1 } else { 2 },
+            streams: HashMap::new(),
+            backlog: VecDeque::new(),
+            accept_waker: None,
+            read: ReadState::Header([0; HEADER_LEN], 0),
+            out: Vec::new(),
+            out_pos: 0,
+            go_away_sent: false,
+            remote_gone: false,
+            error: None,
+        };
+
+        Self {
+            shared: Arc::new(Shared {
+                inner: Mutex::new(inner),
+                wakers: Arc::new(WakerSet::default()),
+            }),
+        }
+    }
+
+    /// Open substream, remote gets it from `accept`.
+    ///
+    /// Remote is told when substream or mux is polled next, writes can start at once.
+    pub fn open(&self) -> io::Result<Substream<S>> {
+        let mut inner = self.shared.lock();
+
+        inner.check()?;
+
+        if inner.remote_gone || inner.go_away_sent {
+            return Err(io::ErrorKind::ConnectionAborted.into());
+        }
+
+        let id = inner.next_id;
+
+        inner.next_id = id
+            .checked_add(2)
+            .ok_or_else(|| io::Error::other("mux stream ids exhausted"))?;
+
+        inner.streams.insert(id, StreamState::new());
+        inner.push_control(TYPE_WINDOW_UPDATE, FLAG_SYN, id, 0);
+        inner.flush(&self.shared.wakers);
+
+        Ok(Substream {
+            id,
+            shared: self.shared.clone(),
+        })
+    }
+
+    /// Accept substream opened by remote.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<Substream<S>>> {
+        let mut inner = self.shared.lock();
+
+        inner.drive(cx, &self.shared.wakers);
+
+        if let Some(id) = inner.backlog.pop_front() {
+            return Poll::Ready(Ok(Substream {
+                id,
+                shared: self.shared.clone(),
+            }));
+        }
+
+        if inner.remote_gone {
+            return Poll::Ready(Err(io::ErrorKind::ConnectionAborted.into()));
+        }
+
+        inner.check()?;
+
+        inner.accept_waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+
+    pub fn accept(&self) -> AcceptSubstreamFuture<'_, S> {
+        AcceptSubstreamFuture { mux: self }
+    }
+
+    /// Tell remote no more substreams are accepted and close underlying stream.
+    ///
+    /// Open substreams fail afterwards.
+    pub fn poll_close(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut inner = self.shared.lock();
+
+        if !inner.go_away_sent {
+            inner.go_away_sent = true;
+            inner.push_control(TYPE_GO_AWAY, 0, 0, 0);
+        }
+
+        inner.check()?;
+
+        let waker = Waker::from(self.shared.wakers.clone());
+        self.shared.wakers.register(cx.waker());
+
+        let mut io_cx = Context::from_waker(&waker);
+
+        if inner.poll_flush_out(&mut io_cx)?.is_pending() {
+            return Poll::Pending;
+        }
+
+        ready!(Pin::new(&mut inner.io).poll_close(&mut io_cx))?;
+
+        inner.fail(io::ErrorKind::ConnectionAborted);
+
+        Poll::Ready(Ok(()))
+    }
+
+    pub fn close(&self) -> CloseMuxFuture<'_, S> {
+        CloseMuxFuture { mux: self }
+    }
+}
+
+/// Logical stream of `Mux`.
+pub struct Substream<S: P2pStream + Unpin> {
+    id: u32,
+    shared: Arc<Shared<S>>,
+}
+
+impl<S: P2pStream + Unpin> Substream<S> {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl<S: P2pStream + Unpin> AsyncRead for Substream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut inner = self.shared.lock();
+
+        inner.drive(cx, &self.shared.wakers);
+
+        let error = inner.error;
+        let stream = inner
+            .streams
+            .get_mut(&self.id)
+            .expect("stream of live handle");
+
+        let front = match stream.recv.front_mut() {
+            Some(front) => front,
+            None if stream.reset => return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into())),
+            None if stream.remote_closed => return Poll::Ready(Ok(0)),
+            None => {
+                if let Some(kind) = error {
+                    return Poll::Ready(Err(kind.into()));
+                }
+
+                stream.read_waker = Some(cx.waker().clone());
+
+                return Poll::Pending;
+            }
+        };
+
+        let n = front.len().min(buf.len());
+
+        buf[..n].copy_from_slice(&front[..n]);
+        front.advance(n);
+
+        if front.is_empty() {
+            stream.recv.pop_front();
+        }
+
+        stream.consumed += n as u32;
+
+        // Grant remote window back once half of it is read.
+        if stream.consumed >= MUX_WINDOW / 2 && !stream.remote_closed {
+            let delta = std::mem::take(&mut stream.consumed);
+
+            stream.recv_window += delta;
+
+            inner.push_control(TYPE_WINDOW_UPDATE, 0, self.id, delta);
+            inner.drive(cx, &self.shared.wakers);
+        }
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<S: P2pStream + Unpin> AsyncWrite for Substream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut inner = self.shared.lock();
+
+        inner.drive(cx, &self.shared.wakers);
+        inner.check()?;
+
+        // Waker is registered by drive, underlying stream wakes it once written.
+        if inner.pending_out() >= MAX_PENDING_OUT {
+            return Poll::Pending;
+        }
+
+        let stream = inner
+            .streams
+            .get_mut(&self.id)
+            .expect("stream of live handle");
+
+        if stream.reset {
+            return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+        }
+
+        if stream.local_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        if stream.send_window == 0 {
+            stream.write_waker = Some(cx.waker().clone());
+
+            return Poll::Pending;
+        }
+
+        let n = buf
+            .len()
+            .min(stream.send_window as usize)
+            .min(MAX_FRAME_DATA);
+
+        stream.send_window -= n as u32;
+
+        inner.push_data(self.id, &buf[..n]);
+        inner.drive(cx, &self.shared.wakers);
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut inner = self.shared.lock();
+
+        inner.drive(cx, &self.shared.wakers);
+        inner.check()?;
+
+        if inner.pending_out() > 0 {
+            return Poll::Pending;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        {
+            let mut inner = self.shared.lock();
+
+            let stream = inner
+                .streams
+                .get_mut(&self.id)
+                .expect("stream of live handle");
+
+            if !stream.local_closed && !stream.reset {
+                stream.local_closed = true;
+
+                inner.push_control(TYPE_WINDOW_UPDATE, FLAG_FIN, self.id, 0);
+            }
+        }
+
+        self.poll_flush(cx)
+    }
+}
+
+impl<S: P2pStream + Unpin> Drop for Substream<S> {
+    fn drop(&mut self) {
+        let mut inner = self.shared.lock();
+
+        let stream = match inner.streams.remove(&self.id) {
+            Some(s) => s,
+            None => return,
+        };
+
+        let finished = stream.local_closed && stream.remote_closed;
+
+        if !stream.reset && !finished {
+            inner.push_control(TYPE_WINDOW_UPDATE, FLAG_RST, self.id, 0);
+            inner.flush(&self.shared.wakers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use futures_lite::{
+        future::{self, block_on},
+        AsyncReadExt, AsyncWriteExt,
+    };
+
+    use super::{Mux, MUX_WINDOW};
+    use crate::pipe::{pipe, Pipe};
+
+    fn mux_pair() -> (Mux<Pipe>, Mux<Pipe>) {
+        let (a, b) = pipe();
+
+        (Mux::new(a, true), Mux::new(b, false))
+    }
+
+    #[test]
+    fn open_accept_round_trip() {
+        block_on(async {
+            let (a, b) = mux_pair();
+
+            let mut s1 = a.open().unwrap();
+            let mut s2 = b.open().unwrap();
+
+            assert_eq!(s1.id() % 2, 1);
+            assert_eq!(s2.id() % 2, 0);
+
+            s1.write_all(b"hello").await.unwrap();
+            s2.write_all(b"world").await.unwrap();
+            s1.close().await.unwrap();
+
+            let mut r1 = b.accept().await.unwrap();
+            let mut r2 = a.accept().await.unwrap();
+
+            assert_eq!(r1.id(), s1.id());
+
+            let mut buf = Vec::new();
+            r1.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, b"hello");
+
+            let mut buf = [0; 5];
+            r2.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"world");
+
+            r1.write_all(b"bye").await.unwrap();
+            r1.close().await.unwrap();
+
+            let mut buf = Vec::new();
+            s1.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, b"bye");
+        });
+    }
+
+    #[test]
+    fn per_stream_flow_control() {
+        block_on(async {
+            let (a, b) = mux_pair();
+
+            let mut slow = a.open().unwrap();
+            let mut fast = a.open().unwrap();
+
+            let data = vec![7u8; MUX_WINDOW as usize * 3];
+
+            let mut write = Box::pin(slow.write_all(&data));
+
+            // Remote doesn't read, write stalls once window is used up.
+            for _ in 0..100 {
+                assert!(future::poll_once(&mut write).await.is_none());
+            }
+
+            let mut slow_remote = b.accept().await.unwrap();
+            let mut fast_remote = b.accept().await.unwrap();
+
+            fast.write_all(b"ping").await.unwrap();
+
+            let mut buf = [0; 4];
+            fast_remote.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+
+            let mut received = vec![0; data.len()];
+            let (w, r) = future::zip(write, slow_remote.read_exact(&mut received)).await;
+
+            w.unwrap();
+            r.unwrap();
+            assert_eq!(received, data);
+        });
+    }
+
+    #[test]
+    fn reset_and_close() {
+        block_on(async {
+            let (a, b) = mux_pair();
+
+            let mut s = a.open().unwrap();
+            s.write_all(b"x").await.unwrap();
+
+            let mut r = b.accept().await.unwrap();
+
+            drop(s);
+
+            let mut buf = [0; 1];
+            r.read_exact(&mut buf).await.unwrap();
+
+            let err = r.read(&mut buf).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+
+            a.close().await.unwrap();
+
+            let err = b.accept().await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+            assert!(a.open().is_err());
+        });
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures_lite::{ready, AsyncRead, AsyncWrite};
+
+use crate::{futures::NegotiateFuture, FrameError, FrameReader, FrameWriter, P2pStream};
+
+/// Header sent by both sides before protocols are proposed.
+pub const MULTISTREAM_PROTOCOL: &str = "/multistream/1.0.0";
+
+/// Largest message of negotiation, protocol ids are short.
+const MAX_MESSAGE_SIZE: usize = 1024;
+
+const NOT_AVAILABLE: &str = "na";
+
+/// Proposals listener answers `na` before giving up on dialer.
+const MAX_REJECTED: usize = 32;
+
+#[derive(Debug)]
+pub enum NegotiateError {
+    ErrInvalidHeader,
+    ErrInvalidMessage,
+    /// No protocol is supported by both sides.
+    ErrUnsupported,
+    /// Stream closed before a protocol was agreed.
+    ErrClosed,
+    FrameError(FrameError),
+}
+
+impl fmt::Display for NegotiateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NegotiateError::ErrInvalidHeader => write!(f, "invalid multistream header"),
+            NegotiateError::ErrInvalidMessage => write!(f, "invalid negotiation message"),
+            NegotiateError::ErrUnsupported => write!(f, "no protocol supported by both sides"),
+            NegotiateError::ErrClosed => write!(f, "stream closed during negotiation"),
+            NegotiateError::FrameError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NegotiateError {}
+
+impl From<FrameError> for NegotiateError {
+    fn from(e: FrameError) -> Self {
+        NegotiateError::FrameError(e)
+    }
+}
+
+enum Role {
+    /// Protocols not yet refused by listener, first one is proposed.
+    Dialer(VecDeque<String>),
+    Listener(Vec<String>),
+}
+
+/// Progress of multistream-select negotiation on a stream.
+///
+/// Each message is a frame of `<text>\n`. Both sides send `MULTISTREAM_PROTOCOL`,
+/// then dialer proposes protocols by preference until listener echoes one back,
+/// listener answers `na` to protocols it doesn't support, up to `MAX_REJECTED` of
+/// them. Frames are read exactly, so data following negotiation stays in stream.
+pub struct NegotiateState {
+    role: Role,
+    reader: FrameReader,
+    writer: FrameWriter,
+    outgoing: VecDeque<String>,
+    header_received: bool,
+    selected: Option<String>,
+    rejected: usize,
+}
+
+impl NegotiateState {
+    /// Propose `protocols`, most preferred first.
+    pub fn dialer<I, P>(protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        let protocols: VecDeque<String> = protocols.into_iter().map(Into::into).collect();
+
+        let mut state = Self::new(Role::Dialer(protocols.clone()));
+
+        // Header and first proposal are sent without waiting for listener.
+        state.outgoing.extend(protocols.front().cloned());
+
+        state
+    }
+
+    /// Accept any of `protocols`.
+    pub fn listener<I, P>(protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        Self::new(Role::Listener(
+            protocols.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    fn new(role: Role) -> Self {
+        Self {
+            role,
+            reader: FrameReader::default(),
+            writer: FrameWriter::default(),
+            outgoing: VecDeque::from([String::from(MULTISTREAM_PROTOCOL)]),
+            header_received: false,
+            selected: None,
+            rejected: 0,
+        }
+    }
+
+    /// Negotiate on `stream`, ready with agreed protocol.
+    pub fn poll_negotiate<S>(
+        &mut self,
+        cx: &mut Context<'_>,
+        stream: &mut S,
+    ) -> Poll<Result<String, NegotiateError>>
+    where
+        S: P2pStream + Unpin,
+    {
+        if matches!(&self.role, Role::Dialer(p) if p.is_empty()) {
+            return Poll::Ready(Err(NegotiateError::ErrUnsupported));
+        }
+
+        loop {
+            loop {
+                ready!(self.writer.poll_write(cx, stream))?;
+
+                match self.outgoing.pop_front() {
+                    Some(message) => self
+                        .writer
+                        .start(format!("{}\n", message).as_bytes(), MAX_MESSAGE_SIZE)?,
+                    None => break,
+                }
+            }
+
+            if let Some(protocol) = self.selected.take() {
+                return Poll::Ready(Ok(protocol));
+            }
+
+            let frame = match ready!(self.reader.poll_read(cx, stream, MAX_MESSAGE_SIZE)) {
+                Some(frame) => frame?,
+                None => return Poll::Ready(Err(NegotiateError::ErrClosed)),
+            };
+
+            let message = parse_message(frame)?;
+
+            if !self.header_received {
+                if message != MULTISTREAM_PROTOCOL {
+                    return Poll::Ready(Err(NegotiateError::ErrInvalidHeader));
+                }
+
+                self.header_received = true;
+
+                continue;
+            }
+
+            match &mut self.role {
+                Role::Dialer(protocols) => {
+                    if protocols.front() == Some(&message) {
+                        self.selected = Some(message);
+                    } else if message == NOT_AVAILABLE {
+                        protocols.pop_front();
+
+                        match protocols.front() {
+                            Some(next) => self.outgoing.push_back(next.clone()),
+                            None => return Poll::Ready(Err(NegotiateError::ErrUnsupported)),
+                        }
+                    } else {
+                        return Poll::Ready(Err(NegotiateError::ErrInvalidMessage));
+                    }
+                }
+                Role::Listener(protocols) => {
+                    if protocols.contains(&message) {
+                        self.outgoing.push_back(message.clone());
+                        self.selected = Some(message);
+                    } else if self.rejected < MAX_REJECTED {
+                        self.rejected += 1;
+                        self.outgoing.push_back(String::from(NOT_AVAILABLE));
+                    } else {
+                        return Poll::Ready(Err(NegotiateError::ErrUnsupported));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn parse_message(frame: Bytes) -> Result<String, NegotiateError> {
+    let text = frame
+        .strip_suffix(b"\n")
+        .ok_or(NegotiateError::ErrInvalidMessage)?;
+
+    String::from_utf8(text.to_vec()).map_err(|_| NegotiateError::ErrInvalidMessage)
+}
+
+/// Stream on which a protocol is agreed.
+#[derive(Debug)]
+pub struct Negotiated<S> {
+    stream: S,
+    protocol: String,
+}
+
+impl<S: P2pStream + Unpin> Negotiated<S> {
+    /// Propose `protocols` on `stream`, most preferred first.
+    pub fn dial<I, P>(stream: S, protocols: I) -> NegotiateFuture<S>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        NegotiateFuture {
+            stream: Some(stream),
+            state: NegotiateState::dialer(protocols),
+        }
+    }
+
+    /// Accept protocol proposed on `stream` from `protocols`.
+    pub fn listen<I, P>(stream: S, protocols: I) -> NegotiateFuture<S>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        NegotiateFuture {
+            stream: Some(stream),
+            state: NegotiateState::listener(protocols),
+        }
+    }
+}
+
+impl<S> Negotiated<S> {
+    /// Wrap `stream` negotiated by [`NegotiateState`].
+    pub fn new(stream: S, protocol: String) -> Self {
+        Self { stream, protocol }
+    }
+
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Negotiated<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Negotiated<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+
+    use super::{NegotiateError, Negotiated, MAX_REJECTED};
+    use crate::pipe::pipe;
+
+    #[test]
+    fn agree_on_supported() {
+        future::block_on(async {
+            let (a, b) = pipe();
+
+            let (dialer, listener) = future::zip(
+                Negotiated::dial(a, ["/echo/2.0.0", "/echo/1.0.0"]),
+                Negotiated::listen(b, ["/echo/1.0.0", "/chat/1.0.0"]),
+            )
+            .await;
+
+            let (mut dialer, mut listener) = (dialer.unwrap(), listener.unwrap());
+            assert_eq!(dialer.protocol(), "/echo/1.0.0");
+            assert_eq!(listener.protocol(), "/echo/1.0.0");
+
+            // Data written after negotiation isn't consumed by it.
+            dialer.write_all(b"ping").await.unwrap();
+
+            let mut buf = [0u8; 4];
+            listener.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"ping");
+        });
+    }
+
+    #[test]
+    fn reject_unsupported() {
+        future::block_on(async {
+            let (a, b) = pipe();
+
+            let dialer = async {
+                let res = Negotiated::dial(a, ["/echo/2.0.0"]).await;
+                assert!(matches!(res, Err(NegotiateError::ErrUnsupported)));
+            };
+
+            let listener = async {
+                let res = Negotiated::listen(b, ["/echo/1.0.0"]).await;
+                assert!(matches!(res, Err(NegotiateError::ErrClosed)));
+            };
+
+            future::zip(dialer, listener).await;
+
+            let (a, _b) = pipe();
+            let res = Negotiated::dial(a, Vec::<String>::new()).await;
+            assert!(matches!(res, Err(NegotiateError::ErrUnsupported)));
+        });
+    }
+
+    #[test]
+    fn limit_rejected_proposals() {
+        future::block_on(async {
+            let (a, b) = pipe();
+
+            let mut protocols: Vec<_> = (0..MAX_REJECTED + 1)
+                .map(|i| format!("/unknown/{}", i))
+                .collect();
+            protocols.push(String::from("/echo/1.0.0"));
+
+            // Listener stops answering before dialer reaches a supported one.
+            let (dialer, listener) = future::zip(
+                Negotiated::dial(a, protocols),
+                Negotiated::listen(b, ["/echo/1.0.0"]),
+            )
+            .await;
+
+            assert!(matches!(listener, Err(NegotiateError::ErrUnsupported)));
+            assert!(dialer.is_err());
+        });
+    }
+}
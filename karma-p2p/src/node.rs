@@ -0,0 +1,737 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    futures::NextEventFuture, Config, Discovery, EstablishError, EstablishState, NodeTransport,
+    P2pSocket, P2pSocketEvents, PeerEvent, Signaling, SocketEvent,
+};
+
+type Addr<T> = <<T as NodeTransport>::Socket as P2pSocket>::Addr;
+
+#[derive(Debug)]
+pub enum NodeError<T, S, G> {
+    TransportError(T),
+    SocketError(S),
+    SignalingError(G),
+}
+
+impl<T, S, G> From<EstablishError<S, G>> for NodeError<T, S, G> {
+    fn from(e: EstablishError<S, G>) -> Self {
+        match e {
+            EstablishError::SocketError(e) => NodeError::SocketError(e),
+            EstablishError::SignalingError(e) => NodeError::SignalingError(e),
+        }
+    }
+}
+
+pub type NodeErrorOf<T> = NodeError<
+    <T as NodeTransport>::Error,
+    <<T as NodeTransport>::Socket as P2pSocket>::Error,
+    <<T as NodeTransport>::Discovery as Signaling<Addr<T>>>::Error,
+>;
+
+/// Event of node, failures are reported as events and node keeps running.
+#[derive(Debug)]
+pub enum NodeEvent<E> {
+    BootstrapConnected(SocketAddr),
+    BootstrapFailed(SocketAddr, E),
+    BootstrapClosed(SocketAddr),
+
+    Listening(SocketAddr),
+    ListenFailed(SocketAddr, E),
+    ListenerClosed(SocketAddr),
+
+    PeerAdded(String),
+    /// Connection to peer is established, addresses of restarts are still exchanged.
+    PeerConnected(String),
+    PeerRemoved(String),
+    PeerFailed(String, E),
+    /// Event reported by socket of peer.
+    PeerEvent(String, SocketEvent),
+}
+
+struct Peer<T: NodeTransport> {
+    socket: T::Socket,
+    // Bootstrap node relaying addresses of this peer.
+    via: SocketAddr,
+    state: EstablishState<T::Socket>,
+    exchanged: bool,
+    // Socket reporting no events at all is connected once addresses are exchanged.
+    reported: bool,
+    events_done: bool,
+    connected: bool,
+}
+
+/// Node of p2p network driven by [`Config`].
+///
+/// Node contacts `bootstrap_nodes`, creates a socket to every peer found through them
+/// and keeps it until peer leaves. When `enable_forward` is set, node also listens on
+/// `forward_listen`. Node does nothing unless events are polled.
+///
+/// Addresses of a peer are exchanged as long as the peer is kept, so restarts of its
+/// socket are negotiated too. Socket events are taken by node and reported as
+/// [`NodeEvent::PeerEvent`].
+pub struct Node<T: NodeTransport> {
+    config: Config,
+    local: String,
+    transport: T,
+    dialing: Vec<SocketAddr>,
+    listening: Vec<SocketAddr>,
+    discoveries: Vec<(SocketAddr, T::Discovery)>,
+    listeners: Vec<(SocketAddr, T::Listener)>,
+    binding: Vec<(String, SocketAddr)>,
+    peers: HashMap<String, Peer<T>>,
+    events: VecDeque<NodeEvent<NodeErrorOf<T>>>,
+}
+
+impl<T: NodeTransport> Node<T> {
+    /// Create node with id `local`.
+    pub fn new(config: Config, local: &str, transport: T) -> Self {
+        let dialing = config.bootstrap_nodes.clone();
+
+        let listening = if config.enable_forward {
+            config.forward_listen.clone()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            config,
+            local: String::from(local),
+            transport,
+            dialing,
+            listening,
+            discoveries: Vec::new(),
+            listeners: Vec::new(),
+            binding: Vec::new(),
+            peers: HashMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn local_id(&self) -> &str {
+        &self.local
+    }
+
+    /// Ids of peers with live socket.
+    pub fn peers(&self) -> impl Iterator<Item = &str> {
+        self.peers.keys().map(String::as_str)
+    }
+
+    pub fn peer(&self, id: &str) -> Option<&T::Socket> {
+        self.peers.get(id).map(|p| &p.socket)
+    }
+
+    /// Check connection to peer is established.
+    pub fn is_connected(&self, id: &str) -> bool {
+        self.peers.get(id).is_some_and(|p| p.connected)
+    }
+
+    /// Take socket of peer out of node, node won't exchange its addresses anymore.
+    pub fn remove_peer(&mut self, id: &str) -> Option<T::Socket> {
+        self.peers.remove(id).map(|p| p.socket)
+    }
+
+    pub fn next_event(&mut self) -> NextEventFuture<'_, T> {
+        NextEventFuture { node: self }
+    }
+
+    fn remove_via(&mut self, via: SocketAddr) {
+        self.binding.retain(|(_, v)| *v != via);
+
+        let removed: Vec<String> = self
+            .peers
+            .iter()
+            .filter(|(_, p)| p.via == via)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in removed {
+            self.peers.remove(&id);
+            self.events.push_back(NodeEvent::PeerRemoved(id));
+        }
+    }
+}
+
+impl<T> Node<T>
+where
+    T: NodeTransport + Unpin,
+    Addr<T>: Clone,
+{
+    pub fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<NodeEvent<NodeErrorOf<T>>> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(event);
+            }
+
+            let progress = self.poll_dialing(cx)
+                | self.poll_listening(cx)
+                | self.poll_listeners(cx)
+                | self.poll_discoveries(cx)
+                | self.poll_binding(cx)
+                | self.poll_peers(cx);
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    fn poll_dialing(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+
+        while i < self.dialing.len() {
+            let addr = self.dialing[i];
+
+            match Pin::new(&mut self.transport).poll_dial(cx, &addr) {
+                Poll::Ready(Ok(discovery)) => {
+                    self.discoveries.push((addr, discovery));
+                    self.events.push_back(NodeEvent::BootstrapConnected(addr));
+                }
+                Poll::Ready(Err(e)) => {
+                    let e = NodeError::TransportError(e);
+                    self.events.push_back(NodeEvent::BootstrapFailed(addr, e));
+                }
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            self.dialing.remove(i);
+            progress = true;
+        }
+
+        progress
+    }
+
+    fn poll_listening(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+
+        while i < self.listening.len() {
+            let addr = self.listening[i];
+
+            match Pin::new(&mut self.transport).poll_listen(cx, &addr) {
+                Poll::Ready(Ok(listener)) => {
+                    self.listeners.push((addr, listener));
+                    self.events.push_back(NodeEvent::Listening(addr));
+                }
+                Poll::Ready(Err(e)) => {
+                    let e = NodeError::TransportError(e);
+                    self.events.push_back(NodeEvent::ListenFailed(addr, e));
+                }
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            self.listening.remove(i);
+            progress = true;
+        }
+
+        progress
+    }
+
+    fn poll_listeners(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+
+        while i < self.listeners.len() {
+            let (addr, listener) = &mut self.listeners[i];
+            let addr = *addr;
+
+            match Pin::new(listener).poll(cx) {
+                Poll::Ready(Ok(())) => self.events.push_back(NodeEvent::ListenerClosed(addr)),
+                Poll::Ready(Err(e)) => {
+                    let e = NodeError::TransportError(e);
+                    self.events.push_back(NodeEvent::ListenFailed(addr, e));
+                }
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            drop(self.listeners.remove(i));
+            progress = true;
+        }
+
+        progress
+    }
+
+    fn poll_discoveries(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+
+        while i < self.discoveries.len() {
+            let (addr, discovery) = &mut self.discoveries[i];
+            let addr = *addr;
+
+            match Pin::new(discovery).poll_next_peer(cx) {
+                Poll::Ready(Ok(Some(PeerEvent::Joined(peer)))) => {
+                    let known = peer == self.local
+                        || self.peers.contains_key(&peer)
+                        || self.binding.iter().any(|(p, _)| *p == peer);
+
+                    if !known {
+                        self.binding.push((peer, addr));
+                    }
+
+                    progress = true;
+                    continue;
+                }
+                Poll::Ready(Ok(Some(PeerEvent::Left(peer)))) => {
+                    self.binding.retain(|(p, v)| !(*p == peer && *v == addr));
+
+                    if self.peers.get(&peer).map(|p| p.via) == Some(addr) {
+                        self.peers.remove(&peer);
+                        self.events.push_back(NodeEvent::PeerRemoved(peer));
+                    }
+
+                    progress = true;
+                    continue;
+                }
+                Poll::Ready(Ok(None)) => {
+                    self.events.push_back(NodeEvent::BootstrapClosed(addr));
+                }
+                Poll::Ready(Err(e)) => {
+                    let e = NodeError::SignalingError(e);
+                    self.events.push_back(NodeEvent::BootstrapFailed(addr, e));
+                }
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            self.discoveries.remove(i);
+            self.remove_via(addr);
+            progress = true;
+        }
+
+        progress
+    }
+
+    fn poll_binding(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+
+        while i < self.binding.len() {
+            let (peer, via) = &self.binding[i];
+
+            match Pin::new(&mut self.transport).poll_bind(cx, peer) {
+                Poll::Ready(Ok(socket)) => {
+                    // Lower id sends the offer.
+                    let initiator = self.local < *peer;
+
+                    let p = Peer {
+                        socket,
+                        via: *via,
+                        state: EstablishState::new(initiator),
+                        exchanged: false,
+                        reported: false,
+                        events_done: false,
+                        connected: false,
+                    };
+
+                    self.peers.insert(peer.clone(), p);
+                    self.events.push_back(NodeEvent::PeerAdded(peer.clone()));
+                }
+                Poll::Ready(Err(e)) => {
+                    let e = NodeError::TransportError(e);
+                    self.events
+                        .push_back(NodeEvent::PeerFailed(peer.clone(), e));
+                }
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            self.binding.remove(i);
+            progress = true;
+        }
+
+        progress
+    }
+
+    fn poll_peers(&mut self, cx: &mut Context<'_>) -> bool {
+        let mut failed = Vec::new();
+        let mut progress = false;
+
+        for (id, peer) in self.peers.iter_mut() {
+            if let Some((_, discovery)) = self.discoveries.iter_mut().find(|(a, _)| *a == peer.via)
+            {
+                // Ready again after exchange, only first one is progress.
+                match peer
+                    .state
+                    .poll_establish(cx, &mut peer.socket, discovery, id)
+                {
+                    Poll::Ready(Ok(())) if !peer.exchanged => {
+                        peer.exchanged = true;
+                        progress = true;
+                    }
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(e)) => {
+                        failed.push((id.clone(), e));
+                        continue;
+                    }
+                    Poll::Pending => {}
+                }
+            }
+
+            while !peer.events_done {
+                match Pin::new(&peer.socket).poll_event(cx) {
+                    Poll::Ready(Some(event)) => {
+                        match event {
+                            SocketEvent::Connected if !peer.connected => {
+                                peer.connected = true;
+                                self.events.push_back(NodeEvent::PeerConnected(id.clone()));
+                            }
+                            SocketEvent::Failed | SocketEvent::Closed => peer.connected = false,
+                            _ => {}
+                        }
+
+                        self.events
+                            .push_back(NodeEvent::PeerEvent(id.clone(), event));
+                        peer.reported = true;
+                        progress = true;
+                    }
+                    Poll::Ready(None) => peer.events_done = true,
+                    Poll::Pending => break,
+                }
+            }
+
+            if peer.events_done && !peer.reported && peer.exchanged && !peer.connected {
+                peer.connected = true;
+                self.events.push_back(NodeEvent::PeerConnected(id.clone()));
+                progress = true;
+            }
+        }
+
+        for (id, e) in failed {
+            self.peers.remove(&id);
+            self.events
+                .push_back(NodeEvent::PeerFailed(id, NodeError::from(e)));
+            progress = true;
+        }
+
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        future::{pending, Pending},
+        net::SocketAddr,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    };
+
+    use futures_lite::{
+        future::{self, block_on},
+        io::Cursor,
+    };
+
+    use crate::{
+        Config, Discovery, Node, NodeEvent, NodeTransport, P2pSocket, P2pSocketEvents, PeerEvent,
+        Signaling, SocketEvent,
+    };
+
+    #[derive(Default)]
+    struct FakeSocket {
+        local: Mutex<VecDeque<u16>>,
+    }
+
+    impl P2pSocketEvents for FakeSocket {
+        fn poll_event(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+            Poll::Ready(None)
+        }
+    }
+
+    impl P2pSocket for FakeSocket {
+        type Stream = Cursor<Vec<u8>>;
+
+        type Addr = u16;
+
+        type Error = ();
+
+        type Binding = future::Ready<Result<Self, ()>>;
+
+        type Connecting = future::Ready<Result<Self::Stream, ()>>;
+
+        type SettingRemoteAddr = future::Ready<Result<(), ()>>;
+
+        fn binding(_bootstrap: u16) -> Self::Binding {
+            future::ready(Ok(Self::default()))
+        }
+
+        fn connecting(&self, _label: u16, _port: u16) -> Self::Connecting {
+            future::ready(Err(()))
+        }
+
+        fn poll_accept(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Result<Self::Stream, ()>> {
+            Poll::Pending
+        }
+
+        fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            self.local.lock().unwrap().push_back(0);
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_fetch_local_addr(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<u16, ()>> {
+            match self.local.lock().unwrap().pop_front() {
+                Some(addr) => Poll::Ready(Ok(addr)),
+                None => Poll::Pending,
+            }
+        }
+
+        fn setting_remote_addr(&self, _remote: u16) -> Self::SettingRemoteAddr {
+            future::ready(Ok(()))
+        }
+
+        fn is_last_addr(addr: &u16) -> bool {
+            *addr < 10
+        }
+    }
+
+    type Sent = Arc<Mutex<Vec<(String, u16)>>>;
+
+    type Received = Arc<Mutex<VecDeque<u16>>>;
+
+    struct FakeDiscovery {
+        peers: Arc<Mutex<VecDeque<PeerEvent>>>,
+        sent: Sent,
+        received: Received,
+    }
+
+    impl Signaling<u16> for FakeDiscovery {
+        type Error = ();
+
+        fn poll_send(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            remote: &str,
+            addr: &u16,
+        ) -> Poll<Result<(), ()>> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((String::from(remote), *addr));
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_recv(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _remote: &str,
+        ) -> Poll<Result<Option<u16>, ()>> {
+            match self.received.lock().unwrap().pop_front() {
+                Some(addr) => Poll::Ready(Ok(Some(addr))),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    impl Discovery<u16> for FakeDiscovery {
+        fn poll_next_peer(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<Result<Option<PeerEvent>, ()>> {
+            match self.peers.lock().unwrap().pop_front() {
+                Some(e) => Poll::Ready(Ok(Some(e))),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    struct FakeTransport {
+        peers: Arc<Mutex<VecDeque<PeerEvent>>>,
+        sent: Sent,
+        received: Received,
+    }
+
+    impl NodeTransport for FakeTransport {
+        type Socket = FakeSocket;
+
+        type Discovery = FakeDiscovery;
+
+        type Listener = Pending<Result<(), &'static str>>;
+
+        type Error = &'static str;
+
+        fn poll_dial(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            addr: &SocketAddr,
+        ) -> Poll<Result<FakeDiscovery, &'static str>> {
+            if addr.port() == 1 {
+                return Poll::Ready(Err("refused"));
+            }
+
+            Poll::Ready(Ok(FakeDiscovery {
+                peers: self.peers.clone(),
+                sent: self.sent.clone(),
+                received: self.received.clone(),
+            }))
+        }
+
+        fn poll_listen(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _addr: &SocketAddr,
+        ) -> Poll<Result<Self::Listener, &'static str>> {
+            Poll::Ready(Ok(pending()))
+        }
+
+        fn poll_bind(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _remote: &str,
+        ) -> Poll<Result<FakeSocket, &'static str>> {
+            Poll::Ready(Ok(FakeSocket::default()))
+        }
+    }
+
+    type Peers = Arc<Mutex<VecDeque<PeerEvent>>>;
+
+    fn node(config: Config) -> (Node<FakeTransport>, Peers, Sent, Received) {
+        let peers = Peers::default();
+        let sent = Sent::default();
+        let received = Received::default();
+
+        let transport = FakeTransport {
+            peers: peers.clone(),
+            sent: sent.clone(),
+            received: received.clone(),
+        };
+
+        (Node::new(config, "b", transport), peers, sent, received)
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn joined(peers: &Peers, ids: &[&str]) {
+        let mut peers = peers.lock().unwrap();
+
+        for id in ids {
+            peers.push_back(PeerEvent::Joined(String::from(*id)));
+        }
+    }
+
+    #[test]
+    fn track_peers_from_bootstrap() {
+        let config = Config {
+            bootstrap_nodes: vec![addr(1), addr(2)],
+            forward_listen: vec![addr(3)],
+            ..Default::default()
+        };
+
+        let (mut node, peers, sent, _) = node(config);
+
+        joined(&peers, &["a", "b", "c", "c"]);
+
+        block_on(async {
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::BootstrapFailed(a, _) if a == addr(1)));
+
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::BootstrapConnected(a) if a == addr(2)));
+
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::PeerAdded(p) if p == "a"));
+
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::PeerAdded(p) if p == "c"));
+        });
+
+        let mut ids: Vec<&str> = node.peers().collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec!["a", "c"]);
+
+        // Only lower id sends offer.
+        assert_eq!(*sent.lock().unwrap(), vec![(String::from("c"), 0)]);
+
+        peers
+            .lock()
+            .unwrap()
+            .push_back(PeerEvent::Left(String::from("a")));
+
+        let event = block_on(node.next_event());
+        assert!(matches!(event, NodeEvent::PeerRemoved(p) if p == "a"));
+        assert!(node.peer("a").is_none());
+        assert!(node.peer("c").is_some());
+    }
+
+    #[test]
+    fn listen_when_forward_enabled() {
+        let config = Config {
+            forward_listen: vec![addr(3)],
+            enable_forward: true,
+            ..Default::default()
+        };
+
+        let (mut node, _, _, _) = node(config);
+
+        let event = block_on(node.next_event());
+        assert!(matches!(event, NodeEvent::Listening(a) if a == addr(3)));
+    }
+
+    #[test]
+    fn exchange_after_connected() {
+        let config = Config {
+            bootstrap_nodes: vec![addr(2)],
+            ..Default::default()
+        };
+
+        let (mut node, peers, sent, received) = node(config);
+
+        joined(&peers, &["c"]);
+        received.lock().unwrap().push_back(1);
+
+        block_on(async {
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::BootstrapConnected(_)));
+
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::PeerAdded(p) if p == "c"));
+
+            let event = node.next_event().await;
+            assert!(matches!(event, NodeEvent::PeerConnected(p) if p == "c"));
+        });
+
+        assert!(node.is_connected("c"));
+
+        // Addresses of a restart are exchanged after connected.
+        node.peer("c").unwrap().local.lock().unwrap().push_back(17);
+        received.lock().unwrap().push_back(18);
+
+        assert!(block_on(future::poll_once(node.next_event())).is_none());
+
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![(String::from("c"), 0), (String::from("c"), 17)]
+        );
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
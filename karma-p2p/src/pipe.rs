@@ -0,0 +1,109 @@
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures_lite::{AsyncRead, AsyncWrite};
+
+#[derive(Default)]
+struct PipeBuf {
+    data: VecDeque<u8>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// In-memory duplex stream for tests.
+pub(crate) struct Pipe {
+    rx: Arc<Mutex<PipeBuf>>,
+    tx: Arc<Mutex<PipeBuf>>,
+}
+
+pub(crate) fn pipe() -> (Pipe, Pipe) {
+    let a = Arc::<Mutex<PipeBuf>>::default();
+    let b = Arc::<Mutex<PipeBuf>>::default();
+
+    (
+        Pipe {
+            rx: a.clone(),
+            tx: b.clone(),
+        },
+        Pipe { rx: b, tx: a },
+    )
+}
+
+impl AsyncRead for Pipe {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut rx = self.rx.lock().unwrap();
+
+        if rx.data.is_empty() {
+            if rx.closed {
+                return Poll::Ready(Ok(0));
+            }
+
+            rx.waker = Some(cx.waker().clone());
+
+            return Poll::Pending;
+        }
+
+        let n = rx.data.len().min(buf.len());
+
+        for (b, d) in buf.iter_mut().zip(rx.data.drain(..n)) {
+            *b = d;
+        }
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for Pipe {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut tx = self.tx.lock().unwrap();
+
+        tx.data.extend(buf);
+
+        if let Some(w) = tx.waker.take() {
+            w.wake();
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut tx = self.tx.lock().unwrap();
+
+        tx.closed = true;
+
+        if let Some(w) = tx.waker.take() {
+            w.wake();
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut tx = self.tx.lock().unwrap();
+
+        tx.closed = true;
+
+        if let Some(w) = tx.waker.take() {
+            w.wake();
+        }
+    }
+}
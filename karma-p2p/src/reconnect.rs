@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use crate::SocketEvent;
+
+/// When socket restarts its connection by itself.
+///
+/// Only the side which started the socket restarts, so offers of both sides don't
+/// cross. Remote answers the restart offer received through signaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReconnectPolicy {
+    /// Only restart when asked, such as by `restart_ice` of WebRTC sockets.
+    #[default]
+    Manual,
+
+    /// Restart as soon as connection is disconnected, such as when network interface
+    /// changes.
+    OnDisconnected,
+
+    /// Restart once connectivity checks give up on all candidate pairs.
+    OnFailed,
+}
+
+impl ReconnectPolicy {
+    /// Whether connection is restarted when socket reports `event`.
+    pub fn applies(&self, event: &SocketEvent) -> bool {
+        matches!(
+            (self, event),
+            (Self::OnDisconnected, SocketEvent::Disconnected)
+                | (Self::OnFailed, SocketEvent::Failed)
+        )
+    }
+}
+
+/// Delay of second automatic restart, doubled on each further one.
+pub const RECONNECT_BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+pub const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// Delays between automatic restarts, so a peer which is gone isn't offered to in a
+/// loop. Reset once connected.
+#[derive(Debug, Default)]
+pub struct ReconnectBackoff {
+    attempts: u32,
+}
+
+impl ReconnectBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delay before next restart, first one isn't delayed.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = match self.attempts {
+            0 => Duration::ZERO,
+            n => RECONNECT_BACKOFF_BASE
+                .saturating_mul(1 << (n - 1).min(16))
+                .min(RECONNECT_BACKOFF_MAX),
+        };
+
+        self.attempts = self.attempts.saturating_add(1);
+
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ReconnectBackoff, ReconnectPolicy, RECONNECT_BACKOFF_MAX};
+    use crate::SocketEvent;
+
+    #[test]
+    fn policy_and_backoff() {
+        assert!(ReconnectPolicy::OnFailed.applies(&SocketEvent::Failed));
+        assert!(!ReconnectPolicy::OnFailed.applies(&SocketEvent::Disconnected));
+        assert!(!ReconnectPolicy::Manual.applies(&SocketEvent::Failed));
+
+        let mut backoff = ReconnectBackoff::new();
+
+        let delays: Vec<_> = (0..4).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![0, 1, 2, 4]);
+
+        for _ in 0..64 {
+            assert!(backoff.next_delay() <= RECONNECT_BACKOFF_MAX);
+        }
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_lite::{future::Boxed, Future, FutureExt};
+
+use crate::{
+    futures::{RouteFuture, ServeRouterFuture},
+    NegotiateError, NegotiateState, Negotiated, P2pSocket, P2pStream,
+};
+
+type Handler<S> = Arc<dyn Fn(Negotiated<S>) -> Boxed<()> + Send + Sync>;
+
+/// Dispatch streams to handlers by protocol negotiated on them.
+///
+/// Remote side picks protocol with [`Negotiated::dial`].
+pub struct Router<S> {
+    handlers: HashMap<String, Handler<S>>,
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+impl<S> Clone for Router<S> {
+    fn clone(&self) -> Self {
+        Self {
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+impl<S: P2pStream + Unpin> Router<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle streams of `protocol`, replaces previous handler of it.
+    pub fn register<F, Fut>(&mut self, protocol: &str, handler: F)
+    where
+        F: Fn(Negotiated<S>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.handlers.insert(
+            String::from(protocol),
+            Arc::new(move |stream| handler(stream).boxed()),
+        );
+    }
+
+    pub fn protocols(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
+    /// Negotiate protocol on stream of `state`, then run its handler.
+    pub fn poll_route(
+        &self,
+        cx: &mut Context<'_>,
+        state: &mut RouteState<S>,
+    ) -> Poll<Result<(), NegotiateError>> {
+        if let Some(stream) = &mut state.stream {
+            let protocol = match state.negotiate.poll_negotiate(cx, stream) {
+                Poll::Ready(Ok(protocol)) => protocol,
+                Poll::Ready(Err(e)) => {
+                    state.stream = None;
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let stream = state.stream.take().expect("checked is some");
+
+            // Listener agrees on protocols registered when state was made, which may
+            // not be handled by this router.
+            let handler = match self.handlers.get(&protocol) {
+                Some(handler) => handler,
+                None => return Poll::Ready(Err(NegotiateError::ErrUnsupported)),
+            };
+
+            state.handler = Some(handler(Negotiated::new(stream, protocol)));
+        }
+
+        match &mut state.handler {
+            Some(handler) => handler.poll(cx).map(Ok),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    pub fn route(&self, stream: S) -> RouteFuture<'_, S> {
+        RouteFuture {
+            router: self,
+            state: RouteState::new(self, stream),
+        }
+    }
+
+    /// Accept streams of `socket` and route them concurrently, ready only when
+    /// accepting fails. Streams failed negotiation are dropped.
+    pub fn poll_serve<P>(
+        &self,
+        cx: &mut Context<'_>,
+        socket: Pin<&P>,
+        routes: &mut Vec<RouteState<S>>,
+    ) -> Poll<P::Error>
+    where
+        P: P2pSocket<Stream = S>,
+    {
+        loop {
+            match socket.poll_accept(cx) {
+                Poll::Ready(Ok(stream)) => routes.push(RouteState::new(self, stream)),
+                Poll::Ready(Err(e)) => return Poll::Ready(e),
+                Poll::Pending => break,
+            }
+        }
+
+        routes.retain_mut(|route| self.poll_route(cx, route).is_pending());
+
+        Poll::Pending
+    }
+
+    pub fn serve<'a, P>(&'a self, socket: &'a P) -> ServeRouterFuture<'a, P>
+    where
+        P: P2pSocket<Stream = S>,
+    {
+        ServeRouterFuture {
+            router: self,
+            socket,
+            routes: Vec::new(),
+        }
+    }
+}
+
+/// Progress of routing one stream.
+pub struct RouteState<S> {
+    pub stream: Option<S>,
+    pub negotiate: NegotiateState,
+    pub handler: Option<Boxed<()>>,
+}
+
+impl<S> RouteState<S> {
+    pub fn new(router: &Router<S>, stream: S) -> Self {
+        Self {
+            stream: Some(stream),
+            negotiate: NegotiateState::listener(router.handlers.keys().cloned()),
+            handler: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+
+    use super::{RouteState, Router};
+    use crate::{pipe::pipe, NegotiateError, Negotiated};
+
+    #[test]
+    fn dispatch_by_protocol() {
+        future::block_on(async {
+            let mut router = Router::new();
+
+            router.register("/echo/1.0.0", |mut stream: Negotiated<_>| async move {
+                let mut buf = [0u8; 4];
+                stream.read_exact(&mut buf).await.unwrap();
+                stream.write_all(&buf).await.unwrap();
+            });
+
+            router.register("/hello/1.0.0", |mut stream: Negotiated<_>| async move {
+                stream.write_all(b"hello").await.unwrap();
+            });
+
+            let (a, b) = pipe();
+            let (c, d) = pipe();
+            let (e, f) = pipe();
+
+            let dialers = async {
+                let mut echo = Negotiated::dial(a, ["/echo/1.0.0"]).await.unwrap();
+                echo.write_all(b"ping").await.unwrap();
+
+                let mut buf = [0u8; 4];
+                echo.read_exact(&mut buf).await.unwrap();
+                assert_eq!(&buf, b"ping");
+
+                let mut hello = Negotiated::dial(c, ["/hello/2.0.0", "/hello/1.0.0"])
+                    .await
+                    .unwrap();
+                assert_eq!(hello.protocol(), "/hello/1.0.0");
+
+                let mut buf = Vec::new();
+                hello.read_to_end(&mut buf).await.unwrap();
+                assert_eq!(buf, b"hello");
+
+                let res = Negotiated::dial(e, ["/chat/1.0.0"]).await;
+                assert!(matches!(res, Err(NegotiateError::ErrUnsupported)));
+            };
+
+            let routes = async {
+                let ((x, y), z) = future::zip(
+                    future::zip(router.route(b), router.route(d)),
+                    router.route(f),
+                )
+                .await;
+
+                assert!(x.is_ok());
+                assert!(y.is_ok());
+                assert!(matches!(z, Err(NegotiateError::ErrClosed)));
+            };
+
+            future::zip(dialers, routes).await;
+        });
+    }
+
+    #[test]
+    fn unknown_protocol_of_state() {
+        future::block_on(async {
+            let mut router = Router::new();
+            router.register("/echo/1.0.0", |_: Negotiated<_>| async {});
+
+            let (a, b) = pipe();
+
+            // State agrees on protocols of another router.
+            let mut state = RouteState::new(&router, b);
+            let other = Router::new();
+
+            let (dialer, routed) = future::zip(
+                Negotiated::dial(a, ["/echo/1.0.0"]),
+                future::poll_fn(|cx| other.poll_route(cx, &mut state)),
+            )
+            .await;
+
+            assert!(dialer.is_ok());
+            assert!(matches!(routed, Err(NegotiateError::ErrUnsupported)));
+        });
+    }
+}
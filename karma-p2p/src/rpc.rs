@@ -0,0 +1,818 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures_lite::{future::Boxed, ready, Future, FutureExt};
+use serde::{
+    de::{self, DeserializeOwned, SeqAccess, Visitor},
+    ser::Error as _,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::value::RawValue;
+
+use crate::{
+    futures::{CallFuture, CloseRpcFuture, ServeFuture},
+    waker_set::WakerSet,
+    Format, P2pStream, TaskSet, TypedError, TypedStream,
+};
+
+/// Default timeout of calls made by `RpcClient`.
+pub const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default number of requests `RpcServer` handles at once on one stream.
+pub const RPC_MAX_IN_FLIGHT: usize = 64;
+
+/// Message exchanged between `RpcClient` and `RpcServer`.
+///
+/// Params and results are encoded by the same `Format` as messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RpcMessage {
+    Request {
+        id: u64,
+        method: String,
+        params: RpcPayload,
+    },
+
+    Response {
+        id: u64,
+        result: Result<RpcPayload, RpcFault>,
+    },
+
+    /// Caller gave up on request, server drops its handler.
+    Cancel { id: u64 },
+}
+
+/// Encoded params or result.
+///
+/// Human readable formats nest it as is, so `Json` messages carry json values
+/// instead of arrays of numbers. Others write it as bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcPayload(pub Vec<u8>);
+
+impl Serialize for RpcPayload {
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        if serializer.is_human_readable() {
+            let raw: &RawValue = serde_json::from_slice(&self.0).map_err(Se::Error::custom)?;
+
+            return raw.serialize(serializer);
+        }
+
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for RpcPayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let raw = Box::<RawValue>::deserialize(deserializer)?;
+
+            return Ok(RpcPayload(raw.get().as_bytes().to_vec()));
+        }
+
+        deserializer.deserialize_byte_buf(PayloadVisitor)
+    }
+}
+
+struct PayloadVisitor;
+
+impl<'de> Visitor<'de> for PayloadVisitor {
+    type Value = RpcPayload;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "encoded rpc payload")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<RpcPayload, E> {
+        Ok(RpcPayload(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<RpcPayload, E> {
+        Ok(RpcPayload(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<RpcPayload, A::Error> {
+        let mut buf = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+
+        while let Some(b) = seq.next_element()? {
+            buf.push(b);
+        }
+
+        Ok(RpcPayload(buf))
+    }
+}
+
+/// Error response of server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RpcFault {
+    MethodNotFound(String),
+    InvalidParams(String),
+    Handler(String),
+}
+
+#[derive(Debug)]
+pub enum RpcError {
+    ErrTimeout,
+    ErrClosed,
+    ErrMethodNotFound(String),
+    ErrInvalidParams(String),
+    ErrHandler(String),
+    TypedError(TypedError),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::ErrTimeout => write!(f, "rpc call timeout"),
+            RpcError::ErrClosed => write!(f, "rpc stream closed"),
+            RpcError::ErrMethodNotFound(m) => write!(f, "rpc method {} not found", m),
+            RpcError::ErrInvalidParams(e) => write!(f, "invalid rpc params: {}", e),
+            RpcError::ErrHandler(e) => write!(f, "rpc handler failed: {}", e),
+            RpcError::TypedError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<TypedError> for RpcError {
+    fn from(e: TypedError) -> Self {
+        RpcError::TypedError(e)
+    }
+}
+
+impl From<RpcFault> for RpcError {
+    fn from(e: RpcFault) -> Self {
+        match e {
+            RpcFault::MethodNotFound(m) => RpcError::ErrMethodNotFound(m),
+            RpcFault::InvalidParams(e) => RpcError::ErrInvalidParams(e),
+            RpcFault::Handler(e) => RpcError::ErrHandler(e),
+        }
+    }
+}
+
+struct Call {
+    result: Option<Result<Bytes, RpcFault>>,
+    waker: Option<Waker>,
+}
+
+struct ClientInner<S> {
+    stream: TypedStream<S, RpcMessage>,
+    next_id: u64,
+    calls: HashMap<u64, Call>,
+    outgoing: VecDeque<RpcMessage>,
+    closed: bool,
+}
+
+impl<S: P2pStream + Unpin> ClientInner<S> {
+    fn close(&mut self) {
+        self.closed = true;
+
+        for call in self.calls.values_mut() {
+            if let Some(w) = call.waker.take() {
+                w.wake();
+            }
+        }
+    }
+
+    /// Send queued messages and dispatch responses until stream blocks.
+    fn drive(&mut self, cx: &mut Context<'_>) {
+        while !self.closed {
+            let mut progress = false;
+
+            if !self.outgoing.is_empty() {
+                match self.stream.poll_send_ready(cx) {
+                    Poll::Ready(Ok(())) => {
+                        let message = self.outgoing.pop_front().expect("checked not empty");
+
+                        if self.stream.start_send(message).is_err() {
+                            self.close();
+                        }
+
+                        progress = true;
+                    }
+                    Poll::Ready(Err(_)) => self.close(),
+                    Poll::Pending => {}
+                }
+            }
+
+            if let Poll::Ready(Err(_)) = self.stream.poll_send_flush(cx) {
+                self.close();
+            }
+
+            match self.stream.poll_recv(cx) {
+                Poll::Ready(Some(Ok(RpcMessage::Response { id, result }))) => {
+                    // Responses of cancelled calls are dropped.
+                    if let Some(call) = self.calls.get_mut(&id) {
+                        call.result = Some(result.map(|p| Bytes::from(p.0)));
+
+                        if let Some(w) = call.waker.take() {
+                            w.wake();
+                        }
+                    }
+
+                    progress = true;
+                }
+                Poll::Ready(Some(Ok(_))) => progress = true,
+                Poll::Ready(Some(Err(_))) | Poll::Ready(None) => self.close(),
+                Poll::Pending => {}
+            }
+
+            if !progress {
+                break;
+            }
+        }
+    }
+}
+
+struct ClientShared<S> {
+    inner: Mutex<ClientInner<S>>,
+    wakers: Arc<WakerSet>,
+    format: Format,
+    timeout: Option<Duration>,
+}
+
+/// Calls methods of `RpcServer` on the other end of stream.
+///
+/// Calls can be made concurrently from clones of client. Stream is driven by
+/// whichever call is polled, a call dropped before response is cancelled.
+pub struct RpcClient<S> {
+    shared: Arc<ClientShared<S>>,
+}
+
+impl<S> Clone for RpcClient<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S> RpcClient<S> {
+    pub fn format(&self) -> Format {
+        self.shared.format
+    }
+
+    /// Forget call `id` and tell server to drop its handler.
+    pub fn cancel(&self, id: u64) {
+        let mut inner = self.shared.inner.lock().unwrap();
+
+        if inner.calls.remove(&id).is_some() && !inner.closed {
+            inner.outgoing.push_back(RpcMessage::Cancel { id });
+
+            drop(inner);
+
+            self.shared.wakers.wake_by_ref();
+        }
+    }
+}
+
+impl<S: P2pStream + Unpin> RpcClient<S> {
+    /// Use `RPC_TIMEOUT`.
+    pub fn new(stream: S, format: Format) -> Self {
+        Self::with_options(stream, format, Some(RPC_TIMEOUT))
+    }
+
+    /// Calls fail with `ErrTimeout` after `timeout`, `None` waits forever.
+    pub fn with_options(stream: S, format: Format, timeout: Option<Duration>) -> Self {
+        let inner = ClientInner {
+            stream: TypedStream::new(stream, format),
+            next_id: 0,
+            calls: HashMap::new(),
+            outgoing: VecDeque::new(),
+            closed: false,
+        };
+
+        Self {
+            shared: Arc::new(ClientShared {
+                inner: Mutex::new(inner),
+                wakers: Arc::default(),
+                format,
+                timeout,
+            }),
+        }
+    }
+
+    /// Send request of `method`, get its response with `poll_response`.
+    pub fn start_call<P: Serialize>(&self, method: &str, params: P) -> Result<u64, RpcError> {
+        let params = self.shared.format.encode(&params)?;
+
+        let mut inner = self.shared.inner.lock().unwrap();
+
+        if inner.closed {
+            return Err(RpcError::ErrClosed);
+        }
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        inner.calls.insert(
+            id,
+            Call {
+                result: None,
+                waker: None,
+            },
+        );
+
+        inner.outgoing.push_back(RpcMessage::Request {
+            id,
+            method: String::from(method),
+            params: RpcPayload(params.to_vec()),
+        });
+
+        drop(inner);
+
+        // Stream is written by whoever drives it next.
+        self.shared.wakers.wake_by_ref();
+
+        Ok(id)
+    }
+
+    /// Fail pending calls with `ErrClosed` and close stream once their cancels are
+    /// sent, server sees end of stream and finishes serving.
+    pub fn poll_close(&self, cx: &mut Context<'_>) -> Poll<Result<(), RpcError>> {
+        let mut inner = self.shared.inner.lock().unwrap();
+
+        if !inner.closed {
+            let pending: Vec<u64> = inner.calls.keys().copied().collect();
+
+            inner
+                .outgoing
+                .extend(pending.into_iter().map(|id| RpcMessage::Cancel { id }));
+
+            inner.close();
+        }
+
+        while !inner.outgoing.is_empty() {
+            ready!(inner.stream.poll_send_ready(cx))?;
+
+            let message = inner.outgoing.pop_front().expect("checked not empty");
+            inner.stream.start_send(message)?;
+        }
+
+        ready!(inner.stream.poll_send_close(cx))?;
+
+        Poll::Ready(Ok(()))
+    }
+
+    pub fn close(&self) -> CloseRpcFuture<'_, S> {
+        CloseRpcFuture { client: self }
+    }
+
+    /// Wait response of call `id`, encoded result is returned.
+    pub fn poll_response(&self, cx: &mut Context<'_>, id: u64) -> Poll<Result<Bytes, RpcError>> {
+        let mut inner = self.shared.inner.lock().unwrap();
+
+        self.shared.wakers.register(cx.waker());
+
+        let waker = Waker::from(self.shared.wakers.clone());
+        inner.drive(&mut Context::from_waker(&waker));
+
+        let closed = inner.closed;
+
+        let call = match inner.calls.get_mut(&id) {
+            Some(call) => call,
+            None => return Poll::Ready(Err(RpcError::ErrClosed)),
+        };
+
+        if let Some(result) = call.result.take() {
+            inner.calls.remove(&id);
+
+            return Poll::Ready(result.map_err(RpcError::from));
+        }
+
+        if closed {
+            inner.calls.remove(&id);
+
+            return Poll::Ready(Err(RpcError::ErrClosed));
+        }
+
+        call.waker = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+
+    /// Call `method` with `params` and decode result as `R`.
+    pub fn call<P, R>(&self, method: &str, params: P) -> CallFuture<'_, S, R>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        self.call_timeout(method, params, self.shared.timeout)
+    }
+
+    pub fn call_timeout<P, R>(
+        &self,
+        method: &str,
+        params: P,
+        timeout: Option<Duration>,
+    ) -> CallFuture<'_, S, R>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let (id, error) = match self.start_call(method, params) {
+            Ok(id) => (Some(id), None),
+            Err(e) => (None, Some(e)),
+        };
+
+        CallFuture {
+            client: self,
+            id,
+            error,
+            delay: timeout.map(futures_timer::Delay::new),
+            result: PhantomData,
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(Bytes) -> Boxed<Result<Bytes, RpcFault>> + Send + Sync>;
+
+/// Serves requests of `RpcClient` with handlers registered by method name.
+#[derive(Clone)]
+pub struct RpcServer {
+    format: Format,
+    handlers: HashMap<String, Handler>,
+    max_in_flight: usize,
+}
+
+impl RpcServer {
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            handlers: HashMap::new(),
+            max_in_flight: RPC_MAX_IN_FLIGHT,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Requests handled at once on each stream, more aren't read until one is
+    /// answered. Default is `RPC_MAX_IN_FLIGHT`.
+    pub fn set_max_in_flight(&mut self, max: usize) {
+        self.max_in_flight = max.max(1);
+    }
+
+    /// Handle requests of `method`, replaces previous handler of it.
+    ///
+    /// Error of handler is sent to caller as `ErrHandler`.
+    pub fn register<P, R, F, Fut>(&mut self, method: &str, handler: F)
+    where
+        P: DeserializeOwned,
+        R: Serialize,
+        F: Fn(P) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, String>> + Send + 'static,
+    {
+        let format = self.format;
+
+        let handler: Handler = Arc::new(move |params| {
+            let params = match format.decode::<P>(&params) {
+                Ok(p) => p,
+                Err(e) => {
+                    let fault = RpcFault::InvalidParams(e.to_string());
+                    return async move { Err(fault) }.boxed();
+                }
+            };
+
+            let fu = handler(params);
+
+            async move {
+                let result = fu.await.map_err(RpcFault::Handler)?;
+
+                format
+                    .encode(&result)
+                    .map_err(|e| RpcFault::Handler(e.to_string()))
+            }
+            .boxed()
+        });
+
+        self.handlers.insert(String::from(method), handler);
+    }
+
+    /// Serve requests on `state` until stream is closed by client, handlers of
+    /// requests run concurrently up to `max_in_flight`.
+    pub fn poll_serve<S>(
+        &self,
+        cx: &mut Context<'_>,
+        state: &mut ServeState<S>,
+    ) -> Poll<Result<(), RpcError>>
+    where
+        S: P2pStream + Unpin,
+    {
+        loop {
+            let mut progress = false;
+
+            // Unanswered requests stay in stream, so client is pushed back.
+            let busy = state.in_flight.len() + state.outgoing.len() >= self.max_in_flight;
+
+            if !state.recv_done && !busy {
+                match state.stream.poll_recv(cx) {
+                    Poll::Ready(Some(Ok(message))) => {
+                        self.handle(state, message);
+                        progress = true;
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e.into())),
+                    Poll::Ready(None) => state.recv_done = true,
+                    Poll::Pending => {}
+                }
+            }
+
+            while let Poll::Ready(Some((id, result))) = state.in_flight.poll_next(cx) {
+                state.outgoing.push_back(RpcMessage::Response {
+                    id,
+                    result: result.map(|r| RpcPayload(r.to_vec())),
+                });
+
+                progress = true;
+            }
+
+            if !state.outgoing.is_empty() {
+                if let Poll::Ready(res) = state.stream.poll_send_ready(cx) {
+                    res?;
+
+                    let message = state.outgoing.pop_front().expect("checked not empty");
+                    state.stream.start_send(message)?;
+
+                    progress = true;
+                }
+            }
+
+            let flushed = state.stream.poll_send_flush(cx)?.is_ready();
+
+            if state.recv_done && state.in_flight.is_empty() && state.outgoing.is_empty() && flushed
+            {
+                return Poll::Ready(Ok(()));
+            }
+
+            if !progress {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    fn handle<S>(&self, state: &mut ServeState<S>, message: RpcMessage) {
+        match message {
+            RpcMessage::Request { id, method, params } => match self.handlers.get(&method) {
+                Some(handler) => state.in_flight.insert(id, handler(Bytes::from(params.0))),
+                None => state.outgoing.push_back(RpcMessage::Response {
+                    id,
+                    result: Err(RpcFault::MethodNotFound(method)),
+                }),
+            },
+            RpcMessage::Cancel { id } => {
+                state.in_flight.remove(id);
+            }
+            RpcMessage::Response { .. } => {}
+        }
+    }
+
+    pub fn serve<S>(&self, stream: S) -> ServeFuture<'_, S>
+    where
+        S: P2pStream + Unpin,
+    {
+        ServeFuture {
+            server: self,
+            state: ServeState::new(stream, self.format),
+        }
+    }
+}
+
+/// Progress of serving one stream, used by owners of stream which can't lend it to
+/// [`RpcServer::serve`].
+pub struct ServeState<S> {
+    pub stream: TypedStream<S, RpcMessage>,
+    pub in_flight: TaskSet<Result<Bytes, RpcFault>>,
+    pub outgoing: VecDeque<RpcMessage>,
+    pub recv_done: bool,
+}
+
+impl<S: P2pStream + Unpin> ServeState<S> {
+    pub fn new(stream: S, format: Format) -> Self {
+        Self {
+            stream: TypedStream::new(stream, format),
+            in_flight: TaskSet::new(),
+            outgoing: VecDeque::new(),
+            recv_done: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use futures_lite::future::{self, block_on};
+
+    use crate::{pipe::pipe, Format, RpcClient, RpcError, RpcMessage, RpcPayload, RpcServer};
+
+    fn server() -> RpcServer {
+        let mut server = RpcServer::new(Format::Json);
+
+        server.register("add", |(a, b): (i32, i32)| async move { Ok(a + b) });
+
+        server.register("div", |(a, b): (i32, i32)| async move {
+            a.checked_div(b)
+                .ok_or_else(|| String::from("divide by zero"))
+        });
+
+        server
+    }
+
+    #[test]
+    fn calls_and_errors() {
+        block_on(async {
+            let (a, b) = pipe();
+
+            let server = server();
+            let client = RpcClient::new(a, Format::Json);
+
+            let calls = async {
+                let other = client.clone();
+
+                let (x, y) = future::zip(
+                    client.call::<_, i32>("add", (1, 2)),
+                    other.call::<_, i32>("div", (9, 3)),
+                )
+                .await;
+
+                assert_eq!(x.unwrap(), 3);
+                assert_eq!(y.unwrap(), 3);
+
+                let res = client.call::<_, i32>("div", (1, 0)).await;
+                assert!(matches!(res, Err(RpcError::ErrHandler(e)) if e == "divide by zero"));
+
+                let res = client.call::<_, i32>("mul", (1, 2)).await;
+                assert!(matches!(res, Err(RpcError::ErrMethodNotFound(m)) if m == "mul"));
+
+                let res = client.call::<_, i32>("add", (1,)).await;
+                assert!(matches!(res, Err(RpcError::ErrInvalidParams(_))));
+
+                client.close().await.unwrap();
+
+                let res = other.call::<_, i32>("add", (1, 2)).await;
+                assert!(matches!(res, Err(RpcError::ErrClosed)));
+            };
+
+            // Server finishes once client closed.
+            let (_, served) = future::zip(calls, server.serve(b)).await;
+            served.unwrap();
+        });
+    }
+
+    #[test]
+    fn timeout_cancels_handler() {
+        block_on(async {
+            let (a, b) = pipe();
+
+            // Set when handler future is dropped by server.
+            let dropped = Arc::new(AtomicBool::new(false));
+
+            struct Guard(Arc<AtomicBool>);
+
+            impl Drop for Guard {
+                fn drop(&mut self) {
+                    self.0.store(true, Ordering::SeqCst);
+                }
+            }
+
+            let mut server = server();
+            let flag = dropped.clone();
+
+            server.register("hang", move |_: ()| {
+                let guard = Guard(flag.clone());
+
+                async move {
+                    let _guard = guard;
+                    future::pending::<Result<(), String>>().await
+                }
+            });
+
+            let client = RpcClient::new(a, Format::Json);
+
+            let calls = async {
+                let res = client
+                    .call_timeout::<_, ()>("hang", (), Some(Duration::from_millis(50)))
+                    .await;
+                assert!(matches!(res, Err(RpcError::ErrTimeout)));
+
+                // Cancel is sent before next request, handled once it's answered.
+                assert_eq!(client.call::<_, i32>("add", (2, 2)).await.unwrap(), 4);
+                assert!(dropped.load(Ordering::SeqCst));
+
+                // Pending call is cancelled by close.
+                let hang = client.call::<_, ()>("hang", ());
+                let (res, closed) = future::zip(hang, async {
+                    future::yield_now().await;
+                    client.close().await
+                })
+                .await;
+
+                assert!(matches!(res, Err(RpcError::ErrClosed)));
+                closed.unwrap();
+            };
+
+            let (_, served) = future::zip(calls, server.serve(b)).await;
+            served.unwrap();
+        });
+    }
+
+    #[test]
+    fn limit_in_flight() {
+        block_on(async {
+            let (a, b) = pipe();
+
+            let running = Arc::new(AtomicUsize::new(0));
+            let most = Arc::new(AtomicUsize::new(0));
+
+            let mut server = server();
+            server.set_max_in_flight(2);
+
+            let (r, m) = (running.clone(), most.clone());
+
+            server.register("work", move |_: ()| {
+                let (r, m) = (r.clone(), m.clone());
+
+                async move {
+                    let n = r.fetch_add(1, Ordering::SeqCst) + 1;
+                    m.fetch_max(n, Ordering::SeqCst);
+
+                    yield_many().await;
+
+                    r.fetch_sub(1, Ordering::SeqCst);
+                    Ok(n)
+                }
+            });
+
+            let client = RpcClient::new(a, Format::Json);
+
+            let calls = async {
+                let results = future::zip(
+                    future::zip(client.call::<_, usize>("work", ()), client.call("work", ())),
+                    future::zip(client.call::<_, usize>("work", ()), client.call("work", ())),
+                )
+                .await;
+
+                let ((w, x), (y, z)) = results;
+                for n in [w, x, y, z] {
+                    assert!(n.unwrap() <= 2);
+                }
+
+                client.close().await.unwrap();
+            };
+
+            let (_, served) = future::zip(calls, server.serve(b)).await;
+            served.unwrap();
+
+            assert_eq!(most.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    /// Yield a few times, so other requests can arrive meanwhile.
+    async fn yield_many() {
+        for _ in 0..16 {
+            future::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn json_payload() {
+        let request = RpcMessage::Request {
+            id: 1,
+            method: String::from("add"),
+            params: RpcPayload(Format::Json.encode(&(1, 2)).unwrap().to_vec()),
+        };
+
+        let frame = Format::Json.encode(&request).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&frame).unwrap(),
+            r#"{"Request":{"id":1,"method":"add","params":[1,2]}}"#
+        );
+
+        assert_eq!(Format::Json.decode::<RpcMessage>(&frame).unwrap(), request);
+
+        // Binary formats keep payload as bytes.
+        for format in [Format::Cbor, Format::Bincode] {
+            let request = RpcMessage::Request {
+                id: 1,
+                method: String::from("add"),
+                params: RpcPayload(format.encode(&(1, 2)).unwrap().to_vec()),
+            };
+
+            let frame = format.encode(&request).unwrap();
+            assert_eq!(format.decode::<RpcMessage>(&frame).unwrap(), request);
+        }
+    }
+}
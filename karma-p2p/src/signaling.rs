@@ -0,0 +1,32 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Transport of addresses between p2p sockets before they are connected.
+pub trait Signaling<A> {
+    type Error;
+
+    /// Send local address to remote peer `remote`.
+    ///
+    /// When `Pending` is returned, sending resumes when polled again with same arguments.
+    fn poll_send(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        remote: &str,
+        addr: &A,
+    ) -> Poll<Result<(), Self::Error>>;
+
+    /// Receive address sent by remote peer `remote`, `None` means no more address.
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        remote: &str,
+    ) -> Poll<Result<Option<A>, Self::Error>>;
+}
+
+#[derive(Debug)]
+pub enum EstablishError<S, G> {
+    SocketError(S),
+    SignalingError(G),
+}
@@ -1,6 +1,7 @@
 // use async_trait::async_trait;
 
 use std::{
+    future::Future,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -15,15 +16,33 @@ pub trait P2pSocket: Sized {
 
     type Error;
 
+    /// Socket being created, polled by [`BindFuture`](crate::futures::BindFuture) until
+    /// ready.
+    type Binding: Future<Output = Result<Self, Self::Error>> + Unpin;
+
+    /// Stream being connected, polled by [`ConnectFuture`](crate::futures::ConnectFuture)
+    /// until ready.
+    type Connecting: Future<Output = Result<Self::Stream, Self::Error>> + Unpin;
+
+    /// Remote address being set, polled by [`SetRemoteAddr`](crate::futures::SetRemoteAddr)
+    /// until done.
+    type SettingRemoteAddr: Future<Output = Result<(), Self::Error>> + Unpin;
+
     /// Create p2p socket.
-    fn poll_bind(cx: &mut Context<'_>, bootstrap: Self::Addr) -> Poll<Result<Self, Self::Error>>;
+    ///
+    /// Bind in flight is kept by the returned future, so it resumes after `Pending`.
+    fn binding(bootstrap: Self::Addr) -> Self::Binding;
 
     /// Connect to remote p2p socket and get p2p stream.
-    fn poll_connect(
+    ///
+    /// Connection in flight is kept by the returned future, so every caller drives its
+    /// own, even with same `label` and `port`.
+    fn connecting(&self, label: Self::Addr, port: u16) -> Self::Connecting;
+
+    /// Accept p2p stream opened by remote p2p socket.
+    fn poll_accept(
         self: Pin<&Self>,
         cx: &mut Context<'_>,
-        label: Self::Addr,
-        port: u16,
     ) -> Poll<Result<Self::Stream, Self::Error>>;
 
     fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
@@ -35,9 +54,19 @@ pub trait P2pSocket: Sized {
     ) -> Poll<Result<Self::Addr, Self::Error>>;
 
     /// Set remote address.
-    fn poll_set_remote_addr(
-        self: Pin<&Self>,
-        cx: &mut Context<'_>,
-        remote: Self::Addr,
-    ) -> Poll<Result<(), Self::Error>>;
+    ///
+    /// Operation in flight is kept by the returned future. Remote addresses set
+    /// concurrently may be applied in any order, so set them one by one when order
+    /// matters.
+    fn setting_remote_addr(&self, remote: Self::Addr) -> Self::SettingRemoteAddr;
+
+    /// Check `addr` is the last address its side sends in a round, such as end of
+    /// candidates.
+    ///
+    /// Address exchange is complete once both sides sent their last address. Without
+    /// it, exchange completes only when signaling has no more address.
+    fn is_last_addr(addr: &Self::Addr) -> bool {
+        let _ = addr;
+        false
+    }
 }
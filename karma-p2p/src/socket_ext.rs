@@ -1,23 +1,27 @@
 use crate::{
-    futures::{BindFuture, ConnectFuture, FetchLocalAddrFuture, SetRemoteAddr, StartFuture},
+    futures::{
+        AcceptFuture, BindFuture, ConnectFuture, FetchLocalAddrFuture, SetRemoteAddr, StartFuture,
+    },
     P2pSocket,
 };
 
 pub trait P2pSocketExt: P2pSocket {
     fn bind(bootstrap: Self::Addr) -> BindFuture<Self> {
         BindFuture {
-            arg: Some(bootstrap),
+            binding: Self::binding(bootstrap),
         }
     }
 
-    fn connect(&self, label: Self::Addr, port: u16) -> ConnectFuture<'_, Self> {
+    fn connect(&self, label: Self::Addr, port: u16) -> ConnectFuture<Self> {
         ConnectFuture {
-            socket: self,
-            label: Some(label),
-            port,
+            connecting: self.connecting(label, port),
         }
     }
 
+    fn accept(&self) -> AcceptFuture<'_, Self> {
+        AcceptFuture { socket: self }
+    }
+
     fn start(&mut self) -> StartFuture<'_, Self> {
         StartFuture { socket: self }
     }
@@ -26,13 +30,142 @@ pub trait P2pSocketExt: P2pSocket {
         FetchLocalAddrFuture { socket: self }
     }
 
-    fn set_remote_addr(&self, remote: Self::Addr) -> SetRemoteAddr<'_, Self> {
+    fn set_remote_addr(&self, remote: Self::Addr) -> SetRemoteAddr<Self> {
         SetRemoteAddr {
-            socket: self,
-            remote: Some(remote),
+            setting: self.setting_remote_addr(remote),
         }
     }
 }
 
 impl<T: P2pSocket> P2pSocketExt for T {}
 
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        future::Future,
+        pin::Pin,
+        rc::Rc,
+        task::{Context, Poll},
+    };
+
+    use futures_lite::{
+        future::{self, block_on},
+        io::Cursor,
+    };
+
+    use crate::{P2pSocket, P2pSocketExt};
+
+    /// Socket which returns `Pending` once before each operation completes.
+    #[derive(Default)]
+    struct PendingSocket {
+        polled: Cell<bool>,
+        remotes: Rc<RefCell<Vec<u16>>>,
+    }
+
+    impl PendingSocket {
+        fn pending_once(&self, cx: &mut Context<'_>) -> Poll<()> {
+            if self.polled.replace(!self.polled.get()) {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Operation which returns `Pending` once before it runs.
+    struct PendingOnce<T> {
+        polled: bool,
+        op: Option<Box<dyn FnOnce() -> T>>,
+    }
+
+    impl<T> PendingOnce<T> {
+        fn new(op: impl FnOnce() -> T + 'static) -> Self {
+            Self {
+                polled: false,
+                op: Some(Box::new(op)),
+            }
+        }
+    }
+
+    impl<T> Future for PendingOnce<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+            let this = self.get_mut();
+
+            if !std::mem::replace(&mut this.polled, true) {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            Poll::Ready((this.op.take().unwrap())())
+        }
+    }
+
+    impl P2pSocket for PendingSocket {
+        type Stream = Cursor<Vec<u8>>;
+
+        type Addr = u16;
+
+        type Error = ();
+
+        type Binding = future::Ready<Result<Self, ()>>;
+
+        type Connecting = PendingOnce<Result<Self::Stream, ()>>;
+
+        type SettingRemoteAddr = PendingOnce<Result<(), ()>>;
+
+        fn binding(_bootstrap: u16) -> Self::Binding {
+            future::ready(Ok(Self::default()))
+        }
+
+        fn connecting(&self, label: u16, port: u16) -> Self::Connecting {
+            PendingOnce::new(move || Ok(Cursor::new(vec![label as u8, port as u8])))
+        }
+
+        fn poll_accept(self: Pin<&Self>, cx: &mut Context<'_>) -> Poll<Result<Self::Stream, ()>> {
+            self.pending_once(cx).map(|_| Ok(Cursor::new(Vec::new())))
+        }
+
+        fn poll_start(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            self.pending_once(cx).map(Ok)
+        }
+
+        fn poll_fetch_local_addr(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<u16, ()>> {
+            self.pending_once(cx).map(|_| Ok(7))
+        }
+
+        fn setting_remote_addr(&self, remote: u16) -> Self::SettingRemoteAddr {
+            let remotes = self.remotes.clone();
+
+            PendingOnce::new(move || {
+                remotes.borrow_mut().push(remote);
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn resume_after_pending() {
+        block_on(async {
+            let mut socket = PendingSocket::bind(0).await.unwrap();
+
+            socket.start().await.unwrap();
+            assert_eq!(socket.fetch_local_addr().await.unwrap(), 7);
+
+            socket.set_remote_addr(1).await.unwrap();
+            socket.set_remote_addr(2).await.unwrap();
+            assert_eq!(*socket.remotes.borrow(), vec![1, 2]);
+
+            let stream = socket.connect(3, 4).await.unwrap();
+            assert_eq!(stream.into_inner(), vec![3, 4]);
+
+            socket.accept().await.unwrap();
+        });
+    }
+}
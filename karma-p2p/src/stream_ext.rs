@@ -1,7 +1,41 @@
+use bytes::Bytes;
 use futures_lite::{AsyncReadExt, AsyncWrite};
 
-use crate::P2pStream;
+use crate::{
+    futures::{RecvFrameFuture, SendFrameFuture},
+    FrameReader, FrameWriter, P2pStream,
+};
 
-pub trait P2pStreamExt: P2pStream + AsyncReadExt + AsyncWrite {}
+pub trait P2pStreamExt: P2pStream + AsyncReadExt + AsyncWrite {
+    /// Send `frame` prefixed by varint length, fails if it's larger than `max_size`.
+    ///
+    /// Not cancel safe, dropping the future mid-frame leaves a partial frame on the
+    /// stream. Use [`Framed`](crate::Framed) when sends may be cancelled.
+    fn send_frame(&mut self, frame: Bytes, max_size: usize) -> SendFrameFuture<'_, Self> {
+        SendFrameFuture {
+            stream: self,
+            frame: Some(frame),
+            max_size,
+            writer: FrameWriter::default(),
+        }
+    }
+
+    /// Receive frame sent by `send_frame`, `None` means stream closed.
+    ///
+    /// Frames larger than `max_size` fail without reading their body, the stream
+    /// can't be used for frames afterwards.
+    ///
+    /// Not cancel safe, partly read frame is kept by the returned future, so dropping
+    /// it, as in `select` or a timeout, loses those bytes and breaks framing. Use
+    /// [`Framed`](crate::Framed), which keeps reader state on itself, when receives
+    /// may be cancelled.
+    fn recv_frame(&mut self, max_size: usize) -> RecvFrameFuture<'_, Self> {
+        RecvFrameFuture {
+            stream: self,
+            max_size,
+            reader: FrameReader::default(),
+        }
+    }
+}
 
 impl<T: P2pStream> P2pStreamExt for T {}
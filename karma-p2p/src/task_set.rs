@@ -0,0 +1,186 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Wake, Waker},
+};
+
+use futures_lite::{future::Boxed, FutureExt};
+
+#[derive(Default)]
+struct ReadyQueue {
+    ids: VecDeque<u64>,
+    waker: Option<Waker>,
+}
+
+struct TaskWaker {
+    id: u64,
+    queue: Arc<Mutex<ReadyQueue>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut queue = self.queue.lock().unwrap();
+
+        queue.ids.push_back(self.id);
+
+        if let Some(w) = queue.waker.take() {
+            drop(queue);
+            w.wake();
+        }
+    }
+}
+
+/// Futures keyed by id, like `FuturesUnordered` each is polled only after it woke.
+pub struct TaskSet<T> {
+    tasks: HashMap<u64, Boxed<T>>,
+    ready: Arc<Mutex<ReadyQueue>>,
+}
+
+impl<T> Default for TaskSet<T> {
+    fn default() -> Self {
+        Self {
+            tasks: HashMap::new(),
+            ready: Arc::default(),
+        }
+    }
+}
+
+impl<T> TaskSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Add `fu` as task `id`, replaces previous task of it.
+    pub fn insert(&mut self, id: u64, fu: Boxed<T>) {
+        self.tasks.insert(id, fu);
+        self.ready.lock().unwrap().ids.push_back(id);
+    }
+
+    /// Drop task `id`, returns whether it existed.
+    pub fn remove(&mut self, id: u64) -> bool {
+        self.tasks.remove(&id).is_some()
+    }
+
+    /// Poll woken tasks until one completes, `None` if set is empty.
+    ///
+    /// Tasks woken while polled are left to next call, so a task waking itself
+    /// can't starve caller.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<(u64, T)>> {
+        if self.tasks.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        let woken = std::mem::take(&mut self.ready.lock().unwrap().ids);
+
+        for (i, id) in woken.iter().enumerate() {
+            // Removed, or woken more than once.
+            let fu = match self.tasks.get_mut(id) {
+                Some(fu) => fu,
+                None => continue,
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id: *id,
+                queue: self.ready.clone(),
+            }));
+
+            if let Poll::Ready(output) = fu.poll(&mut Context::from_waker(&waker)) {
+                self.tasks.remove(id);
+
+                // Rest are polled by next call.
+                let mut ready = self.ready.lock().unwrap();
+                for id in woken.range(i + 1..).rev() {
+                    ready.ids.push_front(*id);
+                }
+
+                return Poll::Ready(Some((*id, output)));
+            }
+        }
+
+        let mut ready = self.ready.lock().unwrap();
+
+        if ready.ids.is_empty() {
+            ready.waker = Some(cx.waker().clone());
+        } else {
+            cx.waker().wake_by_ref();
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::Poll,
+    };
+
+    use futures_lite::{
+        future::{self, block_on},
+        FutureExt,
+    };
+
+    use super::TaskSet;
+
+    #[test]
+    fn polls_woken_tasks() {
+        block_on(async {
+            let polls = Arc::new(AtomicUsize::new(0));
+
+            let mut tasks = TaskSet::new();
+
+            for id in 0..8 {
+                let polls = polls.clone();
+                let mut first = true;
+
+                // Never woken after first poll, except task 3.
+                let fu = future::poll_fn(move |cx| {
+                    polls.fetch_add(1, Ordering::SeqCst);
+
+                    if id == 3 && first {
+                        first = false;
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+
+                    if id == 3 {
+                        Poll::Ready(id)
+                    } else {
+                        Poll::Pending
+                    }
+                });
+
+                tasks.insert(id, fu.boxed());
+            }
+
+            let (id, output) = future::poll_fn(|cx| tasks.poll_next(cx)).await.unwrap();
+            assert_eq!((id, output), (3, 3));
+            assert_eq!(polls.load(Ordering::SeqCst), 9);
+
+            // Others aren't polled again until woken.
+            let pending = future::poll_fn(|cx| Poll::Ready(tasks.poll_next(cx))).await;
+            assert!(pending.is_pending());
+            assert_eq!(polls.load(Ordering::SeqCst), 9);
+
+            assert!(tasks.remove(0));
+            assert!(!tasks.remove(3));
+            assert_eq!(tasks.len(), 6);
+        });
+    }
+}
@@ -0,0 +1,335 @@
+//! Conformance checks for [`P2pSocket`] implementations.
+//!
+//! Each check binds fresh sockets through [`Harness`] and panics when the socket
+//! breaks the contract. Checks are plain futures, so backends run them on their own
+//! runtime, usually with a timeout.
+
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+
+use crate::{P2pSocket, P2pSocketExt, P2pStream};
+
+/// Max size of single write in checks, larger payloads are chunked.
+pub const CHUNK_SIZE: usize = 16 * 1024;
+
+/// Payload sizes used by [`check_round_trip`].
+pub const ROUND_TRIP_SIZES: &[usize] = &[1, 100, 1024, CHUNK_SIZE, 256 * 1024 + 7];
+
+/// Describe how to build and inspect sockets of a backend.
+pub trait Harness {
+    type Socket: P2pSocket<Stream = Self::Stream, Addr = Self::Addr, Error = Self::Error> + Unpin;
+
+    type Stream: P2pStream + Unpin;
+
+    type Addr: Clone + Debug + Unpin;
+
+    type Error: Debug;
+
+    /// Address used to bind a new socket.
+    fn bootstrap(&self) -> Self::Addr;
+
+    /// Label address used to connect.
+    fn label(&self, name: &str) -> Self::Addr;
+
+    /// Address of variant rejected by `connect` and `set_remote_addr`.
+    fn wrong_addr(&self) -> Self::Addr;
+
+    /// Check address is offer.
+    fn is_offer(&self, addr: &Self::Addr) -> bool;
+
+    /// Check address is answer.
+    fn is_answer(&self, addr: &Self::Addr) -> bool;
+
+    /// Check address is candidate, backends without candidates never produce one.
+    fn is_candidate(&self, _addr: &Self::Addr) -> bool {
+        false
+    }
+
+    /// Check error is `ErrAddrType` of backend.
+    fn is_addr_type_error(&self, err: &Self::Error) -> bool;
+}
+
+/// Run all checks one by one.
+pub async fn check_all<H: Harness>(harness: &H) {
+    check_addr_type(harness).await;
+    check_offer_answer(harness).await;
+    check_candidates(harness).await;
+    check_round_trip(harness).await;
+    check_close(harness).await;
+}
+
+async fn bind<H: Harness>(harness: &H) -> H::Socket {
+    H::Socket::bind(harness.bootstrap())
+        .await
+        .expect("bind socket")
+}
+
+/// `connect` and `set_remote_addr` reject wrong address variant with `ErrAddrType`.
+pub async fn check_addr_type<H: Harness>(harness: &H) {
+    let socket = bind(harness).await;
+
+    match socket.connect(harness.wrong_addr(), 1).await {
+        Err(e) => assert!(harness.is_addr_type_error(&e), "connect: {:?}", e),
+        Ok(_) => panic!("connect accepted wrong address variant"),
+    }
+
+    match socket.set_remote_addr(harness.wrong_addr()).await {
+        Err(e) => assert!(harness.is_addr_type_error(&e), "set_remote_addr: {:?}", e),
+        Ok(_) => panic!("set_remote_addr accepted wrong address variant"),
+    }
+}
+
+/// Initiator produces offer first, answerer produces answer first after offer is set.
+pub async fn check_offer_answer<H: Harness>(harness: &H) {
+    let mut a = bind(harness).await;
+    let mut b = bind(harness).await;
+
+    // Make sure there is something to negotiate.
+    let _sa = a
+        .connect(harness.label("offer-answer"), 1)
+        .await
+        .expect("connect");
+    let _sb = b
+        .connect(harness.label("offer-answer"), 1)
+        .await
+        .expect("connect");
+
+    a.start().await.expect("start");
+
+    let offer = a.fetch_local_addr().await.expect("fetch offer");
+    assert!(harness.is_offer(&offer), "first addr: {:?}", offer);
+
+    b.set_remote_addr(offer).await.expect("set offer");
+
+    let answer = b.fetch_local_addr().await.expect("fetch answer");
+    assert!(harness.is_answer(&answer), "first addr: {:?}", answer);
+
+    a.set_remote_addr(answer).await.expect("set answer");
+}
+
+/// After offer and answer, `fetch_local_addr` yields only candidates up to the last
+/// address, and connection works once they are delivered.
+pub async fn check_candidates<H: Harness>(harness: &H) {
+    let mut a = bind(harness).await;
+    let mut b = bind(harness).await;
+
+    let mut sa = a
+        .connect(harness.label("candidates"), 1)
+        .await
+        .expect("connect");
+    let mut sb = b
+        .connect(harness.label("candidates"), 1)
+        .await
+        .expect("connect");
+
+    a.start().await.expect("start");
+
+    let offer = a.fetch_local_addr().await.expect("fetch offer");
+    assert!(harness.is_offer(&offer), "first addr: {:?}", offer);
+    let a_last = H::Socket::is_last_addr(&offer);
+
+    b.set_remote_addr(offer).await.expect("set offer");
+
+    let answer = b.fetch_local_addr().await.expect("fetch answer");
+    assert!(harness.is_answer(&answer), "first addr: {:?}", answer);
+    let b_last = H::Socket::is_last_addr(&answer);
+
+    a.set_remote_addr(answer).await.expect("set answer");
+
+    deliver_candidates(harness, &mut a, &b, a_last).await;
+    deliver_candidates(harness, &mut b, &a, b_last).await;
+
+    sa.write_all(b"ping").await.expect("write");
+    sa.flush().await.expect("flush");
+
+    let mut buf = [0u8; 4];
+    sb.read_exact(&mut buf).await.expect("read");
+    assert_eq!(&buf, b"ping");
+}
+
+async fn deliver_candidates<H: Harness>(
+    harness: &H,
+    from: &mut H::Socket,
+    to: &H::Socket,
+    mut last: bool,
+) {
+    while !last {
+        let addr = from.fetch_local_addr().await.expect("fetch candidate");
+
+        last = H::Socket::is_last_addr(&addr);
+
+        assert!(
+            last || harness.is_candidate(&addr),
+            "addr after description: {:?}",
+            addr
+        );
+
+        to.set_remote_addr(addr).await.expect("set candidate");
+    }
+}
+
+/// Bytes written on one side are read exactly on the other, in both directions.
+pub async fn check_round_trip<H: Harness>(harness: &H) {
+    let mut a = bind(harness).await;
+    let mut b = bind(harness).await;
+
+    let mut sa = a
+        .connect(harness.label("round-trip"), 1)
+        .await
+        .expect("connect");
+    let mut sb = b
+        .connect(harness.label("round-trip"), 1)
+        .await
+        .expect("connect");
+
+    let test = async {
+        for (i, size) in ROUND_TRIP_SIZES.iter().enumerate() {
+            let data: Vec<u8> = (0..*size).map(|n| (n + i) as u8).collect();
+
+            let mut out = vec![0u8; data.len()];
+
+            // Read while writing, backends with flow control stall on payloads
+            // larger than their window until remote reads.
+            future::zip(write_chunked(&mut sa, &data), async {
+                sb.read_exact(&mut out).await.expect("read a to b")
+            })
+            .await;
+            assert!(out == data, "a to b mismatch at size {}", size);
+
+            future::zip(write_chunked(&mut sb, &data), async {
+                sa.read_exact(&mut out).await.expect("read b to a")
+            })
+            .await;
+            assert!(out == data, "b to a mismatch at size {}", size);
+        }
+    };
+
+    future::or(test, exchange(&mut a, &mut b)).await;
+}
+
+/// Closing one side is reported as EOF on the other after pending data.
+pub async fn check_close<H: Harness>(harness: &H) {
+    let mut a = bind(harness).await;
+    let mut b = bind(harness).await;
+
+    let mut sa = a.connect(harness.label("close"), 1).await.expect("connect");
+    let mut sb = b.connect(harness.label("close"), 1).await.expect("connect");
+
+    let test = async {
+        sa.write_all(b"bye").await.expect("write");
+        sa.flush().await.expect("flush");
+
+        let mut buf = [0u8; 3];
+        sb.read_exact(&mut buf).await.expect("read");
+        assert_eq!(&buf, b"bye");
+
+        sa.close().await.expect("close");
+
+        let mut rest = Vec::new();
+        sb.read_to_end(&mut rest).await.expect("read to eof");
+        assert!(rest.is_empty());
+    };
+
+    future::or(test, exchange(&mut a, &mut b)).await;
+}
+
+async fn write_chunked<W: futures_lite::AsyncWrite + Unpin>(w: &mut W, data: &[u8]) {
+    for chunk in data.chunks(CHUNK_SIZE) {
+        w.write_all(chunk).await.expect("write");
+    }
+
+    w.flush().await.expect("flush");
+}
+
+/// Start `a` and forward every local address to the other socket, never finishes.
+///
+/// Every fetched address, including candidates, must be accepted by remote.
+pub async fn exchange<S>(a: &mut S, b: &mut S)
+where
+    S: P2pSocket + Unpin,
+    S::Addr: Clone + Debug + Unpin,
+    S::Error: Debug,
+{
+    a.start().await.expect("start");
+
+    Exchange {
+        a,
+        b,
+        a_to_b: None,
+        b_to_a: None,
+    }
+    .await
+}
+
+struct Exchange<'a, S: P2pSocket> {
+    a: &'a mut S,
+    b: &'a mut S,
+    // Address being set on remote, kept until done.
+    a_to_b: Option<(S::Addr, S::SettingRemoteAddr)>,
+    b_to_a: Option<(S::Addr, S::SettingRemoteAddr)>,
+}
+
+impl<S: P2pSocket> Unpin for Exchange<'_, S> {}
+
+fn forward<S>(
+    cx: &mut Context<'_>,
+    from: &mut S,
+    to: &S,
+    slot: &mut Option<(S::Addr, S::SettingRemoteAddr)>,
+) -> bool
+where
+    S: P2pSocket + Unpin,
+    S::Addr: Clone + Debug,
+    S::Error: Debug,
+{
+    let mut progress = false;
+
+    if slot.is_none() {
+        match Pin::new(&mut *from).poll_fetch_local_addr(cx) {
+            Poll::Ready(Ok(addr)) => {
+                *slot = Some((addr.clone(), to.setting_remote_addr(addr)));
+                progress = true;
+            }
+            Poll::Ready(Err(e)) => panic!("fetch_local_addr: {:?}", e),
+            Poll::Pending => return false,
+        }
+    }
+
+    if let Some((addr, setting)) = slot.as_mut() {
+        match Pin::new(setting).poll(cx) {
+            Poll::Ready(Ok(())) => {
+                *slot = None;
+                progress = true;
+            }
+            Poll::Ready(Err(e)) => panic!("set_remote_addr {:?}: {:?}", addr, e),
+            Poll::Pending => {}
+        }
+    }
+
+    progress
+}
+
+impl<S> Future for Exchange<'_, S>
+where
+    S: P2pSocket + Unpin,
+    S::Addr: Clone + Debug,
+    S::Error: Debug,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+
+        while forward(cx, this.a, this.b, &mut this.a_to_b)
+            | forward(cx, this.b, this.a, &mut this.b_to_a)
+        {}
+
+        Poll::Pending
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{Discovery, P2pSocket, P2pSocketEvents};
+
+/// IO used by [`Node`](crate::Node), keeps core free of runtime.
+///
+/// When `Pending` is returned, operation is kept in flight and resumed when polled
+/// again with same arguments.
+pub trait NodeTransport {
+    /// Socket to peer, backends without connection state report no events.
+    type Socket: P2pSocket + P2pSocketEvents + Unpin;
+
+    type Discovery: Discovery<<Self::Socket as P2pSocket>::Addr> + Unpin;
+
+    /// Serve forwarding on listened address until it is closed.
+    type Listener: Future<Output = Result<(), Self::Error>> + Unpin;
+
+    type Error;
+
+    /// Connect to bootstrap node.
+    fn poll_dial(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        addr: &SocketAddr,
+    ) -> Poll<Result<Self::Discovery, Self::Error>>;
+
+    /// Listen for forwarding.
+    fn poll_listen(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        addr: &SocketAddr,
+    ) -> Poll<Result<Self::Listener, Self::Error>>;
+
+    /// Create socket to `remote`, streams needed by both sides should be connected here.
+    fn poll_bind(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        remote: &str,
+    ) -> Poll<Result<Self::Socket, Self::Error>>;
+}
@@ -0,0 +1,299 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    futures::{CloseFramedFuture, RecvItemFuture, SendItemFuture},
+    Codec, FrameError, Framed, P2pStream,
+};
+
+/// Encoding of values sent by `TypedStream`, a variant exists for each enabled
+/// feature of `cbor`, `bincode` and `json`.
+///
+/// There is no default, both sides must pick the same one whatever features are
+/// enabled elsewhere in the build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[cfg(feature = "cbor")]
+    Cbor,
+
+    #[cfg(feature = "bincode")]
+    Bincode,
+
+    #[cfg(feature = "json")]
+    Json,
+}
+
+#[derive(Debug)]
+pub enum TypedError {
+    ErrEncode(String),
+    ErrDecode(String),
+    FrameError(FrameError),
+}
+
+impl fmt::Display for TypedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypedError::ErrEncode(e) => write!(f, "encode value failed: {}", e),
+            TypedError::ErrDecode(e) => write!(f, "decode value failed: {}", e),
+            TypedError::FrameError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TypedError {}
+
+impl From<FrameError> for TypedError {
+    fn from(e: FrameError) -> Self {
+        TypedError::FrameError(e)
+    }
+}
+
+impl Format {
+    /// Encode `item` as one frame.
+    pub fn encode<T: Serialize + ?Sized>(&self, item: &T) -> Result<Bytes, TypedError> {
+        let encode = |e: &dyn fmt::Display| TypedError::ErrEncode(e.to_string());
+
+        let buf = match self {
+            #[cfg(feature = "cbor")]
+            Format::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(item, &mut buf).map_err(|e| encode(&e))?;
+                buf
+            }
+
+            #[cfg(feature = "bincode")]
+            Format::Bincode => bincode::serialize(item).map_err(|e| encode(&e))?,
+
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_vec(item).map_err(|e| encode(&e))?,
+        };
+
+        Ok(Bytes::from(buf))
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, frame: &[u8]) -> Result<T, TypedError> {
+        let decode = |e: &dyn fmt::Display| TypedError::ErrDecode(e.to_string());
+
+        match self {
+            #[cfg(feature = "cbor")]
+            Format::Cbor => ciborium::from_reader(frame).map_err(|e| decode(&e)),
+
+            #[cfg(feature = "bincode")]
+            Format::Bincode => bincode::deserialize(frame).map_err(|e| decode(&e)),
+
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::from_slice(frame).map_err(|e| decode(&e)),
+        }
+    }
+}
+
+/// Codec of serde values, one value per frame.
+pub struct SerdeCodec<T> {
+    format: Format,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> SerdeCodec<T> {
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            _value: PhantomData,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Codec for SerdeCodec<T> {
+    type Item = T;
+
+    type Error = TypedError;
+
+    fn encode(&mut self, item: T) -> Result<Bytes, TypedError> {
+        self.format.encode(&item)
+    }
+
+    fn decode(&mut self, frame: Bytes) -> Result<T, TypedError> {
+        self.format.decode(&frame)
+    }
+}
+
+/// Whole values of `T` sent over `P2pStream`.
+///
+/// Each value is one frame of `Framed`, so both sides must use the same `Format`.
+pub struct TypedStream<S, T> {
+    framed: Framed<S, SerdeCodec<T>>,
+}
+
+impl<S, T> TypedStream<S, T>
+where
+    S: P2pStream + Unpin,
+    T: Serialize + DeserializeOwned,
+{
+    pub fn new(stream: S, format: Format) -> Self {
+        Self {
+            framed: Framed::new(stream, SerdeCodec::new(format)),
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.framed.codec().format()
+    }
+
+    /// Largest encoded value, `MAX_FRAME_SIZE` by default.
+    pub fn set_max_frame_size(&mut self, size: usize) {
+        self.framed.set_max_frame_size(size)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.framed.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        self.framed.get_mut()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.framed.into_inner()
+    }
+
+    /// Receive next value, `None` means stream closed.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T, TypedError>>> {
+        self.framed.poll_recv(cx)
+    }
+
+    /// Wait until previous value is written.
+    pub fn poll_send_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TypedError>> {
+        self.framed.poll_send_ready(cx)
+    }
+
+    /// Begin send value. Must be called after `poll_send_ready` returned `Ready(Ok(()))`.
+    pub fn start_send(&mut self, value: T) -> Result<(), TypedError> {
+        self.framed.start_send(value)
+    }
+
+    pub fn poll_send_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TypedError>> {
+        self.framed.poll_send_flush(cx)
+    }
+
+    pub fn poll_send_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TypedError>> {
+        self.framed.poll_send_close(cx)
+    }
+
+    pub fn recv(&mut self) -> RecvItemFuture<'_, S, SerdeCodec<T>> {
+        self.framed.recv()
+    }
+
+    pub fn send(&mut self, value: T) -> SendItemFuture<'_, S, SerdeCodec<T>> {
+        self.framed.send(value)
+    }
+
+    pub fn close(&mut self) -> CloseFramedFuture<'_, S, SerdeCodec<T>> {
+        self.framed.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures_lite::{future::block_on, io::Cursor};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Format, FrameError, P2pStreamExt, TypedError, TypedStream};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Message {
+        Hello { name: String },
+        Data(Vec<u8>),
+        Bye,
+    }
+
+    fn formats() -> Vec<Format> {
+        vec![
+            #[cfg(feature = "cbor")]
+            Format::Cbor,
+            #[cfg(feature = "bincode")]
+            Format::Bincode,
+            #[cfg(feature = "json")]
+            Format::Json,
+        ]
+    }
+
+    #[test]
+    fn values_round_trip() {
+        block_on(async {
+            for format in formats() {
+                let mut typed = TypedStream::new(Cursor::new(Vec::new()), format);
+
+                let messages = [
+                    Message::Hello {
+                        name: String::from("alice"),
+                    },
+                    Message::Data(vec![1, 2, 3]),
+                    Message::Bye,
+                ];
+
+                for m in messages {
+                    typed.send(m).await.unwrap();
+                }
+
+                typed.close().await.unwrap();
+
+                let mut stream = typed.into_inner();
+                stream.set_position(0);
+
+                let mut typed = TypedStream::<_, Message>::new(stream, format);
+
+                assert_eq!(
+                    typed.recv().await.unwrap().unwrap(),
+                    Message::Hello {
+                        name: String::from("alice")
+                    }
+                );
+                assert_eq!(
+                    typed.recv().await.unwrap().unwrap(),
+                    Message::Data(vec![1, 2, 3])
+                );
+                assert_eq!(typed.recv().await.unwrap().unwrap(), Message::Bye);
+                assert!(typed.recv().await.is_none());
+            }
+        });
+    }
+
+    #[test]
+    fn invalid_values() {
+        block_on(async {
+            for format in formats() {
+                let mut stream = Cursor::new(Vec::new());
+                stream
+                    .send_frame(Bytes::from_static(&[0xff, 0xff]), 16)
+                    .await
+                    .unwrap();
+                stream.set_position(0);
+
+                let mut typed = TypedStream::<_, Message>::new(stream, format);
+
+                assert!(matches!(
+                    typed.recv().await,
+                    Some(Err(TypedError::ErrDecode(_)))
+                ));
+
+                let mut typed = TypedStream::new(Cursor::new(Vec::new()), format);
+                typed.set_max_frame_size(4);
+
+                assert!(matches!(
+                    typed.send(Message::Data(vec![0; 16])).await,
+                    Err(TypedError::FrameError(FrameError::ErrFrameTooLarge(_)))
+                ));
+            }
+        });
+    }
+}
@@ -0,0 +1,32 @@
+use std::{
+    sync::{Arc, Mutex},
+    task::{Wake, Waker},
+};
+
+/// Wakes every task waiting on underlying stream, whichever runs first drives it.
+#[derive(Default)]
+pub(crate) struct WakerSet(Mutex<Vec<Waker>>);
+
+impl WakerSet {
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut wakers = self.0.lock().unwrap();
+
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+impl Wake for WakerSet {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let wakers = std::mem::take(&mut *self.0.lock().unwrap());
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Talk to another peer through a relay.
+//!
+//! Run signaling server with relay, then two peers in other terminals:
+//!
+//! ```text
+//! karma-signal --tcp 127.0.0.1:7100 --relay 127.0.0.1:7102
+//! cargo run --example relay_peer -- 127.0.0.1:7100 127.0.0.1:7102
+//! cargo run --example relay_peer -- 127.0.0.1:7100 127.0.0.1:7102
+//! ```
+//!
+//! Each peer is named by the peer id of a new keypair and talks to first other peer
+//! in the room.
+
+use futures_lite::{future, io::BufReader, AsyncBufReadExt, AsyncWriteExt};
+use karma_p2p::{establish, Keypair, P2pSocketExt};
+use karma_signal::{RelayAddr, RelaySocket, Result, ServerMessage, SignalClient};
+
+const USAGE: &str = "Usage: relay_peer SIGNAL RELAY";
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let (signal, relay) = match args.as_slice() {
+        [signal, relay] => (signal, relay),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let relay = relay.parse().expect("relay address");
+
+    let keypair = Keypair::generate();
+    let local = keypair.peer_id().to_string();
+
+    smol::block_on(async {
+        let mut signaling = SignalClient::<RelayAddr>::connect(signal.as_str(), &local).await?;
+
+        // Signals are only delivered to registered peers, wait for remote first.
+        let remote = match signaling.join("relay").await?.pop() {
+            Some(remote) => remote,
+            None => loop {
+                if let ServerMessage::PeerJoined { peer, .. } = signaling.next_event().await? {
+                    break peer;
+                }
+            },
+        };
+
+        let bootstrap = RelayAddr::Bootstrap { relay, keypair };
+
+        let mut socket = RelaySocket::bind(bootstrap).await?;
+
+        let label = RelayAddr::Label(String::from("chat"));
+        let mut stream = socket.connect(label, 1).await?;
+
+        let chat = async {
+            let line = format!("hello from {}\n", local);
+
+            stream.write_all(line.as_bytes()).await?;
+            stream.flush().await?;
+
+            let mut line = String::new();
+            BufReader::new(&mut stream).read_line(&mut line).await?;
+
+            println!("{:?}: {}", stream.path(), line.trim_end());
+
+            Ok(())
+        };
+
+        let exchange = async {
+            establish(&mut socket, &mut signaling, &remote, local < remote)
+                .await
+                .map_err(|e| karma_signal::Error::ServerError(format!("{:?}", e)))?;
+
+            future::pending().await
+        };
+
+        future::or(chat, exchange).await
+    })
+}
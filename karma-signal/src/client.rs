@@ -0,0 +1,488 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_channel::{bounded, Receiver, Sender, TrySendError};
+use futures_lite::{ready, StreamExt};
+use karma_p2p::{Discovery, PeerEvent, Signaling};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+#[cfg(not(target_arch = "wasm32"))]
+use async_tungstenite::tungstenite::{
+    client::IntoClientRequest, error::UrlError, Error as WsError, Message,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use futures_lite::{io::BufReader, AsyncWriteExt};
+#[cfg(not(target_arch = "wasm32"))]
+use futures_util::SinkExt;
+#[cfg(not(target_arch = "wasm32"))]
+use smol::{
+    net::{AsyncToSocketAddrs, TcpStream},
+    Task,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{relay::read_capped_line, server::ws_config, MAX_MESSAGE_SIZE};
+use crate::{ClientMessage, Error, Result, ServerMessage, MAX_QUEUED_MESSAGES};
+
+#[cfg(not(target_arch = "wasm32"))]
+type Io = Vec<Task<Result<()>>>;
+
+#[cfg(target_arch = "wasm32")]
+type Io = crate::WebSocketIo;
+
+/// Client of signaling server over tcp or websocket, relays addresses of type `A`.
+///
+/// Only websocket is available in browser.
+pub struct SignalClient<A> {
+    peer: String,
+    out_tx: Sender<ClientMessage>,
+    in_rx: Receiver<ServerMessage>,
+    signals: HashMap<String, VecDeque<Value>>,
+    events: VecDeque<ServerMessage>,
+    // Peers found by `join`, reported by discovery before membership changes.
+    joined: VecDeque<String>,
+    left: HashSet<String>,
+    _io: Io,
+    _marker: PhantomData<fn() -> A>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<A> SignalClient<A> {
+    /// Connect to server at `addr` and register as `peer`.
+    pub async fn connect(addr: impl AsyncToSocketAddrs, peer: &str) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+
+        let (out_tx, out_rx) = bounded::<ClientMessage>(MAX_QUEUED_MESSAGES);
+        let (in_tx, in_rx) = bounded(MAX_QUEUED_MESSAGES);
+
+        let mut writer = stream.clone();
+
+        let write_task = smol::spawn(async move {
+            while let Ok(message) = out_rx.recv().await {
+                let mut line = serde_json::to_string(&message)?;
+                line.push('\n');
+
+                writer.write_all(line.as_bytes()).await?;
+            }
+
+            Ok(())
+        });
+
+        let read_task = smol::spawn(async move {
+            let mut reader = BufReader::new(stream);
+
+            loop {
+                let line = match read_capped_line(&mut reader, MAX_MESSAGE_SIZE).await {
+                    Err(Error::ErrChannelClosed) => return Ok(()),
+                    line => line?,
+                };
+
+                let message: ServerMessage = serde_json::from_str(&line)?;
+
+                // Server is not read until there is room, it drops this client if it
+                // stays full.
+                if in_tx.send(message).await.is_err() {
+                    return Ok(());
+                }
+            }
+        });
+
+        Self::register(peer, out_tx, in_rx, vec![write_task, read_task]).await
+    }
+
+    /// Connect to websocket server at `url` such as `ws://127.0.0.1:7101` and register
+    /// as `peer`.
+    pub async fn connect_ws(url: &str, peer: &str) -> Result<Self> {
+        let request = url.into_client_request()?;
+
+        let uri = request.uri();
+
+        if uri.scheme_str() != Some("ws") {
+            return Err(WsError::Url(UrlError::UnsupportedUrlScheme).into());
+        }
+
+        let host = uri.host().ok_or(WsError::Url(UrlError::NoHostName))?;
+        let port = uri.port_u16().unwrap_or(80);
+
+        let stream = TcpStream::connect((host, port)).await?;
+
+        let (ws, _) =
+            async_tungstenite::client_async_with_config(request, stream, Some(ws_config())).await?;
+
+        let (mut sink, mut stream) = futures_util::StreamExt::split(ws);
+
+        let (out_tx, out_rx) = bounded::<ClientMessage>(MAX_QUEUED_MESSAGES);
+        let (in_tx, in_rx) = bounded(MAX_QUEUED_MESSAGES);
+
+        let write_task = smol::spawn(async move {
+            while let Ok(message) = out_rx.recv().await {
+                let text = serde_json::to_string(&message)?;
+
+                sink.send(Message::Text(text)).await?;
+            }
+
+            Ok(())
+        });
+
+        let read_task = smol::spawn(async move {
+            while let Some(message) = stream.next().await {
+                let text = match message? {
+                    Message::Text(text) => text,
+                    Message::Close(_) => break,
+                    _ => continue,
+                };
+
+                let message: ServerMessage = serde_json::from_str(&text)?;
+
+                if in_tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+
+            Ok(())
+        });
+
+        Self::register(peer, out_tx, in_rx, vec![write_task, read_task]).await
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<A> SignalClient<A> {
+    /// Connect to websocket server at `url` such as `ws://127.0.0.1:7101` and register
+    /// as `peer`.
+    pub async fn connect_ws(url: &str, peer: &str) -> Result<Self> {
+        let (io, out_tx, in_rx) = crate::WebSocketIo::open(url).await?;
+
+        Self::register(peer, out_tx, in_rx, io).await
+    }
+}
+
+impl<A> SignalClient<A> {
+    async fn register(
+        peer: &str,
+        out_tx: Sender<ClientMessage>,
+        in_rx: Receiver<ServerMessage>,
+        io: Io,
+    ) -> Result<Self> {
+        let mut client = Self {
+            peer: String::from(peer),
+            out_tx,
+            in_rx,
+            signals: HashMap::new(),
+            events: VecDeque::new(),
+            joined: VecDeque::new(),
+            left: HashSet::new(),
+            _io: io,
+            _marker: PhantomData,
+        };
+
+        client.send(ClientMessage::Register {
+            peer: String::from(peer),
+        })?;
+
+        loop {
+            match client.in_rx.recv().await? {
+                ServerMessage::Registered { .. } => return Ok(client),
+                ServerMessage::Error { message } => return Err(Error::ServerError(message)),
+                m => client.dispatch(m),
+            }
+        }
+    }
+
+    pub fn peer_id(&self) -> &str {
+        &self.peer
+    }
+
+    fn send(&self, message: ClientMessage) -> Result<()> {
+        self.out_tx.try_send(message).map_err(|e| match e {
+            TrySendError::Full(_) => Error::ErrQueueFull,
+            TrySendError::Closed(_) => Error::ErrChannelClosed,
+        })
+    }
+
+    fn dispatch(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::Signal { from, data, .. } => {
+                self.signals.entry(from).or_default().push_back(data)
+            }
+            m => {
+                match &m {
+                    ServerMessage::PeerJoined { peer, .. } => {
+                        self.left.remove(peer);
+                    }
+                    ServerMessage::PeerLeft { peer, .. } | ServerMessage::Disconnected { peer } => {
+                        self.left.insert(peer.clone());
+                    }
+                    ServerMessage::Error { message } => {
+                        log::warn!("Signaling server error: {}", message);
+                    }
+                    _ => {}
+                }
+
+                self.events.push_back(m);
+            }
+        }
+    }
+
+    /// Receive one message from server and dispatch it, `false` means connection is closed.
+    ///
+    /// Message may be for another remote or discovery polled in same task, so task is
+    /// woken to let them see it.
+    fn poll_dispatch(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        match ready!(self.in_rx.poll_next(cx)) {
+            Some(m) => {
+                self.dispatch(m);
+                cx.waker().wake_by_ref();
+                Poll::Ready(true)
+            }
+            None => Poll::Ready(false),
+        }
+    }
+
+    /// Join `room` and get peers already in it.
+    pub async fn join(&mut self, room: &str) -> Result<Vec<String>> {
+        self.send(ClientMessage::Join {
+            room: String::from(room),
+        })?;
+
+        loop {
+            match self.in_rx.recv().await? {
+                ServerMessage::Peers { room: r, peers } if r == room => {
+                    self.joined.extend(peers.iter().cloned());
+                    return Ok(peers);
+                }
+                ServerMessage::Error { message } => return Err(Error::ServerError(message)),
+                m => self.dispatch(m),
+            }
+        }
+    }
+
+    pub fn leave(&mut self, room: &str) -> Result<()> {
+        self.send(ClientMessage::Leave {
+            room: String::from(room),
+        })
+    }
+
+    /// Wait for next message which is not a signal, such as room membership changes.
+    pub async fn next_event(&mut self) -> Result<ServerMessage> {
+        loop {
+            if let Some(m) = self.events.pop_front() {
+                return Ok(m);
+            }
+
+            let message = self.in_rx.recv().await?;
+
+            self.dispatch(message);
+        }
+    }
+}
+
+impl<A: Serialize> SignalClient<A> {
+    /// Send `addr` to all other peers in `room`.
+    pub fn broadcast(&self, room: &str, addr: &A) -> Result<()> {
+        self.send(ClientMessage::Broadcast {
+            room: String::from(room),
+            data: serde_json::to_value(addr)?,
+        })
+    }
+}
+
+impl<A> Signaling<A> for SignalClient<A>
+where
+    A: Serialize + DeserializeOwned,
+{
+    type Error = Error;
+
+    fn poll_send(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        remote: &str,
+        addr: &A,
+    ) -> Poll<Result<()>> {
+        let res = serde_json::to_value(addr)
+            .map_err(Error::from)
+            .and_then(|data| {
+                self.send(ClientMessage::Signal {
+                    to: String::from(remote),
+                    data,
+                })
+            });
+
+        Poll::Ready(res)
+    }
+
+    fn poll_recv(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        remote: &str,
+    ) -> Poll<Result<Option<A>>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(data) = this.signals.get_mut(remote).and_then(|q| q.pop_front()) {
+                return Poll::Ready(serde_json::from_value(data).map(Some).map_err(Error::from));
+            }
+
+            if this.left.contains(remote) {
+                return Poll::Ready(Ok(None));
+            }
+
+            if !ready!(this.poll_dispatch(cx)) {
+                return Poll::Ready(Ok(None));
+            }
+        }
+    }
+}
+
+impl<A> Discovery<A> for SignalClient<A>
+where
+    A: Serialize + DeserializeOwned,
+{
+    fn poll_next_peer(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<PeerEvent>>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(peer) = this.joined.pop_front() {
+                return Poll::Ready(Ok(Some(PeerEvent::Joined(peer))));
+            }
+
+            let pos = this.events.iter().position(|m| {
+                matches!(
+                    m,
+                    ServerMessage::PeerJoined { .. } | ServerMessage::PeerLeft { .. }
+                )
+            });
+
+            match pos.and_then(|i| this.events.remove(i)) {
+                Some(ServerMessage::PeerJoined { peer, .. }) => {
+                    return Poll::Ready(Ok(Some(PeerEvent::Joined(peer))))
+                }
+                Some(ServerMessage::PeerLeft { peer, .. }) => {
+                    return Poll::Ready(Ok(Some(PeerEvent::Left(peer))))
+                }
+                _ => {}
+            }
+
+            if !ready!(this.poll_dispatch(cx)) {
+                return Poll::Ready(Ok(None));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{pin::Pin, time::Duration};
+
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+    use karma_p2p::{establish, P2pSocketExt, Signaling};
+    use karma_p2p_webrtc::{WebrtcAddr, WebrtcSocket};
+    use serde_json::{json, Value};
+    use smol::net::TcpListener;
+
+    use crate::{Error, Server, SignalClient};
+
+    #[test]
+    fn websocket_client() {
+        future::block_on(async {
+            let server = Server::new();
+
+            let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let ws = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+            let (tcp_addr, ws_addr) = (tcp.local_addr().unwrap(), ws.local_addr().unwrap());
+
+            let s = server.clone();
+            smol::spawn(async move { s.serve_tcp(tcp).await }).detach();
+            smol::spawn(async move { server.serve_ws(ws).await }).detach();
+
+            let url = format!("ws://{}", ws_addr);
+
+            let mut alice = SignalClient::<Value>::connect_ws(&url, "alice")
+                .await
+                .unwrap();
+            let mut bob = SignalClient::<Value>::connect(tcp_addr, "bob")
+                .await
+                .unwrap();
+
+            assert!(alice.join("room").await.unwrap().is_empty());
+            assert_eq!(bob.join("room").await.unwrap(), vec!["alice"]);
+
+            let offer = json!({"SDP": "offer"});
+            future::poll_fn(|cx| Pin::new(&mut alice).poll_send(cx, "bob", &offer))
+                .await
+                .unwrap();
+
+            let recv = future::poll_fn(|cx| Pin::new(&mut bob).poll_recv(cx, "alice")).await;
+            assert_eq!(recv.unwrap(), Some(offer));
+
+            bob.broadcast("room", &json!("answer")).unwrap();
+
+            let recv = future::poll_fn(|cx| Pin::new(&mut alice).poll_recv(cx, "bob")).await;
+            assert_eq!(recv.unwrap(), Some(json!("answer")));
+
+            let wss = SignalClient::<Value>::connect_ws("wss://127.0.0.1:1", "carol").await;
+            assert!(matches!(wss, Err(Error::WsError(_))));
+        });
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn establish_webrtc() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        smol::spawn(async move { Server::new().serve_tcp(listener).await }).detach();
+
+        let mut sig_a = SignalClient::<WebrtcAddr>::connect(addr, "a")
+            .await
+            .unwrap();
+        let mut sig_b = SignalClient::<WebrtcAddr>::connect(addr, "b")
+            .await
+            .unwrap();
+
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let mut a = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let mut b = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("signal"));
+
+        let mut sa = a.connect(label.clone(), 1).await.unwrap();
+        let mut sb = b.connect(label, 1).await.unwrap();
+
+        let driver = async {
+            let (ra, rb) = future::zip(
+                establish(&mut a, &mut sig_a, "b", true),
+                establish(&mut b, &mut sig_b, "a", false),
+            )
+            .await;
+
+            ra.unwrap();
+            rb.unwrap();
+
+            // Keep sockets lent until test is done.
+            future::pending::<()>().await;
+        };
+
+        let test = async {
+            sa.write_all(b"hello").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("connection through signaling server timeout");
+    }
+}
@@ -0,0 +1,80 @@
+#[derive(Debug)]
+pub enum Error {
+    ErrChannelClosed,
+    /// Too many messages are waiting to be sent.
+    ErrQueueFull,
+    ErrNotRegistered,
+    ErrPeerExists(String),
+    ErrPeerNotFound(String),
+    ErrNotInRoom(String),
+    ErrUnexpectedMessage,
+    ErrAddrType,
+    ErrPortUnsupported,
+    ServerError(String),
+    SocketError(String),
+    IoError(std::io::Error),
+    SerdeError(serde_json::Error),
+    IdentityError(karma_p2p::IdentityError),
+    #[cfg(not(target_arch = "wasm32"))]
+    WsError(Box<async_tungstenite::tungstenite::Error>),
+}
+
+impl From<async_channel::RecvError> for Error {
+    fn from(_: async_channel::RecvError) -> Self {
+        Error::ErrChannelClosed
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl From<karma_p2p::IdentityError> for Error {
+    fn from(e: karma_p2p::IdentityError) -> Self {
+        Error::IdentityError(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::SerdeError(e)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<async_tungstenite::tungstenite::Error> for Error {
+    fn from(e: async_tungstenite::tungstenite::Error) -> Self {
+        Error::WsError(Box::new(e))
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            Error::SerdeError(e) => Some(e),
+            #[cfg(not(target_arch = "wasm32"))]
+            Error::WsError(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::IoError(e) => e,
+            e => std::io::Error::other(format!("{:?}", e)),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
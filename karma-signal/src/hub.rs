@@ -0,0 +1,229 @@
+use std::collections::{BTreeSet, HashMap};
+
+use serde_json::Value;
+use smol::channel::{Sender, TrySendError};
+
+use crate::{Error, Result, ServerMessage};
+
+/// Registered peers and rooms of signaling server.
+#[derive(Default)]
+pub struct Hub {
+    peers: HashMap<String, Sender<ServerMessage>>,
+    rooms: HashMap<String, BTreeSet<String>>,
+    // Peers which exchanged signals, told when the other disconnects.
+    contacts: HashMap<String, BTreeSet<String>>,
+}
+
+impl Hub {
+    /// Queue `message` to `peer`, peer whose queue is full is too slow and its
+    /// connection is closed.
+    fn send(&self, peer: &str, message: ServerMessage) {
+        if let Some(tx) = self.peers.get(peer) {
+            match tx.try_send(message) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    log::warn!("Peer {} is too slow, disconnect it", peer);
+                    tx.close();
+                }
+                Err(e) => log::error!("Send to peer {} failed: {:?}", peer, e),
+            }
+        }
+    }
+
+    pub fn register(&mut self, peer: &str, tx: Sender<ServerMessage>) -> Result<()> {
+        if self.peers.contains_key(peer) {
+            return Err(Error::ErrPeerExists(String::from(peer)));
+        }
+
+        self.peers.insert(String::from(peer), tx);
+        self.send(
+            peer,
+            ServerMessage::Registered {
+                peer: String::from(peer),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Remove peer and leave all its rooms.
+    pub fn unregister(&mut self, peer: &str) {
+        let rooms: Vec<String> = self
+            .rooms
+            .iter()
+            .filter(|(_, members)| members.contains(peer))
+            .map(|(room, _)| room.clone())
+            .collect();
+
+        for room in rooms {
+            self.leave(&room, peer);
+        }
+
+        self.peers.remove(peer);
+
+        for contact in self.contacts.remove(peer).unwrap_or_default() {
+            if let Some(contacts) = self.contacts.get_mut(&contact) {
+                contacts.remove(peer);
+            }
+
+            self.send(
+                &contact,
+                ServerMessage::Disconnected {
+                    peer: String::from(peer),
+                },
+            );
+        }
+    }
+
+    pub fn join(&mut self, room: &str, peer: &str) {
+        let members = self.rooms.entry(String::from(room)).or_default();
+
+        let peers: Vec<String> = members.iter().cloned().collect();
+
+        members.insert(String::from(peer));
+
+        for member in &peers {
+            self.send(
+                member,
+                ServerMessage::PeerJoined {
+                    room: String::from(room),
+                    peer: String::from(peer),
+                },
+            );
+        }
+
+        self.send(
+            peer,
+            ServerMessage::Peers {
+                room: String::from(room),
+                peers,
+            },
+        );
+    }
+
+    pub fn leave(&mut self, room: &str, peer: &str) {
+        if let Some(members) = self.rooms.get_mut(room) {
+            if !members.remove(peer) {
+                return;
+            }
+
+            let peers: Vec<String> = members.iter().cloned().collect();
+
+            if peers.is_empty() {
+                self.rooms.remove(room);
+            }
+
+            for member in &peers {
+                self.send(
+                    member,
+                    ServerMessage::PeerLeft {
+                        room: String::from(room),
+                        peer: String::from(peer),
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn signal(&mut self, from: &str, to: &str, data: Value) -> Result<()> {
+        if !self.peers.contains_key(to) {
+            return Err(Error::ErrPeerNotFound(String::from(to)));
+        }
+
+        for (a, b) in [(from, to), (to, from)] {
+            self.contacts
+                .entry(String::from(a))
+                .or_default()
+                .insert(String::from(b));
+        }
+
+        self.send(
+            to,
+            ServerMessage::Signal {
+                from: String::from(from),
+                room: None,
+                data,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn broadcast(&self, from: &str, room: &str, data: Value) -> Result<()> {
+        let members = self
+            .rooms
+            .get(room)
+            .filter(|m| m.contains(from))
+            .ok_or_else(|| Error::ErrNotInRoom(String::from(room)))?;
+
+        for member in members.iter().filter(|m| *m != from) {
+            self.send(
+                member,
+                ServerMessage::Signal {
+                    from: String::from(from),
+                    room: Some(String::from(room)),
+                    data: data.clone(),
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use smol::channel::bounded;
+
+    use crate::{Hub, ServerMessage};
+
+    #[test]
+    fn close_slow_peer() {
+        let mut hub = Hub::default();
+
+        let (alice, _alice_rx) = bounded(8);
+        let (bob, bob_rx) = bounded(1);
+
+        hub.register("alice", alice).unwrap();
+        hub.register("bob", bob).unwrap();
+
+        // Queue of bob is full with its registration, so it's closed.
+        hub.signal("alice", "bob", json!(1)).unwrap();
+
+        assert!(matches!(
+            bob_rx.try_recv(),
+            Ok(ServerMessage::Registered { .. })
+        ));
+        assert!(bob_rx.try_recv().is_err());
+        assert!(bob_rx.is_closed());
+    }
+
+    #[test]
+    fn tell_contacts_on_unregister() {
+        let mut hub = Hub::default();
+
+        let (alice, alice_rx) = bounded(8);
+        let (bob, _bob_rx) = bounded(8);
+        let (carol, carol_rx) = bounded(8);
+
+        hub.register("alice", alice).unwrap();
+        hub.register("bob", bob).unwrap();
+        hub.register("carol", carol).unwrap();
+
+        hub.signal("bob", "alice", json!(1)).unwrap();
+        hub.unregister("bob");
+
+        let messages: Vec<ServerMessage> =
+            std::iter::from_fn(|| alice_rx.try_recv().ok()).collect();
+        assert_eq!(
+            messages.last(),
+            Some(&ServerMessage::Disconnected {
+                peer: String::from("bob")
+            })
+        );
+
+        // Carol never signaled bob.
+        assert_eq!(std::iter::from_fn(|| carol_rx.try_recv().ok()).count(), 1);
+    }
+}
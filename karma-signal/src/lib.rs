@@ -0,0 +1,38 @@
+mod message;
+pub use message::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod hub;
+#[cfg(not(target_arch = "wasm32"))]
+pub use hub::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod server;
+#[cfg(not(target_arch = "wasm32"))]
+pub use server::*;
+
+mod client;
+pub use client::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod transport;
+#[cfg(not(target_arch = "wasm32"))]
+pub use transport::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod relay;
+#[cfg(not(target_arch = "wasm32"))]
+pub use relay::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod relay_socket;
+#[cfg(not(target_arch = "wasm32"))]
+pub use relay_socket::*;
+
+#[cfg(target_arch = "wasm32")]
+mod websocket;
+#[cfg(target_arch = "wasm32")]
+pub use websocket::*;
+
+mod error;
+pub use error::*;
@@ -0,0 +1,64 @@
+use futures_lite::future;
+use karma_signal::{Relay, Result, Server};
+use smol::net::TcpListener;
+
+const USAGE: &str = "Usage: karma-signal [--tcp ADDR] [--ws ADDR] [--relay ADDR]
+
+Relay signaling messages between karma peers.
+
+Options:
+    --tcp ADDR      listen address of newline-delimited json over tcp [default: 0.0.0.0:7100]
+    --ws ADDR       listen address of websocket [default: 0.0.0.0:7101]
+    --relay ADDR    also forward streams between peers on this address";
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    let mut tcp = String::from("0.0.0.0:7100");
+    let mut ws = String::from("0.0.0.0:7101");
+    let mut relay = None;
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--tcp", Some(addr)) => tcp = addr,
+            ("--ws", Some(addr)) => ws = addr,
+            ("--relay", Some(addr)) => relay = Some(addr),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    smol::block_on(async {
+        let server = Server::new();
+
+        let tcp_listener = TcpListener::bind(&tcp).await?;
+        let ws_listener = TcpListener::bind(&ws).await?;
+
+        log::info!("Listen tcp on {}, websocket on {}", tcp, ws);
+
+        let serve_relay = async {
+            match relay {
+                Some(addr) => {
+                    let listener = TcpListener::bind(&addr).await?;
+
+                    log::info!("Listen relay on {}", addr);
+
+                    Relay::new().serve(listener).await
+                }
+                None => future::pending().await,
+            }
+        };
+
+        future::try_zip(
+            future::try_zip(server.serve_tcp(tcp_listener), server.serve_ws(ws_listener)),
+            serve_relay,
+        )
+        .await?;
+
+        Ok(())
+    })
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Longest message on a signaling connection, connection sending a longer one is
+/// closed.
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Messages queued to a signaling connection, connection reading slower is closed.
+pub const MAX_QUEUED_MESSAGES: usize = 256;
+
+/// Message sent from peer to signaling server.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Register peer id of this connection, must be sent first.
+    Register {
+        peer: String,
+    },
+
+    Join {
+        room: String,
+    },
+
+    Leave {
+        room: String,
+    },
+
+    /// Relay `data` to peer `to`.
+    Signal {
+        to: String,
+        data: Value,
+    },
+
+    /// Relay `data` to all other peers in `room`.
+    Broadcast {
+        room: String,
+        data: Value,
+    },
+}
+
+/// Message sent from signaling server to peer.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Registered {
+        peer: String,
+    },
+
+    /// Peers already in `room` when joined.
+    Peers {
+        room: String,
+        peers: Vec<String>,
+    },
+
+    PeerJoined {
+        room: String,
+        peer: String,
+    },
+
+    PeerLeft {
+        room: String,
+        peer: String,
+    },
+
+    Signal {
+        from: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
+        data: Value,
+    },
+
+    /// Peer which signals were exchanged with is gone from server.
+    Disconnected {
+        peer: String,
+    },
+
+    Error {
+        message: String,
+    },
+}
+
+/// First line sent by relay on a new connection, `nonce` is signed in the request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RelayChallenge {
+    pub nonce: String,
+}
+
+/// Answer of peer to [`RelayChallenge`], opens channel `label` and `port` to peer `to`.
+///
+/// Peers are named by [`PeerId`](karma_p2p::PeerId), `signature` proves `from` owns
+/// its key, see [`RelayRequest::signed_bytes`]. Signature is base58 encoded.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RelayRequest {
+    pub from: String,
+    pub to: String,
+    pub label: String,
+    pub port: u16,
+    pub signature: String,
+}
+
+impl RelayRequest {
+    /// Bytes signed by `from`, binds request to `nonce` of this connection.
+    pub fn signed_bytes(nonce: &str, to: &str, label: &str, port: u16) -> Vec<u8> {
+        let mut bytes = Vec::from(&b"karma-relay"[..]);
+
+        for field in [nonce, to, label] {
+            bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+
+        bytes.extend_from_slice(&port.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// Line sent by relay, raw bytes of channel follow `Connected`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RelayResponse {
+    Connected,
+
+    Error { message: String },
+}
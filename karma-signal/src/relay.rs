@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    net::Shutdown,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures_lite::{future, io, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use karma_p2p::PeerId;
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use smol::net::{TcpListener, TcpStream};
+
+use crate::{Error, RelayChallenge, RelayRequest, RelayResponse, Result};
+
+const MAX_LINE: usize = 4096;
+
+/// Default time a connection waits for remote peer.
+pub const PENDING_TTL: Duration = Duration::from_secs(30);
+
+/// Default number of connections waiting for remote peer.
+pub const MAX_PENDING: usize = 1024;
+
+// (from, to, label, port)
+type Key = (String, String, String, u16);
+
+/// Forwarding server, splices connections of two peers opening same channel to each
+/// other.
+///
+/// Relay sends a [`RelayChallenge`] line on each connection, which answers with a
+/// [`RelayRequest`] line signed by its peer id. Connection waits until remote peer
+/// opens same label and port back, then both get a `Connected` line and bytes are
+/// forwarded as-is. Waiting connections are dropped after a ttl, and new ones are
+/// refused when too many are waiting.
+#[derive(Clone)]
+pub struct Relay {
+    pending: Arc<Mutex<HashMap<Key, (TcpStream, Instant)>>>,
+    ttl: Duration,
+    max_pending: usize,
+}
+
+impl Default for Relay {
+    fn default() -> Self {
+        Self::with_limits(PENDING_TTL, MAX_PENDING)
+    }
+}
+
+impl Relay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create relay keeping at most `max_pending` waiting connections for `ttl`.
+    pub fn with_limits(ttl: Duration, max_pending: usize) -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            max_pending,
+        }
+    }
+
+    /// Number of connections waiting for remote peer.
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// Accept connections from `listener` forever.
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, addr) = listener.accept().await?;
+
+            log::info!("Accept relay connection from {}", addr);
+
+            let relay = self.clone();
+
+            smol::spawn(async move {
+                if let Err(e) = relay.serve_conn(stream).await {
+                    log::error!("Relay connection {} failed: {:?}", addr, e);
+                }
+            })
+            .detach();
+        }
+    }
+
+    async fn serve_conn(&self, mut stream: TcpStream) -> Result<()> {
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+
+        let nonce = bs58::encode(nonce).into_string();
+
+        write_line(
+            &mut stream,
+            &RelayChallenge {
+                nonce: nonce.clone(),
+            },
+        )
+        .await?;
+
+        let request: RelayRequest = serde_json::from_str(&read_line(&mut stream).await?)?;
+
+        if let Err(e) = verify(&nonce, &request) {
+            let response = RelayResponse::Error {
+                message: format!("{:?}", e),
+            };
+
+            return write_line(&mut stream, &response).await;
+        }
+
+        let RelayRequest {
+            from,
+            to,
+            label,
+            port,
+            ..
+        } = request;
+
+        let waiting = {
+            let mut pending = self.pending.lock().unwrap();
+
+            let now = Instant::now();
+            pending.retain(|_, (_, since)| now.duration_since(*since) < self.ttl);
+
+            match pending.remove(&(to.clone(), from.clone(), label.clone(), port)) {
+                Some((other, _)) => Some(other),
+                None => {
+                    let key = (from.clone(), to.clone(), label.clone(), port);
+
+                    // Newer connection replaces stale one.
+                    if pending.len() < self.max_pending || pending.contains_key(&key) {
+                        pending.insert(key, (stream.clone(), now));
+                        return Ok(());
+                    }
+
+                    None
+                }
+            }
+        };
+
+        let mut other = match waiting {
+            Some(other) => other,
+            None => {
+                let response = RelayResponse::Error {
+                    message: String::from("too many pending connections"),
+                };
+
+                return write_line(&mut stream, &response).await;
+            }
+        };
+
+        log::info!("Splice {} and {} on {}:{}", from, to, label, port);
+
+        write_line(&mut other, &RelayResponse::Connected).await?;
+        write_line(&mut stream, &RelayResponse::Connected).await?;
+
+        future::try_zip(pipe(stream.clone(), other.clone()), pipe(other, stream)).await?;
+
+        Ok(())
+    }
+}
+
+/// Check request is signed by `from` for `nonce`, and peers are valid and distinct.
+fn verify(nonce: &str, request: &RelayRequest) -> Result<()> {
+    if request.from == request.to {
+        return Err(Error::ErrUnexpectedMessage);
+    }
+
+    let from: PeerId = request.from.parse()?;
+    let _: PeerId = request.to.parse()?;
+
+    let signature = bs58::decode(&request.signature)
+        .into_vec()
+        .map_err(|_| Error::ErrUnexpectedMessage)?;
+
+    let message = RelayRequest::signed_bytes(nonce, &request.to, &request.label, request.port);
+
+    from.verify(&message, &signature)?;
+
+    Ok(())
+}
+
+async fn pipe(from: TcpStream, mut to: TcpStream) -> Result<()> {
+    io::copy(from, &mut to).await?;
+
+    // Propagate close to the other side.
+    to.shutdown(Shutdown::Write)?;
+
+    Ok(())
+}
+
+/// Read one line without buffering past it, bytes after line belong to channel.
+pub(crate) async fn read_line<R: AsyncRead + Unpin>(r: &mut R) -> Result<String> {
+    read_capped_line(r, MAX_LINE).await
+}
+
+/// Read one line of at most `max` bytes, longer line is refused.
+///
+/// Bytes are read one by one, so `r` should be buffered when only lines follow.
+pub(crate) async fn read_capped_line<R: AsyncRead + Unpin>(
+    r: &mut R,
+    max: usize,
+) -> Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+
+    loop {
+        if r.read(&mut byte).await? == 0 {
+            return Err(Error::ErrChannelClosed);
+        }
+
+        if byte[0] == b'\n' {
+            break;
+        }
+
+        if line.len() >= max {
+            return Err(Error::ErrUnexpectedMessage);
+        }
+
+        line.push(byte[0]);
+    }
+
+    String::from_utf8(line).map_err(|_| Error::ErrUnexpectedMessage)
+}
+
+pub(crate) async fn write_line<W, T>(w: &mut W, message: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+
+    w.write_all(line.as_bytes()).await?;
+
+    Ok(())
+}
@@ -0,0 +1,744 @@
+use std::{
+    fmt::Debug,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_lite::{future, ready, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt};
+use karma_p2p::{Keypair, P2pSocket, P2pSocketEvents, P2pSocketExt, PeerId, SocketEvent};
+use serde::{Deserialize, Serialize};
+use smol::{
+    channel::{unbounded, Receiver, Sender},
+    net::TcpStream,
+    Task, Timer,
+};
+
+use crate::{
+    relay::{read_line, write_line},
+    Error, RelayChallenge, RelayRequest, RelayResponse, Result,
+};
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum RelayAddr {
+    /// Address of relay and identity of local peer, proven to relay on every stream.
+    #[serde(skip)]
+    Bootstrap { relay: SocketAddr, keypair: Keypair },
+
+    #[serde(skip)]
+    Label(String),
+
+    /// [`PeerId`] of initiator.
+    Offer(String),
+
+    /// [`PeerId`] of answerer.
+    Answer(String),
+}
+
+/// Socket forwarded through [`Relay`](crate::Relay), for peers which can't connect
+/// directly.
+///
+/// Exchanged addresses are peer ids only, which relay checks against signatures of
+/// both peers, so a stream is only spliced to the peer owning the remote id. Streams
+/// returned from `connect` become
+/// usable once remote is known and has connected same label and port. Port `0` is
+/// not supported, so nothing is accepted.
+pub struct RelaySocket {
+    relay: SocketAddr,
+    keypair: Keypair,
+    peer: String,
+    remote: Arc<Mutex<Option<String>>>,
+    // Closed once remote is known.
+    remote_tx: Sender<()>,
+    remote_rx: Receiver<()>,
+    addr_tx: Sender<RelayAddr>,
+    addr_rx: Receiver<RelayAddr>,
+}
+
+impl RelaySocket {
+    pub fn peer_id(&self) -> &str {
+        &self.peer
+    }
+
+    pub fn remote_peer_id(&self) -> Option<String> {
+        self.remote.lock().unwrap().clone()
+    }
+
+    /// Wait until remote is known from exchanged addresses.
+    async fn known_remote(&self) -> Result<String> {
+        // Closed once remote is set, or socket is dropped.
+        let _ = self.remote_rx.recv().await;
+
+        self.remote_peer_id().ok_or(Error::ErrChannelClosed)
+    }
+
+    fn set_remote(&self, remote: &str) -> Result<()> {
+        let _: PeerId = remote.parse()?;
+
+        *self.remote.lock().unwrap() = Some(String::from(remote));
+        self.remote_tx.close();
+
+        Ok(())
+    }
+
+    async fn _connect(
+        relay: SocketAddr,
+        keypair: Keypair,
+        remote: Arc<Mutex<Option<String>>>,
+        remote_rx: Receiver<()>,
+        label: String,
+        port: u16,
+    ) -> Result<(TcpStream, RelayPath)> {
+        // Fails as well when socket is dropped, remote is checked below.
+        let _ = remote_rx.recv().await;
+
+        let remote = remote.lock().unwrap().clone();
+        let remote = remote.ok_or(Error::ErrChannelClosed)?;
+
+        let peer = keypair.peer_id().to_string();
+
+        let mut stream = TcpStream::connect(relay).await?;
+
+        let challenge: RelayChallenge = serde_json::from_str(&read_line(&mut stream).await?)?;
+
+        let signed = RelayRequest::signed_bytes(&challenge.nonce, &remote, &label, port);
+
+        let request = RelayRequest {
+            from: peer.clone(),
+            to: remote.clone(),
+            label,
+            port,
+            signature: bs58::encode(keypair.sign(&signed)).into_string(),
+        };
+
+        write_line(&mut stream, &request).await?;
+
+        match serde_json::from_str(&read_line(&mut stream).await?)? {
+            RelayResponse::Connected => {
+                let path = RelayPath {
+                    local: peer,
+                    relay,
+                    remote,
+                };
+
+                Ok((stream, path))
+            }
+            RelayResponse::Error { message } => Err(Error::ServerError(message)),
+        }
+    }
+}
+
+impl P2pSocket for RelaySocket {
+    type Stream = RelayStream;
+
+    type Addr = RelayAddr;
+
+    type Error = Error;
+
+    type Binding = future::Ready<Result<Self>>;
+
+    type Connecting = future::Ready<Result<RelayStream>>;
+
+    type SettingRemoteAddr = future::Ready<Result<()>>;
+
+    fn binding(bootstrap: RelayAddr) -> Self::Binding {
+        let (relay, keypair) = match bootstrap {
+            RelayAddr::Bootstrap { relay, keypair } => (relay, keypair),
+            _ => return future::ready(Err(Error::ErrAddrType)),
+        };
+
+        let peer = keypair.peer_id().to_string();
+
+        let (remote_tx, remote_rx) = unbounded();
+        let (addr_tx, addr_rx) = unbounded();
+
+        future::ready(Ok(Self {
+            relay,
+            keypair,
+            peer,
+            remote: Arc::new(Mutex::new(None)),
+            remote_tx,
+            remote_rx,
+            addr_tx,
+            addr_rx,
+        }))
+    }
+
+    fn connecting(&self, label: RelayAddr, port: u16) -> Self::Connecting {
+        let label = match &label {
+            RelayAddr::Label(label) => label.clone(),
+            _ => return future::ready(Err(Error::ErrAddrType)),
+        };
+
+        if port == 0 {
+            return future::ready(Err(Error::ErrPortUnsupported));
+        }
+
+        let fu = Self::_connect(
+            self.relay,
+            self.keypair.clone(),
+            self.remote.clone(),
+            self.remote_rx.clone(),
+            label,
+            port,
+        );
+
+        // Dial now, remote waits for this side before its stream can be used.
+        future::ready(Ok(RelayStream {
+            state: StreamState::Connecting(smol::spawn(fu)),
+            path: None,
+        }))
+    }
+
+    fn poll_accept(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Result<RelayStream>> {
+        Poll::Pending
+    }
+
+    fn poll_start(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let offer = RelayAddr::Offer(self.peer.clone());
+
+        Poll::Ready(
+            self.addr_tx
+                .try_send(offer)
+                .map_err(|_| Error::ErrChannelClosed),
+        )
+    }
+
+    fn poll_fetch_local_addr(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<RelayAddr>> {
+        match ready!(self.addr_rx.poll_next(cx)) {
+            Some(addr) => Poll::Ready(Ok(addr)),
+            None => Poll::Ready(Err(Error::ErrChannelClosed)),
+        }
+    }
+
+    fn setting_remote_addr(&self, remote: RelayAddr) -> Self::SettingRemoteAddr {
+        let res = match &remote {
+            RelayAddr::Offer(remote) => self.set_remote(remote).and_then(|_| {
+                self.addr_tx
+                    .try_send(RelayAddr::Answer(self.peer.clone()))
+                    .map_err(|_| Error::ErrChannelClosed)
+            }),
+            RelayAddr::Answer(remote) => self.set_remote(remote),
+            _ => Err(Error::ErrAddrType),
+        };
+
+        future::ready(res)
+    }
+
+    fn is_last_addr(addr: &RelayAddr) -> bool {
+        matches!(addr, RelayAddr::Offer(_) | RelayAddr::Answer(_))
+    }
+}
+
+/// Every stream has its own relayed connection, so no socket event is reported.
+impl P2pSocketEvents for RelaySocket {
+    fn poll_event(self: Pin<&Self>, _cx: &mut Context<'_>) -> Poll<Option<SocketEvent>> {
+        Poll::Ready(None)
+    }
+}
+
+/// Route of relayed stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayPath {
+    pub local: String,
+    pub relay: SocketAddr,
+    pub remote: String,
+}
+
+enum StreamState {
+    Connecting(Task<Result<(TcpStream, RelayPath)>>),
+    Connected(TcpStream),
+    Failed,
+}
+
+pub struct RelayStream {
+    state: StreamState,
+    path: Option<RelayPath>,
+}
+
+impl RelayStream {
+    /// Route through relay, `None` until stream is connected.
+    pub fn path(&self) -> Option<&RelayPath> {
+        self.path.as_ref()
+    }
+
+    fn poll_connected(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<&mut TcpStream>> {
+        if let StreamState::Connecting(fu) = &mut self.state {
+            match ready!(Pin::new(fu).poll(cx)) {
+                Ok((stream, path)) => {
+                    self.path = Some(path);
+                    self.state = StreamState::Connected(stream);
+                }
+                Err(e) => {
+                    self.state = StreamState::Failed;
+                    return Poll::Ready(Err(e.into()));
+                }
+            }
+        }
+
+        match &mut self.state {
+            StreamState::Connected(stream) => Poll::Ready(Ok(stream)),
+            _ => Poll::Ready(Err(std::io::ErrorKind::NotConnected.into())),
+        }
+    }
+}
+
+impl AsyncRead for RelayStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for RelayStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let stream = ready!(self.get_mut().poll_connected(cx))?;
+
+        Pin::new(stream).poll_close(cx)
+    }
+}
+
+/// Stream connected directly or through relay, see [`connect_or_relay`].
+pub enum FallbackStream<S> {
+    Direct(S),
+    Relayed(RelayStream),
+}
+
+impl<S> FallbackStream<S> {
+    /// Route through relay, `None` for direct stream or until relayed one is connected.
+    pub fn relay_path(&self) -> Option<&RelayPath> {
+        match self {
+            FallbackStream::Direct(_) => None,
+            FallbackStream::Relayed(s) => s.path(),
+        }
+    }
+}
+
+/// Path byte sent over relay by peer picking it.
+const PATH_DIRECT: u8 = 0;
+const PATH_RELAY: u8 = 1;
+
+/// Connect `label` and `port` on `direct` once it is connected, or `relay_label` and
+/// `port` on `relay` when `direct` fails or isn't connected within `timeout`.
+///
+/// Both peers take same path: the one with lower peer id waits for `direct` and sends
+/// its pick over the relayed stream, which is kept when relay is picked. So `relay`
+/// must be reachable by both, and its addresses exchanged before. Connection state of
+/// `direct` is read without taking its events, sockets not tracking it are taken as
+/// connected.
+pub async fn connect_or_relay<S>(
+    direct: &S,
+    label: S::Addr,
+    relay: &RelaySocket,
+    relay_label: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<FallbackStream<S::Stream>>
+where
+    S: P2pSocket + P2pSocketEvents + Unpin,
+    S::Addr: Clone + Unpin,
+    S::Error: Debug,
+{
+    let mut relayed = relay
+        .connect(RelayAddr::Label(String::from(relay_label)), port)
+        .await?;
+
+    let remote = relay.known_remote().await?;
+
+    let path = if relay.peer_id() < remote.as_str() {
+        let connected = async {
+            let mut state = None;
+
+            loop {
+                state = match direct.next_state(state).await {
+                    None | Some(SocketEvent::Connected) => return true,
+                    Some(
+                        SocketEvent::Failed | SocketEvent::Closed | SocketEvent::IdentityFailed,
+                    ) => return false,
+                    state => state,
+                };
+            }
+        };
+
+        let expired = async {
+            Timer::after(timeout).await;
+            false
+        };
+
+        let path = if future::or(connected, expired).await {
+            PATH_DIRECT
+        } else {
+            PATH_RELAY
+        };
+
+        relayed.write_all(&[path]).await?;
+        relayed.flush().await?;
+
+        path
+    } else {
+        let mut path = [0u8];
+        relayed.read_exact(&mut path).await?;
+
+        path[0]
+    };
+
+    match path {
+        PATH_DIRECT => {
+            let stream = direct
+                .connect(label, port)
+                .await
+                .map_err(|e| Error::SocketError(format!("{:?}", e)))?;
+
+            Ok(FallbackStream::Direct(stream))
+        }
+        PATH_RELAY => {
+            log::info!("Direct connect failed, relay {}:{}", relay_label, port);
+
+            Ok(FallbackStream::Relayed(relayed))
+        }
+        _ => Err(Error::ErrUnexpectedMessage),
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for FallbackStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            FallbackStream::Direct(s) => Pin::new(s).poll_read(cx, buf),
+            FallbackStream::Relayed(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FallbackStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            FallbackStream::Direct(s) => Pin::new(s).poll_write(cx, buf),
+            FallbackStream::Relayed(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            FallbackStream::Direct(s) => Pin::new(s).poll_flush(cx),
+            FallbackStream::Relayed(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            FallbackStream::Direct(s) => Pin::new(s).poll_close(cx),
+            FallbackStream::Relayed(s) => Pin::new(s).poll_close(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use futures_lite::{future, future::block_on, AsyncReadExt, AsyncWriteExt};
+    use karma_p2p::{
+        testkit::{self, Harness},
+        Keypair, P2pSocketExt,
+    };
+    use smol::{
+        net::{TcpListener, TcpStream},
+        Timer,
+    };
+
+    use crate::{
+        connect_or_relay,
+        relay::{read_line, write_line},
+        Error, FallbackStream, Relay, RelayAddr, RelayChallenge, RelayRequest, RelayResponse,
+        RelaySocket, RelayStream,
+    };
+
+    async fn start() -> SocketAddr {
+        start_relay(Relay::new()).await
+    }
+
+    async fn start_relay(relay: Relay) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        smol::spawn(async move { relay.serve(listener).await }).detach();
+
+        addr
+    }
+
+    async fn timeout<F: std::future::Future<Output = ()>>(fu: F) {
+        future::or(fu, async {
+            Timer::after(Duration::from_secs(30)).await;
+            panic!("relay timeout");
+        })
+        .await
+    }
+
+    fn bootstrap(relay: SocketAddr, keypair: &Keypair) -> RelayAddr {
+        RelayAddr::Bootstrap {
+            relay,
+            keypair: keypair.clone(),
+        }
+    }
+
+    /// Send request of `keypair` claiming to be `from`, get response of relay.
+    async fn request(relay: SocketAddr, keypair: &Keypair, from: &str, to: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(relay).await.unwrap();
+
+        let line = read_line(&mut stream).await.unwrap();
+        let challenge: RelayChallenge = serde_json::from_str(&line).unwrap();
+
+        let signed = RelayRequest::signed_bytes(&challenge.nonce, to, "data", 1);
+
+        let request = RelayRequest {
+            from: String::from(from),
+            to: String::from(to),
+            label: String::from("data"),
+            port: 1,
+            signature: bs58::encode(keypair.sign(&signed)).into_string(),
+        };
+
+        write_line(&mut stream, &request).await.unwrap();
+
+        stream
+    }
+
+    async fn response(stream: &mut TcpStream) -> RelayResponse {
+        serde_json::from_str(&read_line(stream).await.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn relayed_path() {
+        block_on(timeout(async {
+            let relay = start().await;
+
+            let (ka, kb) = (Keypair::generate(), Keypair::generate());
+
+            let mut a = RelaySocket::bind(bootstrap(relay, &ka)).await.unwrap();
+            let mut b = RelaySocket::bind(bootstrap(relay, &kb)).await.unwrap();
+
+            let label = RelayAddr::Label(String::from("data"));
+
+            let mut sa = a.connect(label.clone(), 1).await.unwrap();
+            let mut sb = b.connect(label, 1).await.unwrap();
+            assert!(sa.path().is_none());
+
+            a.start().await.unwrap();
+            b.set_remote_addr(a.fetch_local_addr().await.unwrap())
+                .await
+                .unwrap();
+            a.set_remote_addr(b.fetch_local_addr().await.unwrap())
+                .await
+                .unwrap();
+
+            sa.write_all(b"hello").await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            let (ia, ib) = (ka.peer_id().to_string(), kb.peer_id().to_string());
+
+            let path = sa.path().unwrap();
+            assert_eq!((&path.local, &path.remote), (&ia, &ib));
+            assert_eq!(path.relay, relay);
+            assert_eq!(sb.path().unwrap().remote, ia);
+        }));
+    }
+
+    #[test]
+    fn agree_on_direct() {
+        block_on(timeout(async {
+            let relay = start().await;
+
+            let (ka, kb) = (Keypair::generate(), Keypair::generate());
+
+            let bind = |keypair: &Keypair| RelaySocket::bind(bootstrap(relay, keypair));
+
+            let (mut da, mut db) = (bind(&ka).await.unwrap(), bind(&kb).await.unwrap());
+            let (mut ra, mut rb) = (bind(&ka).await.unwrap(), bind(&kb).await.unwrap());
+
+            for (a, b) in [(&mut da, &mut db), (&mut ra, &mut rb)] {
+                a.start().await.unwrap();
+                b.set_remote_addr(a.fetch_local_addr().await.unwrap())
+                    .await
+                    .unwrap();
+                a.set_remote_addr(b.fetch_local_addr().await.unwrap())
+                    .await
+                    .unwrap();
+            }
+
+            // Direct socket tracks no connection, so it's taken as connected by both.
+            let label = RelayAddr::Label(String::from("direct"));
+            let wait = Duration::from_secs(1);
+
+            let (sa, sb) = future::zip(
+                connect_or_relay(&da, label.clone(), &ra, "data", 1, wait),
+                connect_or_relay(&db, label, &rb, "data", 1, wait),
+            )
+            .await;
+
+            let (mut sa, mut sb) = (sa.unwrap(), sb.unwrap());
+            assert!(matches!(sa, FallbackStream::Direct(_)));
+            assert!(matches!(sb, FallbackStream::Direct(_)));
+
+            sb.write_all(b"hello").await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sa.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        }));
+    }
+
+    #[test]
+    fn reject_forged_request() {
+        block_on(timeout(async {
+            let relay = start().await;
+
+            let (victim, mallory) = (Keypair::generate(), Keypair::generate());
+            let to = Keypair::generate().peer_id().to_string();
+
+            // Signed by another key than claimed.
+            let mut stream = request(relay, &mallory, &victim.peer_id().to_string(), &to).await;
+            assert!(matches!(
+                response(&mut stream).await,
+                RelayResponse::Error { .. }
+            ));
+
+            // Not a peer id.
+            let mut stream = request(relay, &mallory, "mallory", &to).await;
+            assert!(matches!(
+                response(&mut stream).await,
+                RelayResponse::Error { .. }
+            ));
+
+            let socket = RelaySocket::bind(bootstrap(relay, &mallory)).await.unwrap();
+            let res = socket
+                .set_remote_addr(RelayAddr::Offer(String::from("bob")))
+                .await;
+            assert!(matches!(res, Err(Error::IdentityError(_))));
+        }));
+    }
+
+    #[test]
+    fn expire_and_cap_pending() {
+        block_on(timeout(async {
+            let limited = Relay::with_limits(Duration::from_millis(200), 1);
+            let relay = start_relay(limited.clone()).await;
+
+            let (ka, kb, kc) = (
+                Keypair::generate(),
+                Keypair::generate(),
+                Keypair::generate(),
+            );
+            let (ia, ib, ic) = (
+                ka.peer_id().to_string(),
+                kb.peer_id().to_string(),
+                kc.peer_id().to_string(),
+            );
+
+            let mut waiting = request(relay, &ka, &ia, &ib).await;
+
+            while limited.pending_count() == 0 {
+                Timer::after(Duration::from_millis(10)).await;
+            }
+
+            // Full, another pair is refused.
+            let mut refused = request(relay, &kc, &ic, &ib).await;
+            assert!(matches!(
+                response(&mut refused).await,
+                RelayResponse::Error { .. }
+            ));
+
+            Timer::after(Duration::from_millis(300)).await;
+
+            // Expired connection is dropped on next request, which takes its place.
+            let _c = request(relay, &kc, &ic, &ib).await;
+
+            let mut buf = [0u8; 1];
+            assert_eq!(waiting.read(&mut buf).await.unwrap(), 0);
+            assert_eq!(limited.pending_count(), 1);
+        }));
+    }
+
+    struct RelayHarness(SocketAddr);
+
+    impl Harness for RelayHarness {
+        type Socket = RelaySocket;
+
+        type Stream = RelayStream;
+
+        type Addr = RelayAddr;
+
+        type Error = Error;
+
+        fn bootstrap(&self) -> RelayAddr {
+            bootstrap(self.0, &Keypair::generate())
+        }
+
+        fn label(&self, name: &str) -> RelayAddr {
+            RelayAddr::Label(String::from(name))
+        }
+
+        fn wrong_addr(&self) -> RelayAddr {
+            bootstrap(self.0, &Keypair::generate())
+        }
+
+        fn is_offer(&self, addr: &RelayAddr) -> bool {
+            matches!(addr, RelayAddr::Offer(_))
+        }
+
+        fn is_answer(&self, addr: &RelayAddr) -> bool {
+            matches!(addr, RelayAddr::Answer(_))
+        }
+
+        fn is_addr_type_error(&self, err: &Error) -> bool {
+            matches!(err, Error::ErrAddrType)
+        }
+    }
+
+    #[test]
+    fn conformance() {
+        block_on(timeout(async {
+            let relay = start().await;
+
+            testkit::check_all(&RelayHarness(relay)).await;
+        }));
+    }
+}
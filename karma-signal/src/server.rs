@@ -0,0 +1,430 @@
+use std::{
+    future::Future,
+    io,
+    net::Shutdown,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_tungstenite::tungstenite::{protocol::WebSocketConfig, Message};
+use futures_lite::{future, io::BufReader, stream, AsyncWriteExt, Stream};
+use futures_util::{SinkExt, StreamExt};
+use smol::{
+    channel::{bounded, Sender},
+    net::{TcpListener, TcpStream},
+    Timer,
+};
+
+use crate::{
+    relay::read_capped_line, ClientMessage, Error, Hub, Result, ServerMessage, MAX_MESSAGE_SIZE,
+    MAX_QUEUED_MESSAGES,
+};
+
+/// Time a write to peer may take, peer not reading for that long is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn write_timeout<F: Future<Output = Result<()>>>(write: F) -> Result<()> {
+    let expired = async {
+        Timer::after(WRITE_TIMEOUT).await;
+        Err(Error::IoError(io::ErrorKind::TimedOut.into()))
+    };
+
+    future::or(write, expired).await
+}
+
+/// Websocket limits of signaling connections, see [`MAX_MESSAGE_SIZE`].
+pub(crate) fn ws_config() -> WebSocketConfig {
+    WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_MESSAGE_SIZE),
+        ..WebSocketConfig::default()
+    }
+}
+
+/// Signaling server relays messages between peers connected over tcp or websocket.
+///
+/// Tcp connections use newline-delimited json, websocket connections send one json
+/// message per text frame. Messages are at most [`MAX_MESSAGE_SIZE`] long. A
+/// connection with [`MAX_QUEUED_MESSAGES`] not yet written, or not reading for a
+/// while, is closed.
+#[derive(Default, Clone)]
+pub struct Server {
+    hub: Arc<Mutex<Hub>>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept tcp connections from `listener` forever.
+    pub async fn serve_tcp(&self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, addr) = listener.accept().await?;
+
+            log::info!("Accept tcp connection from {}", addr);
+
+            let server = self.clone();
+
+            smol::spawn(async move {
+                if let Err(e) = server.serve_tcp_conn(stream).await {
+                    log::error!("Tcp connection {} failed: {:?}", addr, e);
+                }
+            })
+            .detach();
+        }
+    }
+
+    /// Accept websocket connections from `listener` forever.
+    pub async fn serve_ws(&self, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, addr) = listener.accept().await?;
+
+            log::info!("Accept websocket connection from {}", addr);
+
+            let server = self.clone();
+
+            smol::spawn(async move {
+                if let Err(e) = server.serve_ws_conn(stream).await {
+                    log::error!("Websocket connection {} failed: {:?}", addr, e);
+                }
+            })
+            .detach();
+        }
+    }
+
+    async fn serve_tcp_conn(&self, stream: TcpStream) -> Result<()> {
+        let (tx, rx) = bounded::<ServerMessage>(MAX_QUEUED_MESSAGES);
+
+        let mut writer = stream.clone();
+
+        let write_task = smol::spawn(async move {
+            let res = async {
+                while let Ok(message) = rx.recv().await {
+                    let mut line = serde_json::to_string(&message)?;
+                    line.push('\n');
+
+                    write_timeout(async { Ok(writer.write_all(line.as_bytes()).await?) }).await?;
+                }
+
+                Ok::<(), Error>(())
+            }
+            .await;
+
+            // Writing stops when peer is too slow or gone, so reading ends as well.
+            let _ = writer.shutdown(Shutdown::Both);
+
+            res
+        });
+
+        let reader = BufReader::new(stream);
+
+        let lines = stream::unfold(reader, |mut reader| async move {
+            match read_capped_line(&mut reader, MAX_MESSAGE_SIZE).await {
+                Err(Error::ErrChannelClosed) => None,
+                line => Some((line, reader)),
+            }
+        });
+
+        self.handle_connection(Box::pin(lines), tx).await;
+
+        write_task.await
+    }
+
+    async fn serve_ws_conn(&self, stream: TcpStream) -> Result<()> {
+        let raw = stream.clone();
+
+        let ws = async_tungstenite::accept_async_with_config(stream, Some(ws_config())).await?;
+
+        let (mut sink, stream) = ws.split();
+
+        let (tx, rx) = bounded::<ServerMessage>(MAX_QUEUED_MESSAGES);
+
+        let write_task = smol::spawn(async move {
+            let res = async {
+                while let Ok(message) = rx.recv().await {
+                    let text = serde_json::to_string(&message)?;
+
+                    write_timeout(async { Ok(sink.send(Message::Text(text)).await?) }).await?;
+                }
+
+                Ok::<(), Error>(())
+            }
+            .await;
+
+            // Writing stops when peer is too slow or gone, so reading ends as well.
+            let _ = raw.shutdown(Shutdown::Both);
+
+            res
+        });
+
+        let texts = stream.filter_map(|m| async move {
+            match m {
+                Ok(Message::Text(text)) => Some(Ok(text)),
+                Ok(_) => None,
+                Err(e) => Some(Err(Error::from(e))),
+            }
+        });
+
+        self.handle_connection(Box::pin(texts), tx).await;
+
+        write_task.await
+    }
+
+    async fn handle_connection<S>(&self, mut incoming: S, tx: Sender<ServerMessage>)
+    where
+        S: Stream<Item = Result<String>> + Unpin,
+    {
+        let mut peer = None;
+
+        while let Some(text) = incoming.next().await {
+            let text = match text {
+                Ok(t) => t,
+                Err(e) => {
+                    log::warn!("Read from connection failed: {:?}", e);
+                    break;
+                }
+            };
+
+            if let Err(e) = self.handle_message(&mut peer, &text, &tx) {
+                let message = ServerMessage::Error {
+                    message: format!("{:?}", e),
+                };
+
+                if tx.try_send(message).is_err() {
+                    break;
+                }
+            }
+        }
+
+        if let Some(peer) = peer {
+            log::info!("Peer {} disconnected", peer);
+
+            let mut hub = self.hub.lock().unwrap();
+
+            hub.unregister(&peer);
+        }
+    }
+
+    fn handle_message(
+        &self,
+        peer: &mut Option<String>,
+        text: &str,
+        tx: &Sender<ServerMessage>,
+    ) -> Result<()> {
+        let message: ClientMessage = serde_json::from_str(text)?;
+
+        let mut hub = self.hub.lock().unwrap();
+
+        match (message, peer.as_deref()) {
+            (ClientMessage::Register { peer: id }, None) => {
+                hub.register(&id, tx.clone())?;
+                *peer = Some(id);
+            }
+            (ClientMessage::Register { .. }, Some(_)) => return Err(Error::ErrUnexpectedMessage),
+            (_, None) => return Err(Error::ErrNotRegistered),
+            (ClientMessage::Join { room }, Some(p)) => hub.join(&room, p),
+            (ClientMessage::Leave { room }, Some(p)) => hub.leave(&room, p),
+            (ClientMessage::Signal { to, data }, Some(p)) => hub.signal(p, &to, data)?,
+            (ClientMessage::Broadcast { room, data }, Some(p)) => hub.broadcast(p, &room, data)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, pin::Pin};
+
+    use async_tungstenite::tungstenite::Message;
+    use futures_lite::{
+        future::{block_on, poll_fn},
+        AsyncReadExt, AsyncWriteExt,
+    };
+    use futures_util::{SinkExt, StreamExt};
+    use karma_p2p::Signaling;
+    use serde_json::{json, Value};
+    use smol::net::{TcpListener, TcpStream};
+
+    use crate::{ClientMessage, Error, Server, ServerMessage, SignalClient, MAX_MESSAGE_SIZE};
+
+    async fn start() -> (SocketAddr, SocketAddr) {
+        let server = Server::new();
+
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addrs = (tcp.local_addr().unwrap(), ws.local_addr().unwrap());
+
+        let s = server.clone();
+        smol::spawn(async move { s.serve_tcp(tcp).await }).detach();
+        smol::spawn(async move { server.serve_ws(ws).await }).detach();
+
+        addrs
+    }
+
+    async fn send(client: &mut SignalClient<Value>, remote: &str, value: Value) {
+        poll_fn(|cx| Pin::new(&mut *client).poll_send(cx, remote, &value))
+            .await
+            .unwrap();
+    }
+
+    async fn recv(client: &mut SignalClient<Value>, remote: &str) -> Option<Value> {
+        poll_fn(|cx| Pin::new(&mut *client).poll_recv(cx, remote))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn tcp_relay() {
+        block_on(async {
+            let (tcp, _) = start().await;
+
+            let mut alice = SignalClient::connect(tcp, "alice").await.unwrap();
+            let mut bob = SignalClient::connect(tcp, "bob").await.unwrap();
+
+            send(&mut alice, "bob", json!({"SDP": "offer"})).await;
+            send(&mut bob, "alice", json!({"SDP": "answer"})).await;
+            send(&mut alice, "bob", json!({"ICE": 1})).await;
+
+            assert_eq!(recv(&mut bob, "alice").await, Some(json!({"SDP": "offer"})));
+            assert_eq!(recv(&mut bob, "alice").await, Some(json!({"ICE": 1})));
+            assert_eq!(
+                recv(&mut alice, "bob").await,
+                Some(json!({"SDP": "answer"}))
+            );
+        });
+    }
+
+    #[test]
+    fn duplicate_and_unknown_peer() {
+        block_on(async {
+            let (tcp, _) = start().await;
+
+            let mut alice = SignalClient::<Value>::connect(tcp, "alice").await.unwrap();
+
+            let dup = SignalClient::<Value>::connect(tcp, "alice").await;
+            assert!(matches!(dup, Err(Error::ServerError(_))));
+
+            send(&mut alice, "nobody", json!(null)).await;
+
+            let event = alice.next_event().await.unwrap();
+            assert!(matches!(event, ServerMessage::Error { .. }));
+        });
+    }
+
+    #[test]
+    fn room_membership() {
+        block_on(async {
+            let (tcp, _) = start().await;
+
+            let mut alice = SignalClient::connect(tcp, "alice").await.unwrap();
+            let mut bob = SignalClient::connect(tcp, "bob").await.unwrap();
+
+            assert!(alice.join("room").await.unwrap().is_empty());
+            assert_eq!(bob.join("room").await.unwrap(), vec!["alice"]);
+
+            assert_eq!(
+                alice.next_event().await.unwrap(),
+                ServerMessage::PeerJoined {
+                    room: String::from("room"),
+                    peer: String::from("bob"),
+                }
+            );
+
+            bob.broadcast("room", &json!("hello")).unwrap();
+            assert_eq!(recv(&mut alice, "bob").await, Some(json!("hello")));
+
+            drop(bob);
+
+            // Signals from a peer end when it leaves the room.
+            assert_eq!(recv(&mut alice, "bob").await, None);
+        });
+    }
+
+    #[test]
+    fn direct_remote_disconnected() {
+        block_on(async {
+            let (tcp, _) = start().await;
+
+            let mut alice = SignalClient::connect(tcp, "alice").await.unwrap();
+            let mut bob = SignalClient::connect(tcp, "bob").await.unwrap();
+
+            // Not in a room, so only signals tie them.
+            send(&mut bob, "alice", json!({"SDP": "offer"})).await;
+            assert_eq!(recv(&mut alice, "bob").await, Some(json!({"SDP": "offer"})));
+
+            drop(bob);
+
+            assert_eq!(recv(&mut alice, "bob").await, None);
+        });
+    }
+
+    #[test]
+    fn close_oversized_message() {
+        block_on(async {
+            let (tcp, _) = start().await;
+
+            let mut stream = TcpStream::connect(tcp).await.unwrap();
+
+            let line = vec![b'x'; MAX_MESSAGE_SIZE + 1];
+            let _ = stream.write_all(&line).await;
+
+            let mut buf = Vec::new();
+            let _ = stream.read_to_end(&mut buf).await;
+            assert!(buf.is_empty());
+        });
+    }
+
+    #[test]
+    fn websocket_to_tcp() {
+        block_on(async {
+            let (tcp, ws) = start().await;
+
+            let stream = TcpStream::connect(ws).await.unwrap();
+            let (mut carol, _) = async_tungstenite::client_async("ws://localhost/", stream)
+                .await
+                .unwrap();
+
+            let register = ClientMessage::Register {
+                peer: String::from("carol"),
+            };
+            carol
+                .send(Message::Text(serde_json::to_string(&register).unwrap()))
+                .await
+                .unwrap();
+
+            let registered = carol.next().await.unwrap().unwrap();
+            let registered: ServerMessage =
+                serde_json::from_str(registered.to_text().unwrap()).unwrap();
+            assert!(matches!(registered, ServerMessage::Registered { .. }));
+
+            let mut alice = SignalClient::connect(tcp, "alice").await.unwrap();
+
+            send(&mut alice, "carol", json!({"ICE": 2})).await;
+
+            let signal = carol.next().await.unwrap().unwrap();
+            let signal: ServerMessage = serde_json::from_str(signal.to_text().unwrap()).unwrap();
+            assert_eq!(
+                signal,
+                ServerMessage::Signal {
+                    from: String::from("alice"),
+                    room: None,
+                    data: json!({"ICE": 2}),
+                }
+            );
+
+            let reply = ClientMessage::Signal {
+                to: String::from("alice"),
+                data: json!({"ICE": 3}),
+            };
+            carol
+                .send(Message::Text(serde_json::to_string(&reply).unwrap()))
+                .await
+                .unwrap();
+
+            assert_eq!(recv(&mut alice, "carol").await, Some(json!({"ICE": 3})));
+        });
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use karma_p2p::{NodeTransport, P2pSocket, P2pSocketEvents};
+use serde::{de::DeserializeOwned, Serialize};
+use smol::net::TcpListener;
+
+use crate::{Error, Relay, Result, SignalClient};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Transport of [`Node`](karma_p2p::Node) using signaling servers as bootstrap nodes.
+///
+/// Every bootstrap node is joined in `room`, peers in the room are found and
+/// addresses are relayed through it. Sockets to peers are created by `bind`. Node
+/// with forwarding enabled serves [`Relay`] on listened addresses.
+pub struct SignalTransport<S: P2pSocket, F> {
+    peer: String,
+    room: String,
+    bind: F,
+    dialing: HashMap<SocketAddr, BoxFuture<Result<SignalClient<S::Addr>>>>,
+    listening: HashMap<SocketAddr, BoxFuture<Result<TcpListener>>>,
+    binding: HashMap<String, BoxFuture<Result<S>>>,
+}
+
+impl<S: P2pSocket, F> SignalTransport<S, F> {
+    /// Create transport registered as `peer`, must be same as id of node.
+    pub fn new(peer: &str, room: &str, bind: F) -> Self {
+        Self {
+            peer: String::from(peer),
+            room: String::from(room),
+            bind,
+            dialing: HashMap::new(),
+            listening: HashMap::new(),
+            binding: HashMap::new(),
+        }
+    }
+
+    pub fn peer_id(&self) -> &str {
+        &self.peer
+    }
+}
+
+impl<S, F, Fut> NodeTransport for SignalTransport<S, F>
+where
+    S: P2pSocket + P2pSocketEvents + Unpin + Send + 'static,
+    S::Addr: Serialize + DeserializeOwned + Send + 'static,
+    S::Error: Debug,
+    F: FnMut(&str) -> Fut + Unpin,
+    Fut: Future<Output = std::result::Result<S, S::Error>> + Send + 'static,
+{
+    type Socket = S;
+
+    type Discovery = SignalClient<S::Addr>;
+
+    type Listener = BoxFuture<Result<()>>;
+
+    type Error = Error;
+
+    fn poll_dial(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        addr: &SocketAddr,
+    ) -> Poll<Result<Self::Discovery>> {
+        let this = self.get_mut();
+
+        let fu = this.dialing.entry(*addr).or_insert_with(|| {
+            let addr = *addr;
+            let peer = this.peer.clone();
+            let room = this.room.clone();
+
+            Box::pin(async move {
+                let mut client = SignalClient::connect(addr, &peer).await?;
+                client.join(&room).await?;
+                Ok(client)
+            })
+        });
+
+        let res = futures_lite::ready!(fu.as_mut().poll(cx));
+        this.dialing.remove(addr);
+
+        Poll::Ready(res)
+    }
+
+    fn poll_listen(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        addr: &SocketAddr,
+    ) -> Poll<Result<Self::Listener>> {
+        let this = self.get_mut();
+
+        let fu = this.listening.entry(*addr).or_insert_with(|| {
+            let addr = *addr;
+
+            Box::pin(async move { Ok(TcpListener::bind(addr).await?) })
+        });
+
+        let res = futures_lite::ready!(fu.as_mut().poll(cx));
+        this.listening.remove(addr);
+
+        Poll::Ready(res.map(|listener| {
+            let relay = Relay::new();
+
+            Box::pin(async move { relay.serve(listener).await }) as BoxFuture<Result<()>>
+        }))
+    }
+
+    fn poll_bind(self: Pin<&mut Self>, cx: &mut Context<'_>, remote: &str) -> Poll<Result<S>> {
+        let this = self.get_mut();
+
+        let bind = &mut this.bind;
+
+        let fu = this.binding.entry(String::from(remote)).or_insert_with(|| {
+            let fu = bind(remote);
+
+            Box::pin(async move { fu.await.map_err(|e| Error::SocketError(format!("{:?}", e))) })
+        });
+
+        let res = futures_lite::ready!(fu.as_mut().poll(cx));
+        this.binding.remove(remote);
+
+        Poll::Ready(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::Future, pin::Pin, time::Duration};
+
+    use futures_lite::{future, AsyncReadExt, AsyncWriteExt};
+    use karma_p2p::{establish, Config, Keypair, Node, NodeEvent, P2pSocketExt};
+    use karma_p2p_webrtc::{WebrtcAddr, WebrtcSocket, WebrtcStream};
+    use smol::{
+        channel::{unbounded, Receiver},
+        net::TcpListener,
+    };
+
+    use crate::{
+        connect_or_relay, FallbackStream, RelayAddr, RelaySocket, Server, SignalClient,
+        SignalTransport,
+    };
+
+    type BindFuture = Pin<Box<dyn Future<Output = karma_p2p_webrtc::Result<WebrtcSocket>> + Send>>;
+
+    #[allow(clippy::type_complexity)]
+    fn node(
+        id: &str,
+        config: Config,
+    ) -> (
+        Node<SignalTransport<WebrtcSocket, impl FnMut(&str) -> BindFuture + Unpin>>,
+        Receiver<(String, WebrtcStream)>,
+    ) {
+        let (tx, rx) = unbounded();
+
+        let bind = move |remote: &str| -> BindFuture {
+            let tx = tx.clone();
+            let remote = String::from(remote);
+
+            Box::pin(async move {
+                let socket = WebrtcSocket::bind(WebrtcAddr::Bootstrap(Vec::new())).await?;
+
+                let label = WebrtcAddr::Label(String::from("node"));
+                let stream = socket.connect(label, 1).await?;
+
+                let _ = tx.try_send((remote, stream));
+
+                Ok(socket)
+            })
+        };
+
+        let transport = SignalTransport::new(id, "room", bind);
+
+        (Node::new(config, id, transport), rx)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn nodes_through_bootstrap() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        smol::spawn(async move { Server::new().serve_tcp(listener).await }).detach();
+
+        let config = Config {
+            bootstrap_nodes: vec![addr],
+            ..Default::default()
+        };
+
+        let (mut a, a_rx) = node("a", config.clone());
+        let (mut b, b_rx) = node("b", config);
+
+        let test = async {
+            let (remote, mut sa) = a_rx.recv().await.unwrap();
+            assert_eq!(remote, "b");
+
+            let (remote, mut sb) = b_rx.recv().await.unwrap();
+            assert_eq!(remote, "a");
+
+            sa.write_all(b"hello").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        };
+
+        let driver = async {
+            let a_events = async {
+                loop {
+                    if let NodeEvent::PeerFailed(p, e) = a.next_event().await {
+                        panic!("peer {} failed: {:?}", p, e);
+                    }
+                }
+            };
+
+            let b_events = async {
+                loop {
+                    if let NodeEvent::PeerFailed(p, e) = b.next_event().await {
+                        panic!("peer {} failed: {:?}", p, e);
+                    }
+                }
+            };
+
+            future::zip(a_events, b_events).await;
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("connection between nodes timeout");
+
+        assert_eq!(a.peers().collect::<Vec<_>>(), vec!["b"]);
+        assert_eq!(b.peers().collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fallback_through_forwarding_node() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let signal = listener.local_addr().unwrap();
+
+        smol::spawn(async move { Server::new().serve_tcp(listener).await }).detach();
+
+        // Pick a free port for forwarding.
+        let forward = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .unwrap();
+
+        // Third node only forwards.
+        let config = Config {
+            bootstrap_nodes: vec![signal],
+            forward_listen: vec![forward],
+            enable_forward: true,
+        };
+
+        let (mut c, _) = node("c", config);
+
+        loop {
+            match c.next_event().await {
+                NodeEvent::Listening(a) if a == forward => break,
+                NodeEvent::ListenFailed(a, e) => panic!("listen {} failed: {:?}", a, e),
+                _ => {}
+            }
+        }
+
+        let (ka, kb) = (Keypair::generate(), Keypair::generate());
+        let (ia, ib) = (ka.peer_id().to_string(), kb.peer_id().to_string());
+
+        let mut sig_a = SignalClient::<RelayAddr>::connect(signal, &ia)
+            .await
+            .unwrap();
+        let mut sig_b = SignalClient::<RelayAddr>::connect(signal, &ib)
+            .await
+            .unwrap();
+
+        let bootstrap = |keypair: &Keypair| RelayAddr::Bootstrap {
+            relay: forward,
+            keypair: keypair.clone(),
+        };
+
+        let mut ra = RelaySocket::bind(bootstrap(&ka)).await.unwrap();
+        let mut rb = RelaySocket::bind(bootstrap(&kb)).await.unwrap();
+
+        let (ea, eb) = future::zip(
+            establish(&mut ra, &mut sig_a, &ib, ia < ib),
+            establish(&mut rb, &mut sig_b, &ia, ib < ia),
+        )
+        .await;
+
+        ea.unwrap();
+        eb.unwrap();
+
+        // Addresses of direct sockets are never exchanged, so they can't connect.
+        let bootstrap = WebrtcAddr::Bootstrap(Vec::new());
+
+        let da = WebrtcSocket::bind(bootstrap.clone()).await.unwrap();
+        let db = WebrtcSocket::bind(bootstrap).await.unwrap();
+
+        let label = WebrtcAddr::Label(String::from("data"));
+        let wait = Duration::from_secs(1);
+
+        let test = async {
+            let (sa, sb) = future::zip(
+                connect_or_relay(&da, label.clone(), &ra, "data", 1, wait),
+                connect_or_relay(&db, label, &rb, "data", 1, wait),
+            )
+            .await;
+
+            let (mut sa, mut sb) = (sa.unwrap(), sb.unwrap());
+            assert!(matches!(sa, FallbackStream::Relayed(_)));
+            assert!(matches!(sb, FallbackStream::Relayed(_)));
+
+            sa.write_all(b"hello").await.unwrap();
+            sa.flush().await.unwrap();
+
+            let mut buf = [0u8; 5];
+            sb.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+
+            let path = sa.relay_path().unwrap();
+            assert_eq!((path.relay, &path.remote), (forward, &ib));
+        };
+
+        let driver = async {
+            loop {
+                c.next_event().await;
+            }
+        };
+
+        let res = tokio::time::timeout(Duration::from_secs(30), future::or(test, driver)).await;
+
+        res.expect("relay fallback timeout");
+    }
+}
@@ -0,0 +1,104 @@
+use async_channel::{bounded, Receiver, Sender};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, WebSocket};
+
+use crate::{ClientMessage, Error, Result, ServerMessage, MAX_QUEUED_MESSAGES};
+
+/// Browser websocket of [`SignalClient`](crate::SignalClient), closed on drop.
+pub struct WebSocketIo {
+    ws: WebSocket,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+}
+
+impl WebSocketIo {
+    /// Open websocket to `url`, messages are sent to `Sender` and received from
+    /// `Receiver`.
+    pub(crate) async fn open(
+        url: &str,
+    ) -> Result<(Self, Sender<ClientMessage>, Receiver<ServerMessage>)> {
+        let ws = WebSocket::new(url).map_err(js_error)?;
+
+        let (open_tx, open_rx) = bounded::<()>(1);
+        let (out_tx, out_rx) = bounded::<ClientMessage>(MAX_QUEUED_MESSAGES);
+        let (in_tx, in_rx) = bounded(MAX_QUEUED_MESSAGES);
+
+        let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+            let _ = open_tx.try_send(());
+        }) as Box<dyn FnMut(JsValue)>);
+
+        let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let text = match e.data().as_string() {
+                Some(t) => t,
+                None => return,
+            };
+
+            match serde_json::from_str::<ServerMessage>(&text) {
+                Ok(m) => {
+                    // Browser can't hold server back, so messages not read in time
+                    // end receiving.
+                    if in_tx.try_send(m).is_err() {
+                        in_tx.close();
+                    }
+                }
+                Err(e) => log::warn!("Bad message from signaling server: {:?}", e),
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+
+        // Error is always followed by close, which ends both opening and receiving.
+        let (closing_open, closing_in) = (open_rx.clone(), in_rx.clone());
+        let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+            closing_open.close();
+            closing_in.close();
+        }) as Box<dyn FnMut(JsValue)>);
+
+        ws.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        ws.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        let io = Self {
+            ws: ws.clone(),
+            _on_open: on_open,
+            _on_message: on_message,
+            _on_close: on_close,
+        };
+
+        if open_rx.recv().await.is_err() || in_rx.is_closed() {
+            return Err(Error::ErrChannelClosed);
+        }
+
+        wasm_bindgen_futures::spawn_local(async move {
+            while let Ok(message) = out_rx.recv().await {
+                let text = match serde_json::to_string(&message) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        log::warn!("Serialize signaling message failed: {:?}", e);
+                        continue;
+                    }
+                };
+
+                if let Err(e) = ws.send_with_str(&text) {
+                    log::warn!("Send to signaling server failed: {:?}", e);
+                    break;
+                }
+            }
+        });
+
+        Ok((io, out_tx, in_rx))
+    }
+}
+
+impl Drop for WebSocketIo {
+    fn drop(&mut self) {
+        self.ws.set_onopen(None);
+        self.ws.set_onmessage(None);
+        self.ws.set_onclose(None);
+
+        let _ = self.ws.close();
+    }
+}
+
+fn js_error(e: JsValue) -> Error {
+    Error::SocketError(format!("{:?}", e))
+}